@@ -5,6 +5,9 @@
 //! ただし、a?^nとa^nは、a?とaのn回の繰り返し。
 //! 計測は幅優先と深さ優先で行う。
 //!
+//! また、コンパイル済みの正規表現について、命令列を解釈する評価器と
+//! スレッデッドコードに変換した評価器 (いずれも深さ優先) の検索時間を比較する。
+//!
 //! ## n = 3の場合の例
 //!
 //! - 正規表現: a?a?a?aaa
@@ -22,7 +25,7 @@
 //! 実行後は、target/criterion/reports/index.htmlというファイルが生成されるため、
 //! それをWebブラウザで閲覧する。
 use criterion::{criterion_group, criterion_main, Criterion};
-use regex::do_matching;
+use regex::{do_matching, Backend, RegexBuilder};
 use std::time::Duration;
 
 /// (計測のid、a?^n a^nという正規表現、文字列)というタプル
@@ -81,5 +84,24 @@ fn width_first(c: &mut Criterion) {
     }
 }
 
-criterion_group!(benches, width_first, depth_first);
-criterion_main!(benches);
\ No newline at end of file
+/// コンパイル済みの正規表現で検索する時間を計測
+fn backend(c: &mut Criterion, name: &str, backend: Backend) {
+    let mut g = c.benchmark_group(name);
+    g.measurement_time(Duration::from_secs(12));
+
+    for i in INPUTS {
+        let re = RegexBuilder::new(i.1).backend(backend).build().unwrap();
+        g.bench_with_input(i.0, i.2, |b, line| b.iter(|| re.find(line)));
+    }
+}
+
+fn interpreter(c: &mut Criterion) {
+    backend(c, "Interpreter", Backend::Interpreter);
+}
+
+fn threaded(c: &mut Criterion) {
+    backend(c, "Threaded", Backend::Threaded);
+}
+
+criterion_group!(benches, width_first, depth_first, interpreter, threaded);
+criterion_main!(benches);
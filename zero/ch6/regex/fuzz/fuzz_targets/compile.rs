@@ -0,0 +1,15 @@
+//! パーサ、コード生成器、検証器のファジング
+//!
+//! 任意の文字列をコンパイルし、成功した場合はバイナリ形式への変換と読み込みで
+//! 同じプログラムに戻ることを確認する
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use regex::Program;
+
+fuzz_target!(|expr: &str| {
+    if let Ok(prog) = regex::compile(expr) {
+        let loaded = Program::from_bytes(&prog.to_bytes()).unwrap();
+        assert_eq!(prog, loaded);
+    }
+});
@@ -0,0 +1,41 @@
+//! 評価器のファジング
+//!
+//! 任意の正規表現と文字列でマッチングを行い、
+//! エラーにならないことと、深さ優先と幅優先の結果が一致することを確認する。
+//! 後方参照などを含む式は幅優先では評価できないため、エラーとなることのみを確認する
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use regex::Error;
+
+/// 正規表現の長さの上限
+const MAX_EXPR_LEN: usize = 24;
+
+/// 文字列の長さの上限
+const MAX_LINE_LEN: usize = 12;
+
+/// 限量子の数の上限
+const MAX_QUANTIFIERS: usize = 4;
+
+fuzz_target!(|input: (&str, &str)| {
+    let (expr, line) = input;
+
+    // 評価器はバックトラックを行うため、`a++++` のような入れ子の限量子では
+    // 文字列長に対して指数時間となる。タイムアウトとならないよう入力を制限する
+    let quantifiers = expr.chars().filter(|c| matches!(c, '+' | '*' | '?')).count();
+    if expr.chars().count() > MAX_EXPR_LEN
+        || line.chars().count() > MAX_LINE_LEN
+        || quantifiers > MAX_QUANTIFIERS
+    {
+        return;
+    }
+
+    if let Ok(prog) = regex::compile(expr) {
+        let depth = prog.do_matching(line, true).unwrap();
+        match prog.do_matching(line, false) {
+            Ok(width) => assert_eq!(depth, width, "expr = {expr:?}, line = {line:?}"),
+            Err(Error::RequiresBacktracking) if prog.requires_backtracking() => (),
+            Err(e) => panic!("expr = {expr:?}, line = {line:?}: {e}"),
+        }
+    }
+});
@@ -0,0 +1,31 @@
+//! バイナリ形式の読み込みのファジング
+//!
+//! 任意のバイト列を読み込み、検証を通過したプログラムは
+//! 評価時にエラーにならないことを確認する。
+//! 後方参照などを含むプログラムは、深さ優先探索でのみ評価する
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use regex::{Error, Program};
+
+const LINES: &[&str] = &["", "a", "ab", "abcabc", "あいう"];
+
+fuzz_target!(|bytes: &[u8]| {
+    if let Ok(prog) = Program::from_bytes(bytes) {
+        // 評価時間を抑えるため、命令数を制限する
+        if prog.code().len() > 64 {
+            return;
+        }
+        for line in LINES {
+            let depth = prog.do_matching(line, true);
+            let width = prog.do_matching(line, false);
+            match (depth, width) {
+                (Ok(depth), Ok(width)) => assert_eq!(depth, width, "line = {line:?}"),
+                // 最左最長一致のプログラムは、深さ優先探索でも評価できない
+                (Ok(_) | Err(Error::RequiresBacktracking), Err(Error::RequiresBacktracking))
+                    if prog.requires_backtracking() => {}
+                (depth, width) => panic!("line = {line:?}: {depth:?}, {width:?}"),
+            }
+        }
+    }
+});
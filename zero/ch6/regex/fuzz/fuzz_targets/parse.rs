@@ -0,0 +1,12 @@
+//! パーサのファジング
+//!
+//! 任意の文字列をパースし、パースに成功した場合は正規形への変換が冪等であることを確認する
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|expr: &str| {
+    if let Ok(pattern) = regex::canonicalize(expr) {
+        assert_eq!(regex::canonicalize(&pattern).unwrap(), pattern);
+    }
+});
@@ -0,0 +1,50 @@
+#!/usr/bin/env bash
+
+# GNU grep の出力を期待値として tests/expected に保存する
+# 本ツールの構文は拡張正規表現のサブセットのため、grep -E を用いる
+
+set -u
+
+ROOT="tests/inputs"
+OUT_DIR="tests/expected"
+
+[[ ! -d "$OUT_DIR" ]] && mkdir -p "$OUT_DIR"
+
+EMPTY="$ROOT/empty.txt"
+FOX="$ROOT/fox.txt"
+BUSTLE="$ROOT/the-bustle.txt"
+DIR="$ROOT/dir"
+ALL="$BUSTLE $EMPTY $FOX"
+
+grep -E    'the' $BUSTLE > "$OUT_DIR/bustle.the.out"
+grep -E -i 'the' $BUSTLE > "$OUT_DIR/bustle.the.i.out"
+grep -E -v 'the' $BUSTLE > "$OUT_DIR/bustle.the.v.out"
+grep -E -c 'the' $BUSTLE > "$OUT_DIR/bustle.the.c.out"
+grep -E -n 'the' $BUSTLE > "$OUT_DIR/bustle.the.n.out"
+grep -E -vn 'the' $BUSTLE > "$OUT_DIR/bustle.the.vn.out"
+grep -E -on '(in|up)+' $BUSTLE > "$OUT_DIR/bustle.inup.on.out"
+grep -E -e 'death' -e 'love' $BUSTLE > "$OUT_DIR/bustle.death.love.out"
+grep -E -c 'the' $ALL > "$OUT_DIR/all.the.c.out"
+grep -E -l 'the' $ALL > "$OUT_DIR/all.the.l.out"
+grep -E -n 'the|fox' $ALL > "$OUT_DIR/all.thefox.n.out"
+grep -E -r -i 'the' $DIR | sort > "$OUT_DIR/dir.the.ri.out"
+grep -E -o -i 'th(e|o)' $DIR/sub/b.txt > "$OUT_DIR/b.theo.oi.out"
+grep -E 'o' < $FOX > "$OUT_DIR/fox.o.stdin.out"
+
+# 色付けと前後の行
+grep -E --color=always 'the|fox' $ALL > "$OUT_DIR/all.thefox.color.out"
+grep -E --color=always -n -C1 'the' $BUSTLE $FOX > "$OUT_DIR/all.the.n.C1.color.out"
+grep -E --color=always -c 'the' $ALL > "$OUT_DIR/all.the.c.color.out"
+grep -E --color=always -l 'the' $ALL > "$OUT_DIR/all.the.l.color.out"
+grep -E --color=always -o 'in|up' $BUSTLE > "$OUT_DIR/bustle.inup.o.color.out"
+grep -E --color=always -v -A1 'the' $BUSTLE > "$OUT_DIR/bustle.the.v.A1.color.out"
+grep -E -n -A1 -B2 'death|love' $BUSTLE > "$OUT_DIR/bustle.deathlove.n.A1.B2.out"
+grep -E -C1 'Th' $BUSTLE $FOX > "$OUT_DIR/all.Th.C1.out"
+grep -E -A0 'Th' $BUSTLE > "$OUT_DIR/bustle.Th.A0.out"
+grep -E -o -A1 'Th|Un' $BUSTLE > "$OUT_DIR/bustle.ThUn.o.A1.out"
+grep -E -v -B1 'e' $BUSTLE > "$OUT_DIR/bustle.e.v.B1.out"
+
+# POSIX の構文と最左最長一致
+grep -E -o 'th(e|ere)?|[[:upper:]][a-z]{2,}' $BUSTLE > "$OUT_DIR/bustle.posix.E.o.out"
+grep -G -n 'e\(a\|ar\)[[:alpha:]]*$' $BUSTLE > "$OUT_DIR/bustle.posix.G.n.out"
+grep -E --color=always 'in|ing|^[^ ]+' $BUSTLE > "$OUT_DIR/bustle.posix.E.color.out"
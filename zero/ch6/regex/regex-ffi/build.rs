@@ -0,0 +1,15 @@
+//! C のヘッダ zero_regex.h を OUT_DIR に生成する
+//!
+//! リポジトリの include/zero_regex.h は生成したものと同じであることを tests/c_api.rs で検査する
+use std::{env, error::Error, path::PathBuf};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let dir = env::var("CARGO_MANIFEST_DIR")?;
+    let out = PathBuf::from(env::var("OUT_DIR")?).join("zero_regex.h");
+    println!("cargo:rerun-if-changed=src/lib.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+
+    let config = cbindgen::Config::from_file(format!("{dir}/cbindgen.toml"))?;
+    cbindgen::generate_with_config(&dir, config)?.write_to_file(out);
+    Ok(())
+}
@@ -0,0 +1,86 @@
+#ifndef ZERO_REGEX_H
+#define ZERO_REGEX_H
+
+/* cbindgen で生成したファイル。直接編集しないこと */
+
+#include <stddef.h>
+#include <stdint.h>
+
+// 大文字と小文字を区別しない
+#define REGEX_IGNORE_CASE 1
+
+// 最左最長一致で検索する
+#define REGEX_LONGEST 2
+
+// POSIX 拡張正規表現 (ERE) として解釈する
+#define REGEX_ERE 4
+
+// POSIX 基本正規表現 (BRE) として解釈する
+#define REGEX_BRE 8
+
+// コンパイル済みの正規表現
+//
+// C からは不透明な型として扱い、[`regex_free`] で解放する
+typedef struct Regex Regex;
+
+// マッチした範囲
+//
+// start と end は検索対象のバイト列のインデックス
+typedef struct RegexMatch {
+  size_t start;
+  size_t end;
+} RegexMatch;
+
+// 正規表現をコンパイル
+//
+// flags には `REGEX_IGNORE_CASE` などを論理和で指定する。
+// 失敗した場合は NULL を返す
+//
+// # Safety
+//
+// pattern は NUL 終端された UTF-8 の文字列であること
+struct Regex *regex_compile(const char *pattern, uint32_t flags);
+
+// text の先頭から len バイトの中にマッチする箇所があるかを判定
+//
+// マッチすれば 1、しなければ 0、失敗した場合は -1 を返す。
+// UTF-8 として不正なバイトは U+FFFD とみなす
+//
+// # Safety
+//
+// re は [`regex_compile`] が返した解放前のポインタであり、
+// text は NULL でなければ len バイト読み出せること
+int regex_is_match(const struct Regex *re, const char *text, size_t len);
+
+// text の先頭から len バイトのうち、start バイト目以降で最も左にあるマッチを探す
+//
+// マッチすれば範囲を out に格納して 1、しなければ 0、失敗した場合は -1 を返す。
+// start が文字の境界でない場合は、次の文字から検索する
+//
+// # Safety
+//
+// re は [`regex_compile`] が返した解放前のポインタであり、
+// text は NULL でなければ len バイト読み出せること。
+// out は NULL でなければ書き込めること
+int regex_find(const struct Regex *re,
+               const char *text,
+               size_t len,
+               size_t start,
+               struct RegexMatch *out);
+
+// [`regex_compile`] が返した正規表現を解放する
+//
+// NULL の場合は何もしない
+//
+// # Safety
+//
+// re は [`regex_compile`] が返したポインタであり、まだ解放していないこと
+void regex_free(struct Regex *re);
+
+// このスレッドで直前に失敗した関数のエラーメッセージ
+//
+// 失敗した関数がなければ NULL を返す。
+// 返した文字列は、このスレッドで次に関数が失敗するまで有効
+const char *regex_last_error(void);
+
+#endif  /* ZERO_REGEX_H */
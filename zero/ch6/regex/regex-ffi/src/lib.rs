@@ -0,0 +1,286 @@
+//! # 正規表現エンジンの C API
+//!
+//! `cdylib` と `staticlib` として C から利用するための関数を公開する。
+//! ヘッダ `include/zero_regex.h` は cbindgen で生成し、リポジトリに含める。
+//!
+//! ## 利用例
+//!
+//! ```c
+//! #include "zero_regex.h"
+//!
+//! Regex *re = regex_compile("b(cd)+", 0);
+//! if (re == NULL) {
+//!     fprintf(stderr, "%s\n", regex_last_error());
+//!     return 1;
+//! }
+//! RegexMatch m;
+//! if (regex_find(re, "abcdcde", 7, 0, &m) == 1) {
+//!     printf("%zu..%zu\n", m.start, m.end); // 1..6
+//! }
+//! regex_free(re);
+//! ```
+//!
+//! 失敗した関数は NULL か -1 を返し、エラーメッセージを [`regex_last_error`] で取得できる。
+//! エラーメッセージはスレッドごとに保持する。
+use regex::{Error, RegexBuilder, Syntax};
+use std::{
+    cell::RefCell,
+    ffi::{c_char, c_int, CStr, CString},
+    panic::{self, AssertUnwindSafe},
+    ptr, slice,
+};
+
+/// 大文字と小文字を区別しない
+pub const REGEX_IGNORE_CASE: u32 = 1;
+
+/// 最左最長一致で検索する
+pub const REGEX_LONGEST: u32 = 2;
+
+/// POSIX 拡張正規表現 (ERE) として解釈する
+pub const REGEX_ERE: u32 = 4;
+
+/// POSIX 基本正規表現 (BRE) として解釈する
+pub const REGEX_BRE: u32 = 8;
+
+const KNOWN_FLAGS: u32 = REGEX_IGNORE_CASE | REGEX_LONGEST | REGEX_ERE | REGEX_BRE;
+
+/// コンパイル済みの正規表現
+///
+/// C からは不透明な型として扱い、[`regex_free`] で解放する
+pub struct Regex(regex::Regex);
+
+/// マッチした範囲
+///
+/// start と end は検索対象のバイト列のインデックス
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RegexMatch {
+    pub start: usize,
+    pub end: usize,
+}
+
+thread_local! {
+    // 直前に失敗した関数のエラーメッセージ
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// エラーメッセージを保存
+fn set_error(msg: impl ToString) {
+    // C の文字列に含められない NUL は取り除く
+    let msg = msg.to_string().replace('\0', "");
+    LAST_ERROR.with(|e| *e.borrow_mut() = CString::new(msg).ok());
+}
+
+/// f を実行し、エラーやパニックの場合はメッセージを保存して on_error を返す
+///
+/// Regex は検索で状態を変えないため、パニックした後も使い続けられる
+fn guard<T>(on_error: T, f: impl FnOnce() -> Result<T, String>) -> T {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(value)) => value,
+        Ok(Err(msg)) => {
+            set_error(msg);
+            on_error
+        }
+        Err(_) => {
+            set_error("panic in the regex engine");
+            on_error
+        }
+    }
+}
+
+/// 検索対象のバイト列を得る
+///
+/// # Safety
+///
+/// text は NULL でなければ len バイト読み出せること
+unsafe fn bytes<'a>(text: *const c_char, len: usize) -> Result<&'a [u8], String> {
+    if text.is_null() {
+        if len == 0 {
+            return Ok(&[]);
+        }
+        return Err("text is null".to_string());
+    }
+    Ok(slice::from_raw_parts(text.cast(), len))
+}
+
+/// 正規表現をコンパイル
+///
+/// flags には `REGEX_IGNORE_CASE` などを論理和で指定する。
+/// 失敗した場合は NULL を返す
+///
+/// # Safety
+///
+/// pattern は NUL 終端された UTF-8 の文字列であること
+#[no_mangle]
+pub unsafe extern "C" fn regex_compile(pattern: *const c_char, flags: u32) -> *mut Regex {
+    guard(ptr::null_mut(), || {
+        if pattern.is_null() {
+            return Err("pattern is null".to_string());
+        }
+        if flags & !KNOWN_FLAGS != 0 || flags & REGEX_ERE != 0 && flags & REGEX_BRE != 0 {
+            return Err(format!("invalid flags: {flags:#x}"));
+        }
+        let pattern = CStr::from_ptr(pattern)
+            .to_str()
+            .map_err(|e| format!("pattern is not UTF-8: {e}"))?;
+
+        let syntax = if flags & REGEX_ERE != 0 {
+            Syntax::Ere
+        } else if flags & REGEX_BRE != 0 {
+            Syntax::Bre
+        } else {
+            Syntax::Native
+        };
+        let re = RegexBuilder::new(pattern)
+            .syntax(syntax)
+            .ignore_case(flags & REGEX_IGNORE_CASE != 0)
+            .longest(flags & REGEX_LONGEST != 0)
+            .build()
+            .map_err(|e: Error| format!("{e:#}"))?;
+        Ok(Box::into_raw(Box::new(Regex(re))))
+    })
+}
+
+/// text の先頭から len バイトの中にマッチする箇所があるかを判定
+///
+/// マッチすれば 1、しなければ 0、失敗した場合は -1 を返す。
+/// UTF-8 として不正なバイトは U+FFFD とみなす
+///
+/// # Safety
+///
+/// re は [`regex_compile`] が返した解放前のポインタであり、
+/// text は NULL でなければ len バイト読み出せること
+#[no_mangle]
+pub unsafe extern "C" fn regex_is_match(
+    re: *const Regex,
+    text: *const c_char,
+    len: usize,
+) -> c_int {
+    let mut m = RegexMatch::default();
+    regex_find(re, text, len, 0, &mut m)
+}
+
+/// text の先頭から len バイトのうち、start バイト目以降で最も左にあるマッチを探す
+///
+/// マッチすれば範囲を out に格納して 1、しなければ 0、失敗した場合は -1 を返す。
+/// start が文字の境界でない場合は、次の文字から検索する
+///
+/// # Safety
+///
+/// re は [`regex_compile`] が返した解放前のポインタであり、
+/// text は NULL でなければ len バイト読み出せること。
+/// out は NULL でなければ書き込めること
+#[no_mangle]
+pub unsafe extern "C" fn regex_find(
+    re: *const Regex,
+    text: *const c_char,
+    len: usize,
+    start: usize,
+    out: *mut RegexMatch,
+) -> c_int {
+    guard(-1, || {
+        let Some(Regex(re)) = re.as_ref() else {
+            return Err("regex is null".to_string());
+        };
+        let text = bytes(text, len)?;
+        let mut input = regex::Input::new();
+        re.load(&mut input, text);
+        match re.find_in(&input, start).map_err(|e| format!("{e:#}"))? {
+            Some(m) => {
+                if let Some(out) = out.as_mut() {
+                    *out = RegexMatch {
+                        start: m.start(),
+                        end: m.end(),
+                    };
+                }
+                Ok(1)
+            }
+            None => Ok(0),
+        }
+    })
+}
+
+/// [`regex_compile`] が返した正規表現を解放する
+///
+/// NULL の場合は何もしない
+///
+/// # Safety
+///
+/// re は [`regex_compile`] が返したポインタであり、まだ解放していないこと
+#[no_mangle]
+pub unsafe extern "C" fn regex_free(re: *mut Regex) {
+    if !re.is_null() {
+        drop(Box::from_raw(re));
+    }
+}
+
+/// このスレッドで直前に失敗した関数のエラーメッセージ
+///
+/// 失敗した関数がなければ NULL を返す。
+/// 返した文字列は、このスレッドで次に関数が失敗するまで有効
+#[no_mangle]
+pub extern "C" fn regex_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ref().map_or(ptr::null(), |s| s.as_ptr()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn last_error() -> String {
+        unsafe { CStr::from_ptr(regex_last_error()) }
+            .to_str()
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn test_api() {
+        unsafe {
+            let re = regex_compile(c"b(cd)+".as_ptr(), 0);
+            assert!(!re.is_null());
+            let text = "abcdcdbcd";
+            let mut m = RegexMatch::default();
+            assert_eq!(
+                regex_find(re, text.as_ptr().cast(), text.len(), 0, &mut m),
+                1
+            );
+            assert_eq!(m, RegexMatch { start: 1, end: 6 });
+            assert_eq!(
+                regex_find(re, text.as_ptr().cast(), text.len(), 2, &mut m),
+                1
+            );
+            assert_eq!(m, RegexMatch { start: 6, end: 9 });
+            assert_eq!(regex_is_match(re, text.as_ptr().cast(), 3), 0);
+            assert_eq!(regex_is_match(re, ptr::null(), 0), 0);
+            regex_free(re);
+
+            let re = regex_compile(c"[[:digit:]]+".as_ptr(), REGEX_ERE | REGEX_LONGEST);
+            assert_eq!(regex_is_match(re, c"a12".as_ptr(), 3), 1);
+            regex_free(re);
+            regex_free(ptr::null_mut());
+        }
+    }
+
+    #[test]
+    fn test_error() {
+        unsafe {
+            assert!(regex_compile(c"a(b".as_ptr(), 0).is_null());
+            assert_eq!(
+                last_error(),
+                "Error: invalid pattern: ParseError: no right parenthesis"
+            );
+            assert!(regex_compile(c"a".as_ptr(), REGEX_ERE | REGEX_BRE).is_null());
+            assert_eq!(last_error(), "invalid flags: 0xc");
+            assert!(regex_compile(ptr::null(), 0).is_null());
+            assert_eq!(last_error(), "pattern is null");
+            assert_eq!(regex_is_match(ptr::null(), c"a".as_ptr(), 1), -1);
+            assert_eq!(last_error(), "regex is null");
+
+            // エラーメッセージはスレッドごとに保持する
+            std::thread::spawn(|| assert!(regex_last_error().is_null()))
+                .join()
+                .unwrap();
+        }
+    }
+}
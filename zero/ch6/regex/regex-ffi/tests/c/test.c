@@ -0,0 +1,58 @@
+/* C API のテスト。tests/c_api.rs がビルドして実行する */
+#include <assert.h>
+#include <stdio.h>
+#include <string.h>
+
+#include "zero_regex.h"
+
+static int find(const Regex *re, const char *text, size_t start, RegexMatch *m) {
+    return regex_find(re, text, strlen(text), start, m);
+}
+
+int main(void) {
+    RegexMatch m;
+
+    /* 検索 */
+    Regex *re = regex_compile("b(cd)+", 0);
+    assert(re != NULL);
+    assert(find(re, "abcdcde", 0, &m) == 1);
+    assert(m.start == 1 && m.end == 6);
+    assert(find(re, "abcdcdbcd", 2, &m) == 1);
+    assert(m.start == 6 && m.end == 9);
+    assert(find(re, "acd", 0, &m) == 0);
+    assert(regex_is_match(re, "xbcd", 4) == 1);
+    assert(regex_is_match(re, "xbcd", 3) == 0);
+    regex_free(re);
+
+    /* UTF-8 のバイト位置と、不正なバイト */
+    re = regex_compile("い+", REGEX_IGNORE_CASE);
+    assert(find(re, "あいいう", 0, &m) == 1);
+    assert(m.start == 3 && m.end == 9);
+    assert(regex_find(re, "\xff\xe3\x81\x84", 4, 0, &m) == 1);
+    assert(m.start == 1 && m.end == 4);
+    regex_free(re);
+
+    /* POSIX の構文と最左最長一致 */
+    re = regex_compile("a|ab", REGEX_ERE | REGEX_LONGEST);
+    assert(find(re, "xabc", 0, &m) == 1);
+    assert(m.start == 1 && m.end == 3);
+    regex_free(re);
+
+    /* 空文字列にマッチする式の繰り返し */
+    re = regex_compile("(a?)*b", 0);
+    assert(re != NULL);
+    assert(find(re, "xaab", 0, &m) == 1);
+    assert(m.start == 1 && m.end == 4);
+    regex_free(re);
+
+    /* エラー */
+    assert(regex_compile("a(b", 0) == NULL);
+    assert(strcmp(regex_last_error(), "Error: invalid pattern: ParseError: no right parenthesis") == 0);
+    assert(regex_compile("(a)\\1", REGEX_LONGEST) == NULL);
+    assert(strcmp(regex_last_error(), "Error: backreferences and lookaround require the depth-first evaluator") == 0);
+    assert(regex_is_match(NULL, "a", 1) == -1);
+    regex_free(NULL);
+
+    puts("ok");
+    return 0;
+}
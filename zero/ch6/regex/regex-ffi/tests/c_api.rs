@@ -0,0 +1,48 @@
+//! tests/c/test.c をヘッダと静的ライブラリでビルドし、実行する
+use std::{env, fs, path::PathBuf, process::Command};
+
+#[test]
+fn test_header() {
+    // リポジトリのヘッダが build.rs で生成したものと同じか
+    let manifest = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let generated = PathBuf::from(env!("OUT_DIR")).join("zero_regex.h");
+    let expected = fs::read_to_string(&generated).unwrap();
+    let actual = fs::read_to_string(manifest.join("include/zero_regex.h")).unwrap();
+    assert!(
+        actual == expected,
+        "include/zero_regex.h is out of date; copy {} over it",
+        generated.display()
+    );
+}
+
+#[test]
+fn test_c_program() {
+    let manifest = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    // テストの実行ファイルは target/<profile>/deps に置かれる
+    let exe = env::current_exe().unwrap();
+    let lib_dir = exe.parent().unwrap().parent().unwrap();
+    let out = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("c_api_test");
+
+    let cc = env::var("CC").unwrap_or_else(|_| "cc".to_string());
+    let status = Command::new(cc)
+        .arg("-std=c99")
+        .arg("-Wall")
+        .arg("-Werror")
+        .arg("-I")
+        .arg(manifest.join("include"))
+        .arg(manifest.join("tests/c/test.c"))
+        .arg(lib_dir.join("libzero_regex.a"))
+        .args(["-lpthread", "-ldl", "-lm", "-o"])
+        .arg(&out)
+        .status()
+        .expect("failed to run the C compiler");
+    assert!(status.success());
+
+    let output = Command::new(&out).output().unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(output.stdout, b"ok\n");
+}
@@ -0,0 +1,137 @@
+//! # コンパイル時に正規表現をコンパイルするマクロ
+//!
+//! `regex!("...")` は、パースとコード生成をコンパイル時に行い、
+//! 生成した命令列を `static` として埋め込む。
+//! 実行時にはパースを行わないため、定数のパターンでは `regex::Error` を扱う必要がない。
+//!
+//! ## 利用例
+//!
+//! ```
+//! use regex_macros::regex;
+//! let re: &'static regex::Regex = regex!("a(bc)+|c(def)*");
+//! assert_eq!(re.find("xxabcbcx").unwrap().unwrap().range(), 2..7);
+//! ```
+//!
+//! 正規表現にエラーがある場合はコンパイルエラーとなる。
+//!
+//! ```compile_fail
+//! use regex_macros::regex;
+//! let re = regex!("a(b");
+//! ```
+use proc_macro::TokenStream;
+use proc_macro2::{Literal, Span, TokenStream as TokenStream2};
+use quote::quote;
+use regex::{CharClass, Instruction, Look};
+use syn::{parse_macro_input, LitStr};
+
+/// 正規表現をコンパイル時にコンパイルし、`&'static regex::Regex` を返す
+///
+/// 正規表現は本ライブラリ独自の構文で解釈し、深さ優先探索で評価する
+#[proc_macro]
+pub fn regex(input: TokenStream) -> TokenStream {
+    let lit = parse_macro_input!(input as LitStr);
+    match expand(&lit) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn expand(lit: &LitStr) -> syn::Result<TokenStream2> {
+    let expr = lit.value();
+    let prog = regex::compile(&expr)
+        .map_err(|e| syn::Error::new(error_span(lit, &expr, e.position()), format!("{e:#}")))?;
+
+    let len = prog.code().len();
+    let code = prog.code().iter().map(instruction);
+    Ok(quote! {{
+        static CODE: [::regex::Instruction; #len] = [#(#code),*];
+        static RE: ::std::sync::LazyLock<::regex::Regex> = ::std::sync::LazyLock::new(|| {
+            ::regex::Regex::from_program(::regex::Program::from_static(&CODE), true)
+        });
+        &*RE
+    }})
+}
+
+/// 命令を、その命令を生成する式に変換
+fn instruction(inst: &Instruction) -> TokenStream2 {
+    match inst {
+        Instruction::Char(c) => {
+            let c = Literal::character(*c);
+            quote!(::regex::Instruction::Char(#c))
+        }
+        Instruction::Match => quote!(::regex::Instruction::Match),
+        Instruction::Jump(addr) => quote!(::regex::Instruction::Jump(#addr)),
+        Instruction::Split(addr1, addr2) => quote!(::regex::Instruction::Split(#addr1, #addr2)),
+        Instruction::Class(class) => {
+            let class = char_class(class);
+            quote!(::regex::Instruction::Class(#class))
+        }
+        Instruction::End => quote!(::regex::Instruction::End),
+        Instruction::LineStart => quote!(::regex::Instruction::LineStart),
+        Instruction::LineEnd => quote!(::regex::Instruction::LineEnd),
+        Instruction::Save(slot) => quote!(::regex::Instruction::Save(#slot)),
+        Instruction::Backref(n) => quote!(::regex::Instruction::Backref(#n)),
+        Instruction::Look(look, addr) => {
+            let look = match look {
+                Look::Ahead => quote!(Ahead),
+                Look::NotAhead => quote!(NotAhead),
+                Look::Behind => quote!(Behind),
+                Look::NotBehind => quote!(NotBehind),
+            };
+            quote!(::regex::Instruction::Look(::regex::Look::#look, #addr))
+        }
+        Instruction::AtomicStart => quote!(::regex::Instruction::AtomicStart),
+        Instruction::AtomicEnd => quote!(::regex::Instruction::AtomicEnd),
+        Instruction::Mark(n) => quote!(::regex::Instruction::Mark(#n)),
+        Instruction::Progress(n) => quote!(::regex::Instruction::Progress(#n)),
+    }
+}
+
+fn char_class(class: &CharClass) -> TokenStream2 {
+    let ranges = class.ranges().iter().map(|&(lo, hi)| {
+        let (lo, hi) = (Literal::character(lo), Literal::character(hi));
+        quote!((#lo, #hi))
+    });
+    let negated = class.negated();
+    quote!(::regex::CharClass::from_static(&[#(#ranges),*], #negated))
+}
+
+/// エラーを報告する範囲
+///
+/// エスケープを含まない文字列リテラルで、コンパイラが対応している場合は
+/// エラーの位置の文字を指す。それ以外の場合はリテラル全体を指す
+fn error_span(lit: &LitStr, expr: &str, pos: Option<usize>) -> Span {
+    let token = lit.token();
+    let plain = token.to_string() == format!("\"{expr}\"");
+    let sub = pos.filter(|_| plain).and_then(|pos| {
+        let (start, c) = expr.char_indices().nth(pos)?;
+        // 先頭の " の分だけずらす
+        token.subspan(start + 1..start + 1 + c.len_utf8())
+    });
+    sub.unwrap_or_else(|| lit.span())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_instruction() {
+        let class = CharClass::new(&[('a', 'c'), ('x', 'x')], true);
+        let tokens = instruction(&Instruction::Class(class)).to_string();
+        assert_eq!(
+            tokens.replace(' ', ""),
+            "::regex::Instruction::Class(::regex::CharClass::from_static(&[('a','c'),('x','x')],true))"
+        );
+        let tokens = instruction(&Instruction::Split(1usize, 3usize)).to_string();
+        assert_eq!(
+            tokens.replace(' ', ""),
+            "::regex::Instruction::Split(1usize,3usize)"
+        );
+        let tokens = instruction(&Instruction::Look(Look::NotBehind, 4usize)).to_string();
+        assert_eq!(
+            tokens.replace(' ', ""),
+            "::regex::Instruction::Look(::regex::Look::NotBehind,4usize)"
+        );
+    }
+}
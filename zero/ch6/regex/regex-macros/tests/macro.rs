@@ -0,0 +1,34 @@
+//! regex! マクロのテスト
+use regex::Regex;
+use regex_macros::regex;
+
+#[test]
+fn test_regex() {
+    let re = regex!("b(cd)+");
+    assert_eq!(re.find("abcdcde").unwrap().unwrap().range(), 1..6);
+    assert!(re.find("acd").unwrap().is_none());
+
+    // 実行時にコンパイルした場合と同じ命令列
+    for expr in ["a(bc)+|c(def)*", "あ*い?う", "(a|b)\\1(?<!bb)", "\\(\\*\\)"] {
+        let expected = Regex::new(expr).unwrap();
+        let re = match expr {
+            "a(bc)+|c(def)*" => regex!("a(bc)+|c(def)*"),
+            "あ*い?う" => regex!("あ*い?う"),
+            "(a|b)\\1(?<!bb)" => regex!(r"(a|b)\1(?<!bb)"),
+            _ => regex!(r"\(\*\)"),
+        };
+        assert_eq!(re.program().code(), expected.program().code(), "{expr}");
+    }
+}
+
+#[test]
+fn test_static() {
+    // 同じ呼び出し箇所では同じ Regex を返す
+    let get = || regex!("x+");
+    assert!(std::ptr::eq(get(), get()));
+
+    let re: &'static Regex = get();
+    std::thread::spawn(move || assert!(re.is_match("axxb").unwrap()))
+        .join()
+        .unwrap();
+}
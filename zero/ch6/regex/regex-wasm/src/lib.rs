@@ -0,0 +1,51 @@
+//! # 正規表現エンジンの WebAssembly 向け API
+//!
+//! `wasm32-unknown-unknown` 向けにビルドし、wasm-bindgen で JavaScript から呼び出す。
+//! AST、命令列、実行トレースを JSON の文字列で返すため、ブラウザ上で可視化できる。
+//!
+//! ```bash
+//! $ cargo check -p regex-wasm --target wasm32-unknown-unknown
+//! $ cargo build --release -p regex-wasm --target wasm32-unknown-unknown
+//! $ wasm-bindgen --target web --out-dir pkg target/wasm32-unknown-unknown/release/regex_wasm.wasm
+//! ```
+//!
+//! ```js
+//! import init, { ast, program, trace } from "./pkg/regex_wasm.js";
+//! await init();
+//! const tree = JSON.parse(ast("a(bc)+"));
+//! const { match, steps, truncated } = JSON.parse(trace("b+", "abb", true));
+//! ```
+//!
+//! 正規表現にエラーがある場合は、エラーメッセージを持つ例外を投げる。
+use wasm_bindgen::prelude::*;
+
+/// 原因となったエラーのメッセージも含む例外に変換
+fn js_error(e: regex::Error) -> JsError {
+    JsError::new(&format!("{e:#}"))
+}
+
+/// 正規表現をパースし、AST を JSON で返す
+///
+/// 各ノードは `{"type": "or", "label": "|", "children": [...]}` の形式となる
+#[wasm_bindgen]
+pub fn ast(expr: &str) -> Result<String, JsError> {
+    regex::ast_to_json(expr).map_err(js_error)
+}
+
+/// 正規表現をコンパイルし、命令列を JSON で返す
+///
+/// 各命令は `{"pc": 0, "op": "split", "text": "split 0001, 0003", "next": [1, 3]}` の形式となる
+#[wasm_bindgen]
+pub fn program(expr: &str) -> Result<String, JsError> {
+    regex::program_to_json(expr).map_err(js_error)
+}
+
+/// 文字列中を検索し、マッチした範囲と実行した命令の列を JSON で返す
+///
+/// `{"match": {"start": 1, "end": 3}, "steps": [{"thread": 0, "pc": 0, "sp": 0}, ...], "truncated": false}` の形式となる。
+/// マッチの範囲は UTF-8 のバイト単位、sp は文字単位のインデックス。
+/// [`regex::MAX_TRACE_STEPS`] ステップで評価を打ち切った場合は、truncated が true となる
+#[wasm_bindgen]
+pub fn trace(expr: &str, text: &str, depth_first: bool) -> Result<String, JsError> {
+    regex::trace_to_json(expr, text, depth_first).map_err(js_error)
+}
@@ -0,0 +1,46 @@
+//! wasm-bindgen-test-runner で実行するテスト
+//!
+//! ```bash
+//! $ cargo test -p regex-wasm --target wasm32-unknown-unknown
+//! ```
+#![cfg(target_arch = "wasm32")]
+
+use regex_wasm::{ast, program, trace};
+use wasm_bindgen_test::wasm_bindgen_test;
+
+#[wasm_bindgen_test]
+fn test_ast() {
+    let json = ast("a|b").unwrap();
+    assert!(json.starts_with(r#"{"type":"or","label":"|","children":["#));
+}
+
+#[wasm_bindgen_test]
+fn test_program() {
+    let json = program("a").unwrap();
+    assert_eq!(
+        json,
+        r#"[{"pc":0,"op":"char","text":"char a","next":[1]},{"pc":1,"op":"match","text":"match","next":[]}]"#
+    );
+}
+
+#[wasm_bindgen_test]
+fn test_trace() {
+    let json = trace("b", "ab", true).unwrap();
+    assert_eq!(
+        json,
+        r#"{"match":{"start":1,"end":2},"steps":[{"thread":0,"pc":0,"sp":0},{"thread":0,"pc":0,"sp":1},{"thread":0,"pc":1,"sp":2}],"truncated":false}"#
+    );
+    assert!(trace("b", "aa", false)
+        .unwrap()
+        .starts_with(r#"{"match":null,"#));
+    assert!(trace("(a|a)*b", &"a".repeat(16), true)
+        .unwrap()
+        .ends_with(r#"],"truncated":true}"#));
+}
+
+#[wasm_bindgen_test]
+fn test_error() {
+    assert!(ast("a|*").is_err());
+    assert!(program("a(b").is_err());
+    assert!(program("(a?)*").is_ok());
+}
@@ -1,10 +1,42 @@
 //! 正規表現エンジン
+mod analyze;
+mod class;
 mod codegen;
+mod dfa;
+mod dot;
+mod error;
 mod evaluator;
+mod explain;
+mod generate;
+mod glob;
+mod json;
 mod parser;
+mod posix;
+mod printer;
+mod program;
+mod regex;
+mod threaded;
+mod trace;
+mod verifier;
 
 use std::fmt::{self, Display};
-use crate::helper::DynError;
+
+pub use analyze::{analyze, Finding, Issue, Report, Severity};
+pub use class::CharClass;
+pub use codegen::CodeGenError;
+pub use dfa::{Dfa, DfaError};
+pub use error::Error;
+pub use evaluator::EvalError;
+pub use explain::{explain, tokenize, Explanation, Language, Token, TokenKind};
+pub use generate::{enumerate, generate, Enumerate, Generator, Xor64};
+pub use glob::{Glob, GlobError};
+pub use json::{JsonTracer, MAX_TRACE_STEPS};
+pub use parser::{ParseError, Syntax};
+pub use program::{LoadError, Program, FLAG_IGNORE_CASE, FLAG_LONGEST};
+pub use regex::{Input, Match, Matches, Regex, RegexBuilder};
+pub use threaded::Backend;
+pub use trace::{NopTracer, PrintTracer, Tracer};
+pub use verifier::VerifyError;
 
 /// 正規表現と文字列をマッチング
 ///
@@ -26,13 +58,32 @@ use crate::helper::DynError;
 /// エラーなく実行でき、かつマッチングに **失敗** した場合は Ok(false) を返す
 ///
 /// 入力された正規表現にエラーがあったり、内部的な実装エラーがある場合は Err を返す
-pub fn do_matching(expr: &str, line: &str, is_depth: bool)
-                   -> Result<bool, DynError> {
+pub fn do_matching(expr: &str, line: &str, is_depth: bool) -> Result<bool, Error> {
+    compile(expr)?.do_matching(line, is_depth)
+}
+
+/// 正規表現をパースしてコード生成し、コンパイル済みプログラムを返す
+///
+/// # 利用例
+///
+/// ```
+/// use regex;
+/// let prog = regex::compile("abc|(de|cd)+").unwrap();
+/// let bytes = prog.to_bytes(); // バイナリ形式に変換
+/// let prog = regex::Program::from_bytes(&bytes).unwrap(); // 読み込み
+/// assert!(prog.do_matching("decddede", true).unwrap());
+/// ```
+///
+/// # 返り値
+///
+/// 入力された正規表現にエラーがあったり、内部的な実装エラーがある場合はErrを返す。
+/// `(a?)*` のように空文字列にマッチする式の繰り返しは、
+/// 本体が文字を消費しなかった場合に繰り返しを打ち切る命令列となる。
+pub fn compile(expr: &str) -> Result<Program, Error> {
     let ast = parser::parse(expr)?;
     let code = codegen::get_code(&ast)?;
-    let line = line.chars().collect::<Vec<char>>();
-
-    Ok(evaluator::eval(&code, &line, is_depth)?)
+    verifier::verify(&code)?;
+    Ok(Program::new(code))
 }
 
 /// 正規表現をパースしてコード生成し、
@@ -48,9 +99,21 @@ pub fn do_matching(expr: &str, line: &str, is_depth: bool)
 /// # 返り値
 ///
 /// 入力された正規表現にエラーがあったり、内部的な実装エラーがある場合はErrを返す。
-pub fn print(expr: &str) -> Result<(), DynError> {
+pub fn print(expr: &str) -> Result<(), Error> {
+    print_with(expr, Syntax::Native)
+}
+
+/// syntax で指定した構文の正規表現について、[`print`] と同様に AST と命令列を表示
+///
+/// # 利用例
+///
+/// ```
+/// use regex::Syntax;
+/// regex::print_with("\\(ab\\)\\{2\\}", Syntax::Bre);
+/// ```
+pub fn print_with(expr: &str, syntax: Syntax) -> Result<(), Error> {
     println!("EXPR: {expr}");
-    let ast = parser::parse(expr)?;
+    let ast = parser::parse_with(expr, syntax)?;
     println!("AST : {:?}", ast);
     let code = codegen::get_code(&ast)?;
     print!("CODE: ");
@@ -63,13 +126,198 @@ pub fn print(expr: &str) -> Result<(), DynError> {
     Ok(())
 }
 
+/// 正規表現をパースし、正規形の文字列に変換
+///
+/// 出力は冗長なカッコを含まず、再度パースすると同じ AST になる。
+///
+/// # 利用例
+///
+/// ```
+/// use regex;
+/// assert_eq!(regex::canonicalize("a()(b)|(c)").unwrap(), "a(b)|(c)");
+/// assert_eq!(regex::canonicalize("\\)\\+").unwrap(), "\\)\\+");
+/// ```
+///
+/// # 返り値
+///
+/// 入力された正規表現にエラーがある場合はErrを返す。
+pub fn canonicalize(expr: &str) -> Result<String, Error> {
+    let ast = parser::parse(expr)?;
+    Ok(printer::to_pattern(&ast))
+}
+
+/// 正規表現をパースしてコード生成し、
+/// ASTと命令列を Graphviz の DOT 形式で返す。
+///
+/// AST は木構造、命令列は Jump と Split を辺とするグラフとして描画される。
+///
+/// # 利用例
+///
+/// ```
+/// use regex;
+/// let dot = regex::to_dot("abc|(de|cd)+").unwrap();
+/// assert!(dot.starts_with("digraph"));
+/// ```
+///
+/// `dot -Tsvg` などにパイプすると画像に変換できる。
+/// 命令列の検証は行わない。
+///
+/// # 返り値
+///
+/// 入力された正規表現にエラーがあったり、内部的な実装エラーがある場合はErrを返す。
+pub fn to_dot(expr: &str) -> Result<String, Error> {
+    let ast = parser::parse(expr)?;
+    let code = codegen::get_code(&ast)?;
+    Ok(dot::to_dot(&ast, &code))
+}
+
+/// 正規表現をパースし、AST を JSON で返す
+///
+/// # 利用例
+///
+/// ```
+/// use regex;
+/// let json = regex::ast_to_json("a*").unwrap();
+/// assert_eq!(
+///     json,
+///     r#"{"type":"seq","label":"seq","children":[{"type":"star","label":"*","children":[{"type":"char","label":"a","children":[]}]}]}"#
+/// );
+/// ```
+pub fn ast_to_json(expr: &str) -> Result<String, Error> {
+    let ast = parser::parse(expr)?;
+    Ok(json::ast_to_json(&ast))
+}
+
+/// 正規表現をコンパイルし、命令列を JSON で返す
+///
+/// # 利用例
+///
+/// ```
+/// use regex;
+/// let json = regex::program_to_json("a").unwrap();
+/// assert_eq!(
+///     json,
+///     r#"[{"pc":0,"op":"char","text":"char a","next":[1]},{"pc":1,"op":"match","text":"match","next":[]}]"#
+/// );
+/// ```
+pub fn program_to_json(expr: &str) -> Result<String, Error> {
+    let prog = compile(expr)?;
+    Ok(json::code_to_json(prog.code()))
+}
+
+/// 文字列中を検索し、マッチした範囲と評価器が実行した命令の列を JSON で返す
+///
+/// is_depth が true の場合は深さ優先探索、false の場合は幅優先探索を利用。
+/// マッチの範囲はバイト単位、各ステップの sp は文字単位のインデックス。
+/// 先頭の [`MAX_TRACE_STEPS`] ステップで評価を打ち切った場合は、truncated が true となり match は null となる
+///
+/// # 利用例
+///
+/// ```
+/// use regex;
+/// let json = regex::trace_to_json("b", "ab", true).unwrap();
+/// assert!(json.starts_with(r#"{"match":{"start":1,"end":2},"steps":[{"thread":0,"pc":0,"sp":0},"#));
+/// ```
+pub fn trace_to_json(expr: &str, text: &str, is_depth: bool) -> Result<String, Error> {
+    let re = RegexBuilder::new(expr).depth_first(is_depth).build()?;
+    let mut tracer = JsonTracer::new();
+    // 上限で打ち切った場合は、マッチしなかったものとする
+    let m = match re.find_traced(text, &mut tracer) {
+        Err(Error::Eval(EvalError::Aborted)) => None,
+        m => m?,
+    };
+    Ok(tracer.finish(m))
+}
+
 /// アセンブリ命令の表現と対応する型
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Instruction {
     Char(char),
     Match,
     Jump(usize),
     Split(usize, usize),
+    // 文字クラスに含まれる1文字を消費
+    Class(CharClass),
+    // 文字列の末尾でのみ成功し、文字を消費しない
+    End,
+    // 文字列の先頭か改行の直後でのみ成功し、文字を消費しない
+    LineStart,
+    // 文字列の末尾か改行の直前でのみ成功し、文字を消費しない
+    LineEnd,
+    // 現在の位置をキャプチャの位置として保存する。グループ n の開始は 2n、終了は 2n + 1
+    Save(usize),
+    // グループ n がキャプチャした文字列を消費
+    Backref(usize),
+    // 次の命令から Match までを本体とする先読みまたは後読み。成功した場合は addr に進む
+    Look(Look, usize),
+    // アトミックグループの開始。バックトラック先の区切りを置く
+    AtomicStart,
+    // アトミックグループの終了。対応する AtomicStart 以降のバックトラック先を捨てる
+    AtomicEnd,
+    // 繰り返しの本体を評価し始める位置をレジスタ n に保存する
+    Mark(usize),
+    // レジスタ n に保存した位置から文字を消費していなければ失敗する。空ループを防ぐ
+    Progress(usize),
+}
+
+impl Instruction {
+    /// 深さ優先探索の評価器でのみ評価できる命令かどうか
+    pub fn requires_backtracking(&self) -> bool {
+        matches!(
+            self,
+            Instruction::Save(_)
+                | Instruction::Backref(_)
+                | Instruction::Look(..)
+                | Instruction::AtomicStart
+                | Instruction::AtomicEnd
+        )
+    }
+}
+
+/// 先読みと後読みの種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Look {
+    /// `(?=...)`
+    Ahead,
+    /// `(?!...)`
+    NotAhead,
+    /// `(?<=...)`
+    Behind,
+    /// `(?<!...)`
+    NotBehind,
+}
+
+impl Look {
+    /// 本体がマッチしない場合に成功するかどうか
+    pub fn negated(self) -> bool {
+        matches!(self, Look::NotAhead | Look::NotBehind)
+    }
+
+    /// 後読みかどうか
+    pub fn is_behind(self) -> bool {
+        matches!(self, Look::Behind | Look::NotBehind)
+    }
+
+    /// 正規表現での開きカッコ
+    pub fn prefix(self) -> &'static str {
+        match self {
+            Look::Ahead => "(?=",
+            Look::NotAhead => "(?!",
+            Look::Behind => "(?<=",
+            Look::NotBehind => "(?<!",
+        }
+    }
+}
+
+impl Display for Look {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Look::Ahead => write!(f, "ahead"),
+            Look::NotAhead => write!(f, "not_ahead"),
+            Look::Behind => write!(f, "behind"),
+            Look::NotBehind => write!(f, "not_behind"),
+        }
+    }
 }
 
 impl Display for Instruction {
@@ -78,8 +326,18 @@ impl Display for Instruction {
             Instruction::Char(c) => write!(f, "char {}", c),
             Instruction::Match => write!(f, "match"),
             Instruction::Jump(addr) => write!(f, "jump {:>04}", addr),
-            Instruction::Split(addr1, addr2) =>
-                write!(f, "split {:>04}, {:>04}", addr1, addr2),
+            Instruction::Split(addr1, addr2) => write!(f, "split {:>04}, {:>04}", addr1, addr2),
+            Instruction::Class(class) => write!(f, "class {}", class),
+            Instruction::End => write!(f, "end"),
+            Instruction::LineStart => write!(f, "line_start"),
+            Instruction::LineEnd => write!(f, "line_end"),
+            Instruction::Save(slot) => write!(f, "save {}", slot),
+            Instruction::Backref(n) => write!(f, "backref {}", n),
+            Instruction::Look(look, addr) => write!(f, "look_{} {:>04}", look, addr),
+            Instruction::AtomicStart => write!(f, "atomic_start"),
+            Instruction::AtomicEnd => write!(f, "atomic_end"),
+            Instruction::Mark(n) => write!(f, "mark {}", n),
+            Instruction::Progress(n) => write!(f, "progress {}", n),
         }
     }
 }
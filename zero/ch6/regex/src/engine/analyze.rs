@@ -0,0 +1,898 @@
+//! 正規表現の計算量の解析
+//!
+//! 深さ優先探索で評価したとき、入力の長さに対して指数時間や多項式時間を要する式 (ReDoS) を検出する。
+//! AST から Glushkov オートマトンを構成し、同じ文字列を読む経路の重複を調べる。
+//! 状態は文字を消費する AST の葉 (位置) で、遷移はそこに至る経路の数を持つ。
+//!
+//! - 指数的な曖昧さ: 同じ文字列を読んで位置 p から p に戻る経路が 2 つ以上ある
+//! - 多項式的な曖昧さ: 異なる位置 p, q について、同じ文字列で p → p, p → q, q → q と遷移できる
+//! - 有限の曖昧さ: 繰り返しの外にあり、後続と言語が重なる `?` の数だけ分岐が倍になる
+//!
+//! 行頭などのアンカーと後方参照は文字を消費しないものとみなし、先読みと後読みの本体は独立に解析する。
+//! 強欲な量指定子の繰り返しはバックトラックしないため報告しない。
+use super::{
+    codegen,
+    parser::{self, AST},
+    verifier, CharClass, Error,
+};
+use std::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    fmt::{self, Display},
+    ops::Range,
+};
+
+/// 解析する位置の数の上限
+const MAX_POSITIONS: usize = 256;
+
+/// 位置の組を辿る回数の上限
+const MAX_STEPS: usize = 10_000_000;
+
+/// 深刻度
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// 入力の長さに対して線形時間だが、式の長さに対して指数的に分岐する
+    Low,
+    /// 入力の長さに対して多項式時間を要する
+    Medium,
+    /// 入力の長さに対して指数時間を要する
+    High,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Low => write!(f, "low"),
+            Severity::Medium => write!(f, "medium"),
+            Severity::High => write!(f, "high"),
+        }
+    }
+}
+
+/// 検出した問題の種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Issue {
+    /// 言語の重なる量指定子の入れ子。`(a+)+` など
+    NestedQuantifier,
+    /// 指数的な曖昧さ。`(a|aa)*` など
+    ExponentialAmbiguity,
+    /// 次数 k の多項式的な曖昧さ。`a*a*` は k = 2
+    PolynomialAmbiguity(usize),
+    /// 後続と言語が重なる n 個の `?` による有限の曖昧さ。分岐は最大で 2^n 通り
+    BoundedAmbiguity(usize),
+    /// 式が大きすぎるため解析を打ち切った
+    TooLarge,
+}
+
+impl Issue {
+    /// 問題の深刻度
+    pub fn severity(&self) -> Severity {
+        match self {
+            Issue::NestedQuantifier | Issue::ExponentialAmbiguity => Severity::High,
+            Issue::PolynomialAmbiguity(_) | Issue::TooLarge => Severity::Medium,
+            Issue::BoundedAmbiguity(_) => Severity::Low,
+        }
+    }
+}
+
+impl Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Issue::NestedQuantifier => write!(f, "nested quantifiers with overlapping languages"),
+            Issue::ExponentialAmbiguity => write!(f, "exponential ambiguity"),
+            Issue::PolynomialAmbiguity(k) => write!(f, "polynomial ambiguity of degree {k}"),
+            Issue::BoundedAmbiguity(n) => write!(f, "up to 2^{n} overlapping alternatives"),
+            Issue::TooLarge => write!(f, "too large to analyze"),
+        }
+    }
+}
+
+/// 検出した問題と、その原因となる部分式
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    issue: Issue,
+    span: Range<usize>,
+    fragment: String,
+}
+
+impl Finding {
+    fn new(issue: Issue, span: Range<usize>, chars: &[char]) -> Self {
+        let fragment = chars[span.clone()].iter().collect();
+        Finding {
+            issue,
+            span,
+            fragment,
+        }
+    }
+
+    /// 問題の種類
+    pub fn issue(&self) -> Issue {
+        self.issue
+    }
+
+    /// 問題の深刻度
+    pub fn severity(&self) -> Severity {
+        self.issue.severity()
+    }
+
+    /// 部分式の範囲 (文字単位のインデックス)
+    pub fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+
+    /// 部分式の文字列
+    pub fn fragment(&self) -> &str {
+        &self.fragment
+    }
+}
+
+impl Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {}: `{}` at {}..{}",
+            self.severity(),
+            self.issue,
+            self.fragment,
+            self.span.start,
+            self.span.end
+        )
+    }
+}
+
+/// 解析結果
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Report {
+    findings: Vec<Finding>,
+}
+
+impl Report {
+    /// 検出した問題。深刻度の高い順に並ぶ
+    pub fn findings(&self) -> &[Finding] {
+        &self.findings
+    }
+
+    /// 最も高い深刻度。問題がなければ None
+    pub fn severity(&self) -> Option<Severity> {
+        self.findings.iter().map(Finding::severity).max()
+    }
+
+    /// 入力の長さに対して線形時間で評価できるか
+    pub fn is_safe(&self) -> bool {
+        self.severity() <= Some(Severity::Low)
+    }
+}
+
+/// 正規表現の計算量を解析
+///
+/// # 利用例
+///
+/// ```
+/// use regex::{analyze, Issue, Severity};
+/// let report = analyze("(a+)+b").unwrap();
+/// assert_eq!(report.severity(), Some(Severity::High));
+/// assert_eq!(report.findings()[0].issue(), Issue::NestedQuantifier);
+/// assert_eq!(report.findings()[0].fragment(), "(a+)+");
+/// assert!(analyze("(a|b)*c").unwrap().is_safe());
+/// ```
+///
+/// # 返り値
+///
+/// 独自の構文の正規表現を解析する。
+/// 正規表現にエラーがある場合は、コンパイルと同じく Err を返す
+pub fn analyze(expr: &str) -> Result<Report, Error> {
+    let (ast, spans) = parser::parse_spanned(expr)?;
+    let code = codegen::get_code(&ast)?;
+    verifier::verify(&code)?;
+
+    let chars = expr.chars().collect::<Vec<_>>();
+    let mut glushkov = Glushkov::new(&spans, groups(&chars));
+    glushkov.build(&ast);
+
+    let mut findings = glushkov
+        .analyze()
+        .unwrap_or_else(|| vec![(Issue::TooLarge, 0..chars.len())])
+        .into_iter()
+        .map(|(issue, span)| Finding::new(issue, span, &chars))
+        .collect::<Vec<_>>();
+    findings.sort_by_key(|f| (std::cmp::Reverse(f.severity()), f.span.start, f.span.end));
+    Ok(Report { findings })
+}
+
+/// 位置ごとの経路の数
+type Counts = BTreeMap<usize, usize>;
+
+/// 部分式の先頭と末尾の位置と、空文字列にマッチする経路の数
+struct Frag {
+    first: Counts,
+    last: Counts,
+    empty: usize,
+}
+
+impl Frag {
+    fn empty() -> Self {
+        Frag {
+            first: Counts::new(),
+            last: Counts::new(),
+            empty: 1,
+        }
+    }
+}
+
+/// 量指定子の付いた部分式
+struct Quantified {
+    // 式中の範囲
+    span: Range<usize>,
+    // 部分式に含まれる位置
+    positions: Range<usize>,
+    // 繰り返す部分式の先頭と末尾の位置
+    first: Vec<usize>,
+    last: Vec<usize>,
+    // + か * なら true、? なら false
+    looping: bool,
+    // 強欲な量指定子かどうか
+    possessive: bool,
+}
+
+/// 遷移
+#[derive(Default)]
+struct Edge {
+    // 経路の数
+    count: usize,
+    // 遷移を生じた繰り返しの番号
+    loops: Vec<usize>,
+}
+
+/// Glushkov オートマトン
+struct Glushkov<'a> {
+    spans: std::slice::Iter<'a, Range<usize>>,
+    // カッコで囲まれた範囲
+    groups: Vec<Range<usize>>,
+    // 位置ごとの文字の集合
+    labels: Vec<CharClass>,
+    quantified: Vec<Quantified>,
+    follow: BTreeMap<(usize, usize), Edge>,
+    // 強欲な量指定子を抜ける遷移のうち、除く遷移の末尾と先頭の位置
+    cuts: Vec<(Vec<usize>, Vec<usize>)>,
+}
+
+impl<'a> Glushkov<'a> {
+    fn new(spans: &'a [Range<usize>], groups: Vec<Range<usize>>) -> Self {
+        Glushkov {
+            spans: spans.iter(),
+            groups,
+            labels: Vec::new(),
+            quantified: Vec::new(),
+            follow: BTreeMap::new(),
+            cuts: Vec::new(),
+        }
+    }
+
+    /// AST を後行順に辿り、位置と遷移を追加する
+    fn build(&mut self, ast: &AST) -> Frag {
+        match ast {
+            AST::Char(c) => self.position(CharClass::new(&[(*c, *c)], false)),
+            AST::Class(class) => self.position(class.clone()),
+            AST::End | AST::LineStart | AST::LineEnd | AST::Backref(_) => Frag::empty(),
+            AST::Look(_, e) => {
+                self.build(e);
+                Frag::empty()
+            }
+            AST::Capture(_, e) => self.build(e),
+            AST::Atomic(e) => {
+                let frag = self.build(e);
+                if is_quantifier(e) {
+                    // 直前に追加した量指定子が強欲になる
+                    let q = self.quantified.last_mut().unwrap();
+                    q.possessive = true;
+                    let cut = (frag.last.keys().copied().collect(), q.first.clone());
+                    self.cuts.push(cut);
+                }
+                frag
+            }
+            AST::Seq(v) => {
+                let mut frag = Frag::empty();
+                for e in v {
+                    let next = self.build(e);
+                    frag = self.concat(frag, next);
+                }
+                frag
+            }
+            AST::Or(e1, e2) => {
+                let mut frag = self.build(e1);
+                let frag2 = self.build(e2);
+                add(&mut frag.first, &frag2.first, 1);
+                add(&mut frag.last, &frag2.last, 1);
+                frag.empty = frag.empty.saturating_add(frag2.empty);
+                frag
+            }
+            AST::Question(e) => {
+                let mut frag = self.quantify(e, false);
+                frag.empty = frag.empty.saturating_add(1);
+                frag
+            }
+            AST::Plus(e) => self.quantify(e, true),
+            AST::Star(e) => {
+                let mut frag = self.quantify(e, true);
+                frag.empty = 1;
+                frag
+            }
+        }
+    }
+
+    /// 文字を消費する位置を追加
+    fn position(&mut self, label: CharClass) -> Frag {
+        let p = self.labels.len();
+        self.labels.push(label);
+        Frag {
+            first: Counts::from([(p, 1)]),
+            last: Counts::from([(p, 1)]),
+            empty: 0,
+        }
+    }
+
+    /// 量指定子の付いた部分式を追加
+    ///
+    /// コード生成と同じく、`(a*)*` は内側の `a*` のみを繰り返す
+    fn quantify(&mut self, e: &AST, looping: bool) -> Frag {
+        let lo = self.labels.len();
+        let frag = self.build(e);
+        let span = self.spans.next().cloned().unwrap_or_default();
+        let id = self.quantified.len();
+        let collapsed = matches!(e, AST::Star(_))
+            || matches!(e, AST::Seq(v) if matches!(v[..], [AST::Star(_)]));
+        if looping && !collapsed {
+            self.connect(&frag.last, &frag.first, Some(id));
+        }
+        self.quantified.push(Quantified {
+            span,
+            positions: lo..self.labels.len(),
+            first: frag.first.keys().copied().collect(),
+            last: frag.last.keys().copied().collect(),
+            looping,
+            possessive: false,
+        });
+        frag
+    }
+
+    /// 連接
+    fn concat(&mut self, a: Frag, b: Frag) -> Frag {
+        self.connect(&a.last, &b.first, None);
+        let mut first = a.first;
+        add(&mut first, &b.first, a.empty);
+        let mut last = b.last;
+        add(&mut last, &a.last, b.empty);
+        Frag {
+            first,
+            last,
+            empty: a.empty.saturating_mul(b.empty),
+        }
+    }
+
+    /// from の各位置から to の各位置への遷移を追加
+    fn connect(&mut self, from: &Counts, to: &Counts, id: Option<usize>) {
+        for (&p, &m) in from {
+            for (&q, &n) in to {
+                if self.is_cut(p, q) {
+                    continue;
+                }
+                let edge = self.follow.entry((p, q)).or_default();
+                edge.count = edge.count.saturating_add(m.saturating_mul(n));
+                edge.loops.extend(id);
+            }
+        }
+    }
+
+    /// 強欲な量指定子が消費するはずの文字へ抜ける遷移かどうか
+    fn is_cut(&self, p: usize, q: usize) -> bool {
+        self.cuts.iter().any(|(last, first)| {
+            last.contains(&p)
+                && first
+                    .iter()
+                    .any(|&f| overlaps(&self.labels[f], &self.labels[q]))
+        })
+    }
+
+    /// 曖昧さを調べ、問題の種類と範囲を返す
+    ///
+    /// 式が大きすぎる場合は None を返す
+    fn analyze(&self) -> Option<Vec<(Issue, Range<usize>)>> {
+        let n = self.labels.len();
+        if n > MAX_POSITIONS {
+            return None;
+        }
+
+        let mut succ = vec![Vec::new(); n];
+        let mut pred = vec![Vec::new(); n];
+        for &(p, q) in self.follow.keys() {
+            succ[p].push(q);
+            pred[q].push(p);
+        }
+        let overlap = (0..n)
+            .map(|p| {
+                (0..n)
+                    .map(|q| overlaps(&self.labels[p], &self.labels[q]))
+                    .collect()
+            })
+            .collect::<Vec<Vec<_>>>();
+
+        // reach[p][q] は p から 1 文字以上読んで q に至れるか
+        let reach = (0..n)
+            .map(|p| {
+                let mut seen = vec![false; n];
+                let mut queue = succ[p].iter().copied().collect::<VecDeque<_>>();
+                while let Some(q) = queue.pop_front() {
+                    if !seen[q] {
+                        seen[q] = true;
+                        queue.extend(&succ[q]);
+                    }
+                }
+                seen
+            })
+            .collect::<Vec<_>>();
+        let cyclic = (0..n).filter(|&p| reach[p][p]).collect::<Vec<_>>();
+        let same_scc = |p: usize, q: usize| reach[p][q] && reach[q][p];
+
+        let mut pairs = Pairs {
+            n,
+            succ: &succ,
+            pred: &pred,
+            overlap: &overlap,
+            steps: 0,
+        };
+        let mut found = BTreeSet::new();
+
+        // 同じ強連結成分の中にある、経路が 2 つ以上の遷移
+        for (&(p, q), edge) in &self.follow {
+            if edge.count >= 2 && same_scc(p, q) {
+                found.extend(self.exponential(&[p, q], &edge.loops));
+            }
+        }
+
+        // 同じ文字列を読んで (p, p) から異なる位置の組を経由して (p, p) に戻る経路
+        let mut forward = BTreeMap::new();
+        for &p in &cyclic {
+            let from = pairs.search(p * n + p, true)?;
+            let to = pairs.search(p * n + p, false)?;
+            let component = (0..n * n).filter(|&i| from[i] && to[i]).collect::<Vec<_>>();
+            if component.iter().any(|&i| i / n != i % n) {
+                let positions = component
+                    .iter()
+                    .flat_map(|&i| [i / n, i % n])
+                    .collect::<BTreeSet<_>>()
+                    .into_iter()
+                    .collect::<Vec<_>>();
+                found.extend(self.exponential(&positions, &[]));
+            }
+            forward.insert(p, from);
+        }
+
+        // 同じ文字列で p → p, p → q, q → q と遷移できる異なる強連結成分の位置
+        let mut chains = Vec::new();
+        for &q in &cyclic {
+            let to = pairs.search(q * n + q, false)?;
+            for &p in &cyclic {
+                if reach[p][q] && !same_scc(p, q) && forward[&p][p * n + q] && to[p * n + q] {
+                    chains.push((p, q));
+                }
+            }
+        }
+        let root = |p: usize| (0..n).find(|&q| same_scc(p, q)).unwrap();
+        let edges = chains
+            .iter()
+            .map(|&(p, q)| (root(p), root(q)))
+            .collect::<BTreeSet<_>>();
+        let mut polynomial = Vec::new();
+        for &(p, q) in &chains {
+            let (lp, lq) = (self.enclosing(&[p], &[]), self.enclosing(&[q], &[]));
+            if let (Some(lp), Some(lq)) = (lp, lq) {
+                if self.is_possessive(lp) || self.is_possessive(lq) {
+                    continue;
+                }
+                let (sp, sq) = (&self.quantified[lp].span, &self.quantified[lq].span);
+                let span = self.widen(sp.start.min(sq.start)..sp.end.max(sq.end));
+                let degree = longest(root(p), &edges, false) + longest(root(q), &edges, true);
+                polynomial.push((span, degree));
+            }
+        }
+        // 次数は範囲に含まれる鎖のうち最長のものとする
+        for (span, _) in &polynomial {
+            let degree = polynomial
+                .iter()
+                .filter(|(s, _)| contains(span, s))
+                .map(|(_, d)| *d)
+                .max();
+            found.insert((
+                span.start,
+                span.end,
+                Issue::PolynomialAmbiguity(degree.unwrap()),
+            ));
+        }
+
+        found.extend(self.bounded(&succ, &overlap));
+
+        // 同じ種類で他の範囲に含まれる問題は除く
+        let found = found.into_iter().collect::<Vec<_>>();
+        Some(
+            found
+                .iter()
+                .filter(|&&(start, end, issue)| {
+                    !found.iter().any(|&(s, e, i)| {
+                        s <= start
+                            && end <= e
+                            && (s, e) != (start, end)
+                            && i.severity() == issue.severity()
+                    })
+                })
+                .map(|&(start, end, issue)| (issue, start..end))
+                .collect(),
+        )
+    }
+
+    /// 指数的な曖昧さの原因となる繰り返しを返す
+    ///
+    /// 繰り返しの中に関係する位置を含む他の繰り返しがあれば、量指定子の入れ子とする
+    fn exponential(&self, positions: &[usize], loops: &[usize]) -> Option<(usize, usize, Issue)> {
+        let id = self.enclosing(positions, loops)?;
+        if self.is_possessive(id) {
+            return None;
+        }
+        let outer = &self.quantified[id];
+        let nested = self.quantified.iter().enumerate().any(|(i, q)| {
+            i != id
+                && q.looping
+                && contains(&outer.span, &q.span)
+                && positions.iter().any(|p| q.positions.contains(p))
+        });
+        let issue = if nested {
+            Issue::NestedQuantifier
+        } else {
+            Issue::ExponentialAmbiguity
+        };
+        Some((outer.span.start, outer.span.end, issue))
+    }
+
+    /// positions を含み、loops の繰り返しを囲む最小の繰り返しの番号
+    fn enclosing(&self, positions: &[usize], loops: &[usize]) -> Option<usize> {
+        self.quantified
+            .iter()
+            .enumerate()
+            .filter(|(_, q)| {
+                q.looping
+                    && positions.iter().all(|p| q.positions.contains(p))
+                    && loops
+                        .iter()
+                        .all(|&l| contains(&q.span, &self.quantified[l].span))
+            })
+            .min_by_key(|(_, q)| q.span.len())
+            .map(|(i, _)| i)
+    }
+
+    /// 強欲な量指定子の中にあるかどうか
+    fn is_possessive(&self, id: usize) -> bool {
+        let span = &self.quantified[id].span;
+        self.quantified
+            .iter()
+            .any(|q| q.possessive && contains(&q.span, span))
+    }
+
+    /// 繰り返しの外にあり、後続と言語が重なる `?` を数える
+    fn bounded(&self, succ: &[Vec<usize>], overlap: &[Vec<bool>]) -> Option<(usize, usize, Issue)> {
+        let spans = self
+            .quantified
+            .iter()
+            .filter(|q| !q.looping && !q.possessive)
+            .filter(|q| {
+                !self
+                    .quantified
+                    .iter()
+                    .any(|l| l.looping && contains(&l.span, &q.span))
+            })
+            .filter(|q| {
+                q.last.iter().flat_map(|&l| &succ[l]).any(|&next| {
+                    !q.positions.contains(&next) && q.first.iter().any(|&f| overlap[f][next])
+                })
+            })
+            .map(|q| &q.span)
+            .collect::<Vec<_>>();
+        if spans.len() < 2 {
+            return None;
+        }
+        let start = spans.iter().map(|s| s.start).min().unwrap();
+        let end = spans.iter().map(|s| s.end).max().unwrap();
+        let span = self.widen(start..end);
+        Some((span.start, span.end, Issue::BoundedAmbiguity(spans.len())))
+    }
+
+    /// 複数の繰り返しにまたがる範囲を、途中で切れるカッコを含むよう広げる
+    fn widen(&self, mut span: Range<usize>) -> Range<usize> {
+        loop {
+            let cut = self.groups.iter().find(|g| {
+                !contains(&span, g)
+                    && !contains(g, &span)
+                    && g.start < span.end
+                    && span.start < g.end
+            });
+            match cut {
+                Some(g) => span = span.start.min(g.start)..span.end.max(g.end),
+                None => return span,
+            }
+        }
+    }
+}
+
+/// 2 つのコピーが同じ文字列を読むときの位置の組の探索
+struct Pairs<'a> {
+    n: usize,
+    succ: &'a [Vec<usize>],
+    pred: &'a [Vec<usize>],
+    overlap: &'a [Vec<bool>],
+    steps: usize,
+}
+
+impl Pairs<'_> {
+    /// 位置の組 start から辿れる組を返す。forward が false の場合は start に至る組
+    ///
+    /// 組 (p, q) は p * n + q で表す。探索の上限を超えた場合は None
+    fn search(&mut self, start: usize, forward: bool) -> Option<Vec<bool>> {
+        let n = self.n;
+        let next = if forward { self.succ } else { self.pred };
+        let mut seen = vec![false; n * n];
+        seen[start] = true;
+        let mut queue = VecDeque::from([start]);
+        while let Some(i) = queue.pop_front() {
+            let (p, q) = (i / n, i % n);
+            for &p2 in &next[p] {
+                for &q2 in &next[q] {
+                    self.steps += 1;
+                    if self.steps > MAX_STEPS {
+                        return None;
+                    }
+                    // 前向きには遷移先、後ろ向きには遷移元の文字が共通する
+                    let (a, b) = if forward { (p2, q2) } else { (p, q) };
+                    let j = p2 * n + q2;
+                    if self.overlap[a][b] && !seen[j] {
+                        seen[j] = true;
+                        queue.push_back(j);
+                    }
+                }
+            }
+        }
+        Some(seen)
+    }
+}
+
+/// 強連結成分の間の遷移 edges で、s を終点 (from が true なら始点) とする最長の鎖の長さ
+fn longest(s: usize, edges: &BTreeSet<(usize, usize)>, from: bool) -> usize {
+    1 + edges
+        .iter()
+        .filter_map(|&(a, b)| match (from, a == s, b == s) {
+            (true, true, _) => Some(longest(b, edges, true)),
+            (false, _, true) => Some(longest(a, edges, false)),
+            _ => None,
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+/// 対応するカッコで囲まれた範囲を返す。エスケープされたカッコは除く
+fn groups(chars: &[char]) -> Vec<Range<usize>> {
+    let mut groups = Vec::new();
+    let mut opens = Vec::new();
+    let mut escaped = false;
+    for (i, &c) in chars.iter().enumerate() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '(' => opens.push(i),
+            ')' => {
+                if let Some(start) = opens.pop() {
+                    groups.push(start..i + 1);
+                }
+            }
+            _ => (),
+        }
+    }
+    groups
+}
+
+/// 経路の数を times 倍して加える
+fn add(into: &mut Counts, from: &Counts, times: usize) {
+    if times == 0 {
+        return;
+    }
+    for (&p, &m) in from {
+        let count = into.entry(p).or_insert(0);
+        *count = count.saturating_add(m.saturating_mul(times));
+    }
+}
+
+/// 量指定子かどうか。`(?>a*)` のように 1 要素の Seq に包まれていてもよい
+fn is_quantifier(ast: &AST) -> bool {
+    match ast {
+        AST::Plus(_) | AST::Star(_) | AST::Question(_) => true,
+        AST::Seq(v) if v.len() == 1 => is_quantifier(&v[0]),
+        _ => false,
+    }
+}
+
+/// outer が inner を含むか
+fn contains(outer: &Range<usize>, inner: &Range<usize>) -> bool {
+    outer.start <= inner.start && inner.end <= outer.end
+}
+
+/// 2 つのクラスに共通する文字があるか
+///
+/// 範囲の端で区切った区間ごとに、その先頭の文字が両方に含まれるかを調べる
+fn overlaps(a: &CharClass, b: &CharClass) -> bool {
+    let mut bounds = vec![0];
+    for &(lo, hi) in a.ranges().iter().chain(b.ranges()) {
+        bounds.push(u32::from(lo));
+        bounds.push(u32::from(hi) + 1);
+    }
+    bounds
+        .into_iter()
+        .map(|c| char::from_u32(c).unwrap_or('\u{e000}'))
+        .any(|c| a.matches(c) && b.matches(c))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn issues(expr: &str) -> Vec<(Issue, &str)> {
+        let report = analyze(expr).unwrap();
+        let chars = expr.chars().collect::<Vec<_>>();
+        report
+            .findings()
+            .iter()
+            .map(|f| {
+                let start = chars[..f.span().start]
+                    .iter()
+                    .map(|c| c.len_utf8())
+                    .sum::<usize>();
+                (f.issue(), &expr[start..start + f.fragment().len()])
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_exponential() {
+        assert_eq!(issues("(a+)+b"), [(Issue::NestedQuantifier, "(a+)+")]);
+        assert_eq!(issues("x(a+b?)+c"), [(Issue::NestedQuantifier, "(a+b?)+")]);
+        assert_eq!(issues("(a|a)*b"), [(Issue::ExponentialAmbiguity, "(a|a)*")]);
+        assert_eq!(
+            issues("(a|aa)+"),
+            [(Issue::ExponentialAmbiguity, "(a|aa)+")]
+        );
+        assert_eq!(
+            issues("(a(b?|c?)d)*"),
+            [(Issue::ExponentialAmbiguity, "(a(b?|c?)d)*")]
+        );
+        assert_eq!(issues("(?=(a+)+)"), [(Issue::NestedQuantifier, "(a+)+")]);
+
+        // 強欲な量指定子はバックトラックしない
+        assert!(issues("(a+)++b").is_empty());
+        assert!(issues("(a|aa)*+").is_empty());
+        assert!(issues("(a++)+").is_empty());
+    }
+
+    #[test]
+    fn test_polynomial() {
+        assert_eq!(issues("a*a*b"), [(Issue::PolynomialAmbiguity(2), "a*a*")]);
+        assert_eq!(
+            issues("xa+ya*a+"),
+            [(Issue::PolynomialAmbiguity(2), "a*a+")]
+        );
+        assert_eq!(
+            issues("a*a*a*"),
+            [(Issue::PolynomialAmbiguity(3), "a*a*a*")]
+        );
+        assert_eq!(
+            issues("(ab)*a(ba)*"),
+            [(Issue::PolynomialAmbiguity(2), "(ab)*a(ba)*")]
+        );
+        // 範囲はカッコの途中で切らない
+        assert_eq!(
+            issues("(a*)(a*)(a*)b"),
+            [(Issue::PolynomialAmbiguity(3), "(a*)(a*)(a*)")]
+        );
+        assert_eq!(
+            issues("x(ya*)a*"),
+            [(Issue::PolynomialAmbiguity(2), "(ya*)a*")]
+        );
+        assert_eq!(issues("\\(a*a*"), [(Issue::PolynomialAmbiguity(2), "a*a*")]);
+        assert!(issues("a*b*").is_empty());
+        assert!(issues("(ab)*(ba)*").is_empty());
+        assert!(issues("a*+a*").is_empty());
+        assert!(issues("(?>a*)a*").is_empty());
+    }
+
+    #[test]
+    fn test_bounded() {
+        // ベンチマークの a?^n a^n は入力に対して線形だが、n に対して指数的に分岐する
+        for n in 2..=20 {
+            let expr = format!("{}{}", "a?".repeat(n), "a".repeat(n));
+            let report = analyze(&expr).unwrap();
+            assert_eq!(report.severity(), Some(Severity::Low));
+            assert!(report.is_safe());
+            let finding = &report.findings()[0];
+            assert_eq!(finding.issue(), Issue::BoundedAmbiguity(n));
+            assert_eq!(finding.span(), 0..2 * n);
+        }
+        assert!(issues("a?a").is_empty());
+        assert!(issues("colou?r").is_empty());
+        assert!(issues("a?+a?+a").is_empty());
+        assert_eq!(
+            issues("(a?)(a?)a"),
+            [(Issue::BoundedAmbiguity(2), "(a?)(a?)")]
+        );
+    }
+
+    #[test]
+    fn test_safe() {
+        for expr in [
+            "abc",
+            "(a|b)*c",
+            "a(bc)+|c(def)*",
+            "(ab|a)c",
+            "^(a|b)+$",
+            "(a)\\1+",
+            "a**b",
+            "x(ab*)*",
+        ] {
+            let report = analyze(expr).unwrap();
+            assert!(report.findings().is_empty(), "{expr}");
+            assert_eq!(report.severity(), None);
+        }
+    }
+
+    #[test]
+    fn test_report() {
+        let report = analyze("(あ+)+|b?b?b").unwrap();
+        assert_eq!(report.severity(), Some(Severity::High));
+        assert!(!report.is_safe());
+        let findings = report.findings();
+        assert_eq!(findings.len(), 2);
+        assert_eq!(findings[0].span(), 0..5);
+        assert_eq!(
+            findings[0].to_string(),
+            "high: nested quantifiers with overlapping languages: `(あ+)+` at 0..5"
+        );
+        assert_eq!(
+            findings[1].to_string(),
+            "low: up to 2^2 overlapping alternatives: `b?b?` at 6..10"
+        );
+
+        let expr = "a".repeat(MAX_POSITIONS + 1);
+        let report = analyze(&expr).unwrap();
+        assert_eq!(report.findings()[0].issue(), Issue::TooLarge);
+
+        // 不正な式はコンパイルと同じエラー
+        assert!(matches!(analyze("a|*"), Err(Error::Parse(_))));
+    }
+
+    #[test]
+    fn test_overlaps() {
+        let class = |ranges: &[(char, char)], negated| CharClass::new(ranges, negated);
+        assert!(overlaps(
+            &class(&[('a', 'c')], false),
+            &class(&[('c', 'e')], false)
+        ));
+        assert!(!overlaps(
+            &class(&[('a', 'c')], false),
+            &class(&[('d', 'e')], false)
+        ));
+        assert!(!overlaps(
+            &class(&[('a', 'c')], false),
+            &class(&[('a', 'z')], true)
+        ));
+        assert!(overlaps(
+            &class(&[('a', 'c')], true),
+            &class(&[('a', 'z')], true)
+        ));
+        assert!(overlaps(
+            &class(&[('\0', '\u{d7ff}')], true),
+            &class(&[('\u{e000}', '\u{e000}')], false)
+        ));
+    }
+}
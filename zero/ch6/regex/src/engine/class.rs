@@ -0,0 +1,189 @@
+//! 文字クラス
+//!
+//! `[a-c]` や `[!/]` のような文字の集合を、文字の範囲の列として表す。
+use std::{
+    borrow::Cow,
+    fmt::{self, Display},
+};
+
+/// 文字クラス
+///
+/// ranges は昇順に並び、重なりや隣接のない閉区間の列。
+/// negated が true の場合は、ranges に含まれない文字にマッチする
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CharClass {
+    ranges: Cow<'static, [(char, char)]>,
+    negated: bool,
+}
+
+impl CharClass {
+    /// 文字の範囲の列から生成
+    ///
+    /// 範囲は任意の順序でよく、重なっていてもよい。始点が終点より大きい範囲は無視する
+    pub fn new(ranges: &[(char, char)], negated: bool) -> Self {
+        let mut ranges = ranges
+            .iter()
+            .copied()
+            .filter(|(lo, hi)| lo <= hi)
+            .collect::<Vec<_>>();
+        ranges.sort_unstable();
+
+        let mut merged: Vec<(char, char)> = Vec::with_capacity(ranges.len());
+        for (lo, hi) in ranges {
+            match merged.last_mut() {
+                Some((_, last)) if u32::from(lo) <= u32::from(*last) + 1 => {
+                    *last = (*last).max(hi);
+                }
+                _ => merged.push((lo, hi)),
+            }
+        }
+        CharClass {
+            ranges: Cow::Owned(merged),
+            negated,
+        }
+    }
+
+    /// 静的な範囲の列から生成
+    ///
+    /// `regex!` マクロが生成するコードのように、定数としてクラスを作るために用いる。
+    /// ranges が昇順に並び、重なりや隣接のない閉区間の列でない場合はパニックする
+    pub const fn from_static(ranges: &'static [(char, char)], negated: bool) -> Self {
+        let mut i = 0;
+        while i < ranges.len() {
+            assert!(ranges[i].0 <= ranges[i].1, "invalid range");
+            assert!(
+                i == 0 || ranges[i - 1].1 as u32 + 1 < ranges[i].0 as u32,
+                "unsorted ranges"
+            );
+            i += 1;
+        }
+        CharClass {
+            ranges: Cow::Borrowed(ranges),
+            negated,
+        }
+    }
+
+    /// 文字の範囲の列
+    pub fn ranges(&self) -> &[(char, char)] {
+        &self.ranges
+    }
+
+    /// 否定されたクラスかどうか
+    pub fn negated(&self) -> bool {
+        self.negated
+    }
+
+    /// c がクラスに含まれるかを判定
+    pub fn matches(&self, c: char) -> bool {
+        let found = self
+            .ranges
+            .binary_search_by(|&(lo, hi)| {
+                if hi < c {
+                    std::cmp::Ordering::Less
+                } else if lo > c {
+                    std::cmp::Ordering::Greater
+                } else {
+                    std::cmp::Ordering::Equal
+                }
+            })
+            .is_ok();
+        found != self.negated
+    }
+
+    /// 各文字を f で変換した文字を加えたクラスを返す
+    ///
+    /// 大文字と小文字を区別しない場合に、評価器に渡す文字と同じ変換をかけるために用いる
+    pub(crate) fn map(&self, f: impl Fn(char) -> char) -> CharClass {
+        let mut ranges = self.ranges.to_vec();
+        for &(lo, hi) in self.ranges.iter() {
+            for c in lo..=hi {
+                let d = f(c);
+                if d != c {
+                    ranges.push((d, d));
+                }
+            }
+        }
+        CharClass::new(&ranges, self.negated)
+    }
+}
+
+/// `[a-c]`, `[^/]` のようなブラケット表現として表示
+impl Display for CharClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn write_char(f: &mut fmt::Formatter<'_>, c: char) -> fmt::Result {
+            if matches!(c, ']' | '\\' | '^' | '-' | '[') {
+                write!(f, "\\{c}")
+            } else if c.is_control() {
+                write!(f, "{}", c.escape_debug())
+            } else {
+                write!(f, "{c}")
+            }
+        }
+
+        write!(f, "[")?;
+        if self.negated {
+            write!(f, "^")?;
+        }
+        for &(lo, hi) in self.ranges.iter() {
+            write_char(f, lo)?;
+            if lo != hi {
+                write!(f, "-")?;
+                write_char(f, hi)?;
+            }
+        }
+        write!(f, "]")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_class() {
+        let class = CharClass::new(
+            &[('x', 'z'), ('a', 'c'), ('b', 'e'), ('f', 'f'), ('q', 'p')],
+            false,
+        );
+        assert_eq!(class.ranges(), &[('a', 'f'), ('x', 'z')]);
+        assert!(class.matches('a') && class.matches('f') && class.matches('y'));
+        assert!(!class.matches('g') && !class.matches('A'));
+        assert_eq!(class.to_string(), "[a-fx-z]");
+
+        let class = CharClass::new(&[('/', '/')], true);
+        assert!(class.matches('a') && class.matches('\n'));
+        assert!(!class.matches('/'));
+        assert_eq!(class.to_string(), "[^/]");
+
+        // 何も含まない否定クラスは任意の文字にマッチ
+        let any = CharClass::new(&[], true);
+        assert!(any.matches('あ'));
+        assert_eq!(any.to_string(), "[^]");
+
+        assert_eq!(
+            CharClass::new(&[('-', '-'), (']', ']'), ('\n', '\n')], false).to_string(),
+            "[\\n\\-\\]]"
+        );
+    }
+
+    #[test]
+    fn test_from_static() {
+        const DIGIT: CharClass = CharClass::from_static(&[('0', '9'), ('a', 'a')], false);
+        assert_eq!(DIGIT, CharClass::new(&[('a', 'a'), ('0', '9')], false));
+        assert!(DIGIT.matches('5') && !DIGIT.matches('b'));
+
+        assert!(std::panic::catch_unwind(|| CharClass::from_static(
+            &[('a', 'b'), ('c', 'd')],
+            false
+        ))
+        .is_err());
+        assert!(std::panic::catch_unwind(|| CharClass::from_static(&[('b', 'a')], false)).is_err());
+    }
+
+    #[test]
+    fn test_map() {
+        let class =
+            CharClass::new(&[('A', 'C'), ('x', 'x')], false).map(|c| c.to_ascii_lowercase());
+        assert_eq!(class.ranges(), &[('A', 'C'), ('a', 'c'), ('x', 'x')]);
+    }
+}
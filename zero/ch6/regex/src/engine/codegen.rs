@@ -1,7 +1,13 @@
 //! AST からコード生成を行う
-use super::{parser::AST, Instruction};
+use super::{
+    parser::{nullable, AST},
+    Instruction, Look,
+};
 use crate::helper::safe_add;
-use std::{error::Error, fmt::{self, Display}};
+use std::{
+    error::Error,
+    fmt::{self, Display},
+};
 
 /// コード生成を行う関数
 pub fn get_code(ast: &AST) -> Result<Vec<Instruction>, CodeGenError> {
@@ -17,6 +23,7 @@ pub enum CodeGenError {
     FailStar,
     FailOr,
     FailQuestion,
+    FailLook,
 }
 
 impl Display for CodeGenError {
@@ -31,6 +38,8 @@ impl Error for CodeGenError {}
 #[derive(Default, Debug)]
 pub struct Generator {
     pc: usize,
+    // 空文字列にマッチし得る式の繰り返しに割り当てたレジスタの数
+    marks: usize,
     pub insts: Vec<Instruction>,
 }
 
@@ -66,19 +75,27 @@ impl Generator {
                 match &**e1 {
                     // `(a*)*`のように`Star`が二重となっている場合にスタックオーバーフローする問題を回避するため、
                     // このような`(((r*)*)*...*)*`を再帰的に処理して1つの`r*`へと変換する。
-                    AST::Star(_) => self.gen_expr(&e1)?,
+                    AST::Star(_) => self.gen_expr(e1)?,
                     AST::Seq(e2) if e2.len() == 1 => {
-                        if let Some(e3 @ AST::Star(_)) = e2.get(0) {
+                        if let Some(e3 @ AST::Star(_)) = e2.first() {
                             self.gen_expr(e3)?
                         } else {
                             self.gen_star(e1)?
                         }
                     }
-                    e => self.gen_star(&e)?,
+                    e => self.gen_star(e)?,
                 }
             }
             AST::Question(ast) => self.gen_question(ast)?,
             AST::Seq(v) => self.gen_seq(v)?,
+            AST::Class(class) => self.gen_inst(Instruction::Class(class.clone()))?,
+            AST::End => self.gen_inst(Instruction::End)?,
+            AST::LineStart => self.gen_inst(Instruction::LineStart)?,
+            AST::LineEnd => self.gen_inst(Instruction::LineEnd)?,
+            AST::Capture(n, ast) => self.gen_capture(*n, ast)?,
+            AST::Backref(n) => self.gen_inst(Instruction::Backref(*n))?,
+            AST::Look(look, ast) => self.gen_look(*look, ast)?,
+            AST::Atomic(ast) => self.gen_atomic(ast)?,
         }
         Ok(())
     }
@@ -91,6 +108,13 @@ impl Generator {
         Ok(())
     }
 
+    /// オペランドに飛び先を持たない命令の生成関数
+    fn gen_inst(&mut self, inst: Instruction) -> Result<(), CodeGenError> {
+        self.insts.push(inst);
+        self.inc_pc()?;
+        Ok(())
+    }
+
     /// OR 演算子のコード生成器
     ///
     /// ```text
@@ -135,14 +159,20 @@ impl Generator {
         Ok(())
     }
 
-    /// ? 限量子のコード生成器
+    /// + 限量子のコード生成器
     ///
     /// ```text
-    ///     split L1, L2
     /// L1: e のコード
+    ///     split L1, L2
     /// L2:
     /// ```
+    ///
+    /// e が空文字列にマッチし得る場合は gen_progress で生成する
     fn gen_plus(&mut self, ast: &AST) -> Result<(), CodeGenError> {
+        if nullable(ast) {
+            return self.gen_progress(ast);
+        }
+
         // e1 のコード
         let l1 = self.pc;
         self.gen_expr(ast)?;
@@ -163,6 +193,8 @@ impl Generator {
     ///     jmp L1
     /// L3:
     /// ```
+    ///
+    /// e が空文字列にマッチし得る場合は、L2 以降を gen_progress で生成する
     fn gen_star(&mut self, ast: &AST) -> Result<(), CodeGenError> {
         // split L1, L2
         let l1 = self.pc;
@@ -170,12 +202,17 @@ impl Generator {
         let split = Instruction::Split(self.pc, 0);
         self.insts.push(split);
 
-        // L2: e1 のコード
-        self.gen_expr(ast)?;
+        if nullable(ast) {
+            // L2: e+ のコード
+            self.gen_progress(ast)?;
+        } else {
+            // L2: e1 のコード
+            self.gen_expr(ast)?;
 
-        // jmp L1
-        self.inc_pc()?;
-        self.insts.push(Instruction::Jump(l1));
+            // jmp L1
+            self.inc_pc()?;
+            self.insts.push(Instruction::Jump(l1));
+        }
 
         // L3 の値を設定
         if let Some(Instruction::Split(_, l3)) = self.insts.get_mut(l1) {
@@ -187,6 +224,41 @@ impl Generator {
         Ok(())
     }
 
+    /// 空文字列にマッチし得る式の + 限量子のコード生成器
+    ///
+    /// ```text
+    /// L1: mark n
+    ///     e のコード
+    ///     split L2, L3
+    /// L2: progress n
+    ///     jmp L1
+    /// L3:
+    /// ```
+    ///
+    /// 本体が文字を消費しなかった場合は繰り返しを打ち切り、空ループとならないようにする
+    fn gen_progress(&mut self, ast: &AST) -> Result<(), CodeGenError> {
+        let n = self.marks;
+        self.marks += 1;
+
+        // L1: mark n
+        let l1 = self.pc;
+        self.gen_inst(Instruction::Mark(n))?;
+
+        // e のコード
+        self.gen_expr(ast)?;
+
+        // split L2, L3
+        self.inc_pc()?;
+        let l2 = self.pc;
+        self.insts.push(Instruction::Split(l2, l2 + 2));
+
+        // L2: progress n
+        self.gen_inst(Instruction::Progress(n))?;
+
+        // jmp L1
+        self.gen_inst(Instruction::Jump(l1))
+    }
+
     /// ? 限量子のコード生成器
     ///
     /// ```text
@@ -208,12 +280,65 @@ impl Generator {
         if let Some(Instruction::Split(_, l2)) = self.insts.get_mut(split_addr) {
             *l2 = self.pc;
         } else {
-            return Err(CodeGenError::FailOr);
+            return Err(CodeGenError::FailQuestion);
+        }
+
+        Ok(())
+    }
+
+    /// 後方参照されるグループのコード生成器
+    ///
+    /// ```text
+    ///     save 2n
+    ///     e のコード
+    ///     save 2n + 1
+    /// ```
+    fn gen_capture(&mut self, n: usize, ast: &AST) -> Result<(), CodeGenError> {
+        self.gen_inst(Instruction::Save(2 * n))?;
+        self.gen_expr(ast)?;
+        self.gen_inst(Instruction::Save(2 * n + 1))
+    }
+
+    /// 先読みと後読みのコード生成器
+    ///
+    /// ```text
+    ///     look L1
+    ///     e のコード
+    ///     match
+    /// L1:
+    /// ```
+    fn gen_look(&mut self, look: Look, ast: &AST) -> Result<(), CodeGenError> {
+        // look L1
+        let look_addr = self.pc;
+        self.gen_inst(Instruction::Look(look, 0))?;
+
+        // e のコード
+        self.gen_expr(ast)?;
+        self.gen_inst(Instruction::Match)?;
+
+        // L1 の値を設定
+        if let Some(Instruction::Look(_, l1)) = self.insts.get_mut(look_addr) {
+            *l1 = self.pc;
+        } else {
+            return Err(CodeGenError::FailLook);
         }
 
         Ok(())
     }
 
+    /// アトミックグループのコード生成器
+    ///
+    /// ```text
+    ///     atomic_start
+    ///     e のコード
+    ///     atomic_end
+    /// ```
+    fn gen_atomic(&mut self, ast: &AST) -> Result<(), CodeGenError> {
+        self.gen_inst(Instruction::AtomicStart)?;
+        self.gen_expr(ast)?;
+        self.gen_inst(Instruction::AtomicEnd)
+    }
+
     /// 連続する正規表現のコード生成器
     fn gen_seq(&mut self, asts: &[AST]) -> Result<(), CodeGenError> {
         for e in asts {
@@ -221,4 +346,4 @@ impl Generator {
         }
         Ok(())
     }
-}
\ No newline at end of file
+}
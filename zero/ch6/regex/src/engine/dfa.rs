@@ -0,0 +1,728 @@
+//! 命令列から構成する決定性有限オートマトン (DFA)
+//!
+//! 命令列を NFA とみなし、部分集合構成法で DFA に変換する。
+//! 入力文字は、すべての文字を覆う互いに素な区間に分割し、区間ごとに遷移を持つ。
+//! 同じ区間に含まれる文字は、どの命令からも区別されない。
+//!
+//! DFA は文字列 **全体** がパターンにマッチするかを判定する。
+//! 部分文字列の検索と同じ言語を得るには、パターンの前後に `.*` に相当する式を置く。
+//!
+//! 行頭と行末のアンカーは、直前の文字が改行かどうかを状態に含め、
+//! 次の文字を読む際に行末の条件を判定することで扱う。
+use super::{
+    class::CharClass,
+    program::{fold_case, Program, FLAG_IGNORE_CASE},
+    Error, Instruction,
+};
+use std::{
+    collections::{HashMap, VecDeque},
+    error,
+    fmt::{self, Display},
+    sync::OnceLock,
+};
+
+/// 構成する DFA の状態数の上限
+pub const MAX_STATES: usize = 10_000;
+
+/// DFA の構成エラーを表す型
+#[derive(Debug)]
+pub enum DfaError {
+    // 状態数が上限を超えた
+    TooManyStates,
+}
+
+impl Display for DfaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DfaError::TooManyStates => {
+                write!(f, "DfaError: too many states: max = {MAX_STATES}")
+            }
+        }
+    }
+}
+
+impl error::Error for DfaError {}
+
+/// 完全な DFA
+///
+/// 状態 0 が初期状態。すべての状態は、すべての文字の区間について遷移先を持つ
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dfa {
+    // 文字の区間。昇順に並び、すべての文字を覆う
+    classes: Vec<(char, char)>,
+    // 状態 s の区間 k での遷移先は trans[s * classes.len() + k]
+    trans: Vec<usize>,
+    // 受理状態かどうか
+    accepting: Vec<bool>,
+}
+
+/// 部分集合構成法で、次の文字について分かっていること
+#[derive(Clone, Copy)]
+enum Next {
+    // まだ読んでいない
+    Unknown,
+    // 文字 c を読む
+    Char(char),
+    // 文字列の末尾
+    Eof,
+}
+
+impl Dfa {
+    /// 正規表現をコンパイルし、DFA を構成
+    ///
+    /// # 利用例
+    ///
+    /// ```
+    /// use regex::Dfa;
+    /// let a = Dfa::new("(ab)+").unwrap();
+    /// let b = Dfa::new("a(ba)*b|c").unwrap();
+    /// assert!(a.is_equivalent(&b.intersection(&a)));
+    /// assert_eq!(b.difference(&a).shortest_witness().as_deref(), Some("c"));
+    /// ```
+    pub fn new(expr: &str) -> Result<Dfa, Error> {
+        Dfa::from_program(&super::compile(expr)?)
+    }
+
+    /// コンパイル済みのプログラムから DFA を構成
+    ///
+    /// FLAG_IGNORE_CASE が指定されている場合は、大文字と小文字を区別しない言語となる。
+    /// 後方参照や先読みと後読みを含むプログラムは正規言語とは限らないため、Err を返す
+    pub fn from_program(prog: &Program) -> Result<Dfa, Error> {
+        if prog.requires_backtracking() {
+            return Err(Error::RequiresBacktracking);
+        }
+        let changed = if prog.flags() & FLAG_IGNORE_CASE != 0 {
+            Some(case_changed())
+        } else {
+            None
+        };
+        let nfa = Nfa::new(prog.code(), changed);
+        Ok(nfa.determinize()?)
+    }
+
+    /// 状態数
+    pub fn len(&self) -> usize {
+        self.accepting.len()
+    }
+
+    /// 状態を持たないかどうか。構成した DFA は常に初期状態を持つ
+    pub fn is_empty(&self) -> bool {
+        self.accepting.is_empty()
+    }
+
+    /// 文字の区間
+    pub fn classes(&self) -> &[(char, char)] {
+        &self.classes
+    }
+
+    /// 状態 state から区間 class の文字を読んだときの遷移先
+    pub(super) fn next(&self, state: usize, class: usize) -> usize {
+        self.trans[state * self.classes.len() + class]
+    }
+
+    /// 状態 state が受理状態かどうか
+    pub(super) fn is_accepting(&self, state: usize) -> bool {
+        self.accepting[state]
+    }
+
+    /// 文字 c を含む区間のインデックス
+    fn class_of(&self, c: char) -> usize {
+        self.classes.partition_point(|&(_, hi)| hi < c)
+    }
+
+    /// 文字列全体を受理するかを判定
+    pub fn is_match(&self, text: &str) -> bool {
+        let state = text.chars().fold(0, |s, c| self.next(s, self.class_of(c)));
+        self.accepting[state]
+    }
+
+    /// 受理する文字列が存在しないかどうか
+    pub fn is_empty_language(&self) -> bool {
+        self.shortest_path().is_none()
+    }
+
+    /// 受理する最も短い文字列を返す
+    ///
+    /// 各区間からは、英数字、ASCII の表示可能文字、それ以外の順に代表となる文字を選ぶ。
+    /// 同じ長さの文字列が複数ある場合も、同じ順に代表となる文字を優先する
+    pub fn shortest_witness(&self) -> Option<String> {
+        let path = self.shortest_path()?;
+        Some(
+            path.into_iter()
+                .map(|k| representative(self.classes[k]))
+                .collect(),
+        )
+    }
+
+    /// 初期状態から受理状態までの最短経路を、区間のインデックスの列として返す
+    fn shortest_path(&self) -> Option<Vec<usize>> {
+        // 各状態に最初に到達した (直前の状態, 区間)
+        let mut parent: Vec<Option<(usize, usize)>> = vec![None; self.len()];
+        let mut visited = vec![false; self.len()];
+        let mut queue = VecDeque::from([0]);
+        visited[0] = true;
+
+        // 代表となる文字の優先順位の順に区間をたどる
+        let mut order = (0..self.classes.len()).collect::<Vec<_>>();
+        order.sort_by_key(|&k| rank(representative(self.classes[k])));
+
+        while let Some(s) = queue.pop_front() {
+            if self.accepting[s] {
+                let mut path = Vec::new();
+                let mut cur = s;
+                while let Some((prev, k)) = parent[cur] {
+                    path.push(k);
+                    cur = prev;
+                }
+                path.reverse();
+                return Some(path);
+            }
+            for &k in &order {
+                let t = self.next(s, k);
+                if !visited[t] {
+                    visited[t] = true;
+                    parent[t] = Some((s, k));
+                    queue.push_back(t);
+                }
+            }
+        }
+        None
+    }
+
+    /// 受理する言語の補集合を受理する DFA
+    pub fn complement(&self) -> Dfa {
+        Dfa {
+            classes: self.classes.clone(),
+            trans: self.trans.clone(),
+            accepting: self.accepting.iter().map(|a| !a).collect(),
+        }
+    }
+
+    /// 両方が受理する文字列を受理する DFA
+    pub fn intersection(&self, other: &Dfa) -> Dfa {
+        self.product(other, |a, b| a && b)
+    }
+
+    /// いずれかが受理する文字列を受理する DFA
+    pub fn union(&self, other: &Dfa) -> Dfa {
+        self.product(other, |a, b| a || b)
+    }
+
+    /// self が受理し、other が受理しない文字列を受理する DFA
+    pub fn difference(&self, other: &Dfa) -> Dfa {
+        self.product(other, |a, b| a && !b)
+    }
+
+    /// self が受理する文字列をすべて other も受理するかどうか
+    pub fn is_subset_of(&self, other: &Dfa) -> bool {
+        self.difference(other).is_empty_language()
+    }
+
+    /// 受理する言語が等しいかどうか
+    pub fn is_equivalent(&self, other: &Dfa) -> bool {
+        self.product(other, |a, b| a != b).is_empty_language()
+    }
+
+    /// 直積構成
+    ///
+    /// 両者の区間を細分した区間の上で、到達可能な状態の組のみを構成する
+    fn product(&self, other: &Dfa, accept: impl Fn(bool, bool) -> bool) -> Dfa {
+        let classes = refine(&self.classes, &other.classes);
+        let map_a = classes
+            .iter()
+            .map(|&(lo, _)| self.class_of(lo))
+            .collect::<Vec<_>>();
+        let map_b = classes
+            .iter()
+            .map(|&(lo, _)| other.class_of(lo))
+            .collect::<Vec<_>>();
+
+        let mut ids = HashMap::from([((0, 0), 0)]);
+        let mut pairs = vec![(0, 0)];
+        let mut trans = Vec::new();
+        let mut i = 0;
+        while i < pairs.len() {
+            let (a, b) = pairs[i];
+            for k in 0..classes.len() {
+                let pair = (self.next(a, map_a[k]), other.next(b, map_b[k]));
+                let id = *ids.entry(pair).or_insert_with(|| {
+                    pairs.push(pair);
+                    pairs.len() - 1
+                });
+                trans.push(id);
+            }
+            i += 1;
+        }
+
+        let accepting = pairs
+            .iter()
+            .map(|&(a, b)| accept(self.accepting[a], other.accepting[b]))
+            .collect();
+        Dfa {
+            classes,
+            trans,
+            accepting,
+        }
+    }
+
+    /// 状態数が最小の DFA
+    ///
+    /// 到達不能な状態を除き、区別できない状態を同一視する (Moore のアルゴリズム)。
+    /// すべての状態で遷移先が同じ隣り合う区間は1つにまとめる
+    pub fn minimize(&self) -> Dfa {
+        let reachable = self.reachable();
+        let n = self.classes.len();
+
+        // 受理状態かどうかで分割し、遷移先のブロックで区別できなくなるまで細分する
+        let mut block = self
+            .accepting
+            .iter()
+            .map(|a| *a as usize)
+            .collect::<Vec<_>>();
+        let mut count = 0;
+        loop {
+            let mut ids = HashMap::new();
+            let mut next_block = vec![0; self.len()];
+            for &s in &reachable {
+                let signature = (
+                    block[s],
+                    (0..n).map(|k| block[self.next(s, k)]).collect::<Vec<_>>(),
+                );
+                let len = ids.len();
+                next_block[s] = *ids.entry(signature).or_insert(len);
+            }
+            block = next_block;
+            if ids.len() == count {
+                break;
+            }
+            count = ids.len();
+        }
+
+        // 初期状態がブロック 0 となるよう、到達可能な状態を先頭から番号付けしている
+        let mut rows = vec![None; count];
+        let mut accepting = vec![false; count];
+        for &s in &reachable {
+            if rows[block[s]].is_none() {
+                rows[block[s]] = Some((0..n).map(|k| block[self.next(s, k)]).collect::<Vec<_>>());
+                accepting[block[s]] = self.accepting[s];
+            }
+        }
+        let rows = rows.into_iter().map(Option::unwrap).collect::<Vec<_>>();
+
+        // 隣り合う区間をまとめる
+        let mut classes: Vec<(char, char)> = Vec::new();
+        let mut columns: Vec<usize> = Vec::new();
+        for k in 0..n {
+            let same = columns
+                .last()
+                .is_some_and(|&j| rows.iter().all(|row| row[j] == row[k]));
+            if same {
+                classes.last_mut().unwrap().1 = self.classes[k].1;
+            } else {
+                classes.push(self.classes[k]);
+                columns.push(k);
+            }
+        }
+
+        let trans = rows
+            .iter()
+            .flat_map(|row| columns.iter().map(|&k| row[k]))
+            .collect();
+        Dfa {
+            classes,
+            trans,
+            accepting,
+        }
+    }
+
+    /// 初期状態から到達可能な状態を、幅優先探索で到達した順に返す
+    fn reachable(&self) -> Vec<usize> {
+        let mut visited = vec![false; self.len()];
+        let mut order = vec![0];
+        visited[0] = true;
+        let mut i = 0;
+        while i < order.len() {
+            let s = order[i];
+            for k in 0..self.classes.len() {
+                let t = self.next(s, k);
+                if !visited[t] {
+                    visited[t] = true;
+                    order.push(t);
+                }
+            }
+            i += 1;
+        }
+        order
+    }
+}
+
+/// 命令列を NFA とみなしたもの
+struct Nfa<'a> {
+    code: &'a [Instruction],
+    // 文字を消費する命令が消費できる文字の集合
+    sets: Vec<Option<CharClass>>,
+}
+
+impl<'a> Nfa<'a> {
+    /// changed が与えられた場合は、その文字を小文字に変換してから消費するものとする
+    fn new(code: &'a [Instruction], changed: Option<&[char]>) -> Self {
+        let sets = code
+            .iter()
+            .map(|inst| {
+                let class = match inst {
+                    Instruction::Char(c) => CharClass::new(&[(*c, *c)], false),
+                    Instruction::Class(class) => class.clone(),
+                    _ => return None,
+                };
+                Some(match changed {
+                    Some(changed) => unfold(&class, changed),
+                    None => class,
+                })
+            })
+            .collect();
+        Nfa { code, sets }
+    }
+
+    /// すべての命令が区別しない文字の区間に分割
+    fn classes(&self) -> Vec<(char, char)> {
+        // 区間の始点
+        let mut starts = vec![0, u32::from('\n'), u32::from('\n') + 1];
+        for class in self.sets.iter().flatten() {
+            for &(lo, hi) in class.ranges() {
+                starts.push(u32::from(lo));
+                starts.push(u32::from(hi) + 1);
+            }
+        }
+        to_classes(starts)
+    }
+
+    /// pcs から文字を消費しない遷移をたどり、到達した命令を返す
+    ///
+    /// line_start は直前の文字が改行か、文字列の先頭かどうか。
+    /// 返す命令は、文字を消費する命令と Match と、next が Unknown の場合は判定を保留した End と LineEnd
+    fn closure(&self, pcs: &[usize], line_start: bool, next: Next) -> Vec<usize> {
+        let mut visited = vec![false; self.code.len()];
+        let mut stack = pcs.to_vec();
+        let mut result = Vec::new();
+
+        while let Some(pc) = stack.pop() {
+            if std::mem::replace(&mut visited[pc], true) {
+                continue;
+            }
+            match (&self.code[pc], next) {
+                (Instruction::Jump(addr), _) => stack.push(*addr),
+                (Instruction::Split(addr1, addr2), _) => {
+                    stack.push(*addr2);
+                    stack.push(*addr1);
+                }
+                (Instruction::LineStart, _) if line_start => stack.push(pc + 1),
+                (Instruction::LineStart, _) => (),
+                (Instruction::End | Instruction::LineEnd, Next::Unknown) => result.push(pc),
+                (Instruction::End | Instruction::LineEnd, Next::Eof) => stack.push(pc + 1),
+                (Instruction::LineEnd, Next::Char('\n')) => stack.push(pc + 1),
+                (Instruction::End | Instruction::LineEnd, Next::Char(_)) => (),
+                (Instruction::Char(_) | Instruction::Class(_) | Instruction::Match, _) => {
+                    result.push(pc)
+                }
+                (
+                    Instruction::Save(_)
+                    | Instruction::AtomicStart
+                    | Instruction::AtomicEnd
+                    | Instruction::Mark(_)
+                    | Instruction::Progress(_),
+                    _,
+                ) => stack.push(pc + 1),
+                // from_program で除外している
+                (Instruction::Backref(_) | Instruction::Look(..), _) => (),
+            }
+        }
+        result.sort_unstable();
+        result
+    }
+
+    /// 部分集合構成法
+    ///
+    /// DFA の状態は、NFA の命令の集合と、直前の文字が改行か文字列の先頭かどうかの組
+    fn determinize(&self) -> Result<Dfa, DfaError> {
+        let classes = self.classes();
+        let start = (self.closure(&[0], true, Next::Unknown), true);
+
+        let mut ids = HashMap::from([(start.clone(), 0)]);
+        let mut states = vec![start];
+        let mut trans = Vec::new();
+        let mut i = 0;
+        while i < states.len() {
+            let (pcs, line_start) = states[i].clone();
+            for &(c, _) in &classes {
+                let targets = self
+                    .closure(&pcs, line_start, Next::Char(c))
+                    .into_iter()
+                    .filter(|&pc| self.sets[pc].as_ref().is_some_and(|set| set.matches(c)))
+                    .map(|pc| pc + 1)
+                    .collect::<Vec<_>>();
+                let line_start = c == '\n';
+                let state = (
+                    self.closure(&targets, line_start, Next::Unknown),
+                    line_start,
+                );
+
+                let id = match ids.get(&state) {
+                    Some(id) => *id,
+                    None => {
+                        if states.len() >= MAX_STATES {
+                            return Err(DfaError::TooManyStates);
+                        }
+                        ids.insert(state.clone(), states.len());
+                        states.push(state);
+                        states.len() - 1
+                    }
+                };
+                trans.push(id);
+            }
+            i += 1;
+        }
+
+        let accepting = states
+            .iter()
+            .map(|(pcs, line_start)| {
+                self.closure(pcs, *line_start, Next::Eof)
+                    .iter()
+                    .any(|&pc| self.code[pc] == Instruction::Match)
+            })
+            .collect();
+        Ok(Dfa {
+            classes,
+            trans,
+            accepting,
+        })
+    }
+}
+
+/// 区間の始点の列から、すべての文字を覆う区間の列を作る
+///
+/// サロゲートの範囲は文字ではないため除く
+fn to_classes(mut starts: Vec<u32>) -> Vec<(char, char)> {
+    starts.extend([0xD800, 0xE000, u32::from(char::MAX) + 1]);
+    starts.sort_unstable();
+    starts.dedup();
+
+    starts
+        .windows(2)
+        .filter(|w| w[0] != 0xD800)
+        .map(|w| {
+            (
+                char::from_u32(w[0]).unwrap(),
+                char::from_u32(w[1] - 1).unwrap(),
+            )
+        })
+        .collect()
+}
+
+/// 2つの区間の列を細分した区間の列
+fn refine(a: &[(char, char)], b: &[(char, char)]) -> Vec<(char, char)> {
+    let starts = a.iter().chain(b).map(|&(lo, _)| u32::from(lo)).collect();
+    to_classes(starts)
+}
+
+/// 代表となる文字の優先順位。小さいほど優先する
+fn rank(c: char) -> u8 {
+    if c.is_ascii_alphanumeric() {
+        0
+    } else if c.is_ascii_graphic() {
+        1
+    } else {
+        2
+    }
+}
+
+/// 区間を代表する文字
+fn representative((lo, hi): (char, char)) -> char {
+    (lo..=hi).take(128).min_by_key(|&c| rank(c)).unwrap_or(lo)
+}
+
+/// 小文字に変換すると別の文字になる文字の一覧 (昇順)
+fn case_changed() -> &'static [char] {
+    static CHANGED: OnceLock<Vec<char>> = OnceLock::new();
+    CHANGED.get_or_init(|| ('\0'..=char::MAX).filter(|&c| fold_case(c) != c).collect())
+}
+
+/// 小文字に変換してから class と照合した場合に、class にマッチする文字の集合
+///
+/// changed に含まれない文字はそのまま、含まれる文字は変換後の文字で判定する
+fn unfold(class: &CharClass, changed: &[char]) -> CharClass {
+    let mut ranges = Vec::new();
+    for &(lo, hi) in class.ranges() {
+        // 区間内の changed の文字を除く
+        let begin = changed.partition_point(|&c| c < lo);
+        let end = changed.partition_point(|&c| c <= hi);
+        let mut next = u32::from(lo);
+        for &c in &changed[begin..end] {
+            if next < u32::from(c) {
+                ranges.push((
+                    char::from_u32(next).unwrap(),
+                    char::from_u32(u32::from(c) - 1).unwrap(),
+                ));
+            }
+            next = u32::from(c) + 1;
+        }
+        if let Some(next) = char::from_u32(next).filter(|&n| n <= hi) {
+            ranges.push((next, hi));
+        }
+    }
+
+    // 変換後の文字が class の範囲に含まれる文字を加える
+    let positive = CharClass::new(class.ranges(), false);
+    for &c in changed {
+        if positive.matches(fold_case(c)) {
+            ranges.push((c, c));
+        }
+    }
+    CharClass::new(&ranges, class.negated())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{RegexBuilder, Syntax};
+
+    fn dfa(expr: &str) -> Dfa {
+        Dfa::new(expr).unwrap()
+    }
+
+    fn ere(expr: &str) -> Dfa {
+        let re = RegexBuilder::new(expr).syntax(Syntax::Ere).build().unwrap();
+        Dfa::from_program(re.program()).unwrap()
+    }
+
+    #[test]
+    fn test_from_program() {
+        let d = dfa("a(bc)*|d+");
+        for (text, expected) in [
+            ("a", true),
+            ("abcbc", true),
+            ("ddd", true),
+            ("abcb", false),
+            ("", false),
+            ("ad", false),
+        ] {
+            assert_eq!(d.is_match(text), expected, "{text}");
+        }
+        // 区間: [\0-\t], [\n], [\x0b-`], [a], [b], [c], [d], [e-\u{d7ff}], [\u{e000}-\u{10ffff}]
+        assert_eq!(d.classes().len(), 9);
+
+        let d = ere("[^b-y]x.|^$");
+        assert!(d.is_match("axあ") && d.is_match("zx\u{10ffff}") && d.is_match(""));
+        assert!(!d.is_match("cxa") && !d.is_match("ax\n"));
+
+        // 行頭と行末のアンカー
+        let d = ere("a$\n^b|(\n^)+c");
+        assert!(d.is_match("a\nb") && d.is_match("\n\nc"));
+        let d = ere("a^b|a$b");
+        assert!(d.is_empty_language());
+
+        // 大文字と小文字を区別しない
+        let re = RegexBuilder::new("[a-c]x|K")
+            .syntax(Syntax::Ere)
+            .ignore_case(true)
+            .build()
+            .unwrap();
+        let d = Dfa::from_program(re.program()).unwrap();
+        assert!(d.is_match("Bx") && d.is_match("bX") && d.is_match("k") && d.is_match("\u{212a}"));
+        assert!(!d.is_match("dx"));
+        let re = RegexBuilder::new("[^a]")
+            .syntax(Syntax::Ere)
+            .ignore_case(true)
+            .build()
+            .unwrap();
+        let d = Dfa::from_program(re.program()).unwrap();
+        assert!(!d.is_match("A") && d.is_match("B"));
+
+        // グロブ
+        let d =
+            Dfa::from_program(crate::engine::Glob::new("src/**/*.rs").unwrap().program()).unwrap();
+        assert!(d.is_match("src/a/b.rs") && !d.is_match("src/a.rsx"));
+    }
+
+    #[test]
+    fn test_minimize() {
+        let d = dfa("(a|b)*abb").minimize();
+        // 教科書どおりの 4 状態と、a でも b でもない文字で遷移する死んだ状態
+        assert_eq!(d.len(), 5);
+        assert_eq!(
+            d.classes(),
+            &[('\0', '`'), ('a', 'a'), ('b', 'b'), ('c', char::MAX)]
+        );
+        assert!(d.is_match("babb") && !d.is_match("abab"));
+
+        assert!(dfa("(a*)*b").is_equivalent(&dfa("a*b")));
+        assert_eq!(dfa("(a*)*b").minimize().len(), dfa("a*b").minimize().len());
+        assert_eq!(dfa("x|y|z").minimize().classes().len(), 3);
+    }
+
+    #[test]
+    fn test_operations() {
+        let digits = ere("[0-9]+");
+        let hex = ere("0x[0-9a-f]+|[0-9]+");
+        assert!(digits.is_subset_of(&hex));
+        assert!(!hex.is_subset_of(&digits));
+        assert_eq!(
+            hex.difference(&digits).shortest_witness().as_deref(),
+            Some("0x0")
+        );
+
+        assert!(dfa("a+").intersection(&dfa("b+")).is_empty_language());
+        assert!(!ere("/api/.*")
+            .intersection(&ere(".*/v1"))
+            .is_empty_language());
+        assert_eq!(
+            ere("/api/.*")
+                .intersection(&ere(".*/v1"))
+                .shortest_witness()
+                .as_deref(),
+            Some("/api/v1")
+        );
+
+        let all = dfa("a").union(&dfa("a").complement());
+        assert!(all.complement().is_empty_language());
+        assert!(all.is_match("") && all.is_match("xyz"));
+        assert!(dfa("a*").complement().is_match("b"));
+        assert!(dfa("(ab)*").is_equivalent(&ere("(ab){0,}")));
+        assert!(!dfa("(ab)*").is_equivalent(&dfa("(ab)+")));
+    }
+
+    #[test]
+    fn test_witness() {
+        assert_eq!(dfa("(ab)+c").shortest_witness().as_deref(), Some("abc"));
+        assert_eq!(dfa("x*").shortest_witness().as_deref(), Some(""));
+        assert_eq!(ere("[^a-z0-9]").shortest_witness().as_deref(), Some("A"));
+        assert_eq!(
+            ere("[[:punct:]]{2}").shortest_witness().as_deref(),
+            Some("!!")
+        );
+        assert_eq!(ere("\n").shortest_witness().as_deref(), Some("\n"));
+        assert_eq!(dfa("a").intersection(&dfa("b")).shortest_witness(), None);
+    }
+
+    #[test]
+    fn test_too_many_states() {
+        let re = RegexBuilder::new("(a|b)*a(a|b){14}")
+            .syntax(Syntax::Ere)
+            .build()
+            .unwrap();
+        assert!(matches!(
+            Dfa::from_program(re.program()),
+            Err(Error::Dfa(DfaError::TooManyStates))
+        ));
+    }
+
+    #[test]
+    fn test_backtracking() {
+        for expr in ["(a)\\1", "a(?=b)"] {
+            let re = RegexBuilder::new(expr).build().unwrap();
+            assert!(matches!(
+                Dfa::from_program(re.program()),
+                Err(Error::RequiresBacktracking)
+            ));
+        }
+    }
+}
@@ -0,0 +1,167 @@
+//! AST と命令列を Graphviz の DOT 形式に変換
+//!
+//! 出力は `dot -Tsvg` などで画像に変換できる。
+use super::{parser::AST, Instruction};
+use std::fmt::Write;
+
+/// AST と命令列をそれぞれサブグラフとする DOT を生成
+pub fn to_dot(ast: &AST, code: &[Instruction]) -> String {
+    let mut out = String::new();
+    out.push_str("digraph regex {\n");
+    out.push_str("    subgraph cluster_ast {\n        label = \"AST\";\n");
+    write_ast(&mut out, ast, "        ");
+    out.push_str("    }\n");
+    out.push_str("    subgraph cluster_code {\n        label = \"CODE\";\n");
+    write_code(&mut out, code, "        ");
+    out.push_str("    }\n");
+    out.push_str("}\n");
+    out
+}
+
+/// 命令列のみの DOT を生成
+pub fn code_to_dot(code: &[Instruction]) -> String {
+    let mut out = String::new();
+    out.push_str("digraph code {\n");
+    write_code(&mut out, code, "    ");
+    out.push_str("}\n");
+    out
+}
+
+/// DOT の文字列リテラル内で使えるようにエスケープ
+fn escape(s: &str) -> String {
+    let mut result = String::new();
+    for c in s.chars() {
+        match c {
+            '"' | '\\' => {
+                result.push('\\');
+                result.push(c);
+            }
+            '\n' => result.push_str("\\n"),
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
+/// AST の各ノードを `a0`, `a1`, ... という名前で出力
+///
+/// 深い入れ子でもスタックオーバーフローしないよう、明示的なスタックで走査する
+fn write_ast(out: &mut String, ast: &AST, indent: &str) {
+    // (ノード, ノードの ID, 親ノードの ID)
+    let mut stack = vec![(ast, 0, None)];
+    let mut next_id = 1;
+
+    while let Some((node, id, parent)) = stack.pop() {
+        let (label, children): (String, Vec<&AST>) = match node {
+            AST::Char(c) => (format!("'{c}'"), Vec::new()),
+            AST::Plus(e) => ("+".to_string(), vec![e]),
+            AST::Star(e) => ("*".to_string(), vec![e]),
+            AST::Question(e) => ("?".to_string(), vec![e]),
+            AST::Or(e1, e2) => ("|".to_string(), vec![e1, e2]),
+            AST::Seq(v) => ("seq".to_string(), v.iter().collect()),
+            AST::Class(class) => (class.to_string(), Vec::new()),
+            AST::End => ("end".to_string(), Vec::new()),
+            AST::LineStart => ("^".to_string(), Vec::new()),
+            AST::LineEnd => ("$".to_string(), Vec::new()),
+            AST::Capture(n, e) => (format!("group {n}"), vec![e]),
+            AST::Backref(n) => (format!("\\{n}"), Vec::new()),
+            AST::Look(look, e) => (look.prefix().trim_start_matches('(').to_string(), vec![e]),
+            AST::Atomic(e) => ("?>".to_string(), vec![e]),
+        };
+
+        let _ = writeln!(out, "{indent}a{id} [label = \"{}\"];", escape(&label));
+        if let Some(parent) = parent {
+            let _ = writeln!(out, "{indent}a{parent} -> a{id};");
+        }
+
+        // 左の子から順に出力されるよう、逆順にプッシュ
+        let first = next_id;
+        next_id += children.len();
+        for (i, child) in children.into_iter().enumerate().rev() {
+            stack.push((child, first + i, Some(id)));
+        }
+    }
+}
+
+/// 命令を `c0`, `c1`, ... という名前で出力し、遷移を辺として出力
+///
+/// Split の辺には優先順位を 1, 2 として付与する
+fn write_code(out: &mut String, code: &[Instruction], indent: &str) {
+    for (pc, inst) in code.iter().enumerate() {
+        let shape = match inst {
+            Instruction::Match => "doublecircle",
+            _ => "box",
+        };
+        let label = escape(&format!("{pc:04}\n{inst}"));
+        let _ = writeln!(out, "{indent}c{pc} [shape = {shape}, label = \"{label}\"];");
+    }
+
+    for (pc, inst) in code.iter().enumerate() {
+        match inst {
+            Instruction::Char(c) => {
+                let label = escape(&c.to_string());
+                let _ = writeln!(out, "{indent}c{pc} -> c{} [label = \"{label}\"];", pc + 1);
+            }
+            Instruction::Class(class) => {
+                let label = escape(&class.to_string());
+                let _ = writeln!(out, "{indent}c{pc} -> c{} [label = \"{label}\"];", pc + 1);
+            }
+            Instruction::End
+            | Instruction::LineStart
+            | Instruction::LineEnd
+            | Instruction::Save(_)
+            | Instruction::AtomicStart
+            | Instruction::AtomicEnd
+            | Instruction::Mark(_)
+            | Instruction::Progress(_) => {
+                let _ = writeln!(out, "{indent}c{pc} -> c{} [style = dashed];", pc + 1);
+            }
+            Instruction::Backref(n) => {
+                let _ = writeln!(out, "{indent}c{pc} -> c{} [label = \"\\\\{n}\"];", pc + 1);
+            }
+            Instruction::Look(_, addr) => {
+                // 本体への辺と、本体がマッチした後に進む辺
+                let _ = writeln!(out, "{indent}c{pc} -> c{} [style = dotted];", pc + 1);
+                let _ = writeln!(out, "{indent}c{pc} -> c{addr} [style = dashed];");
+            }
+            Instruction::Match => (),
+            Instruction::Jump(addr) => {
+                let _ = writeln!(out, "{indent}c{pc} -> c{addr} [style = dashed];");
+            }
+            Instruction::Split(addr1, addr2) => {
+                let _ = writeln!(
+                    out,
+                    "{indent}c{pc} -> c{addr1} [style = dashed, label = \"1\"];"
+                );
+                let _ = writeln!(
+                    out,
+                    "{indent}c{pc} -> c{addr2} [style = dashed, label = \"2\"];"
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{codegen::get_code, parser::parse};
+
+    #[test]
+    fn test_to_dot() {
+        let ast = parse("a|\"*").unwrap();
+        let code = get_code(&ast).unwrap();
+        let dot = to_dot(&ast, &code);
+
+        assert!(dot.starts_with("digraph regex {\n"));
+        assert!(dot.contains("a0 [label = \"|\"];"));
+        assert!(dot.contains("a0 -> a1;"));
+        assert!(dot.contains("a0 -> a2;"));
+        assert!(dot.contains("[label = \"'\\\"'\"];"));
+        assert!(dot.contains("c0 -> c1 [style = dashed, label = \"1\"];"));
+        assert!(dot.contains("c0 -> c3 [style = dashed, label = \"2\"];"));
+        assert!(dot.contains("c1 -> c2 [label = \"a\"];"));
+        assert!(dot.contains("[shape = doublecircle, label = \"0006\\nmatch\"];"));
+        assert!(dot.ends_with("}\n}\n"));
+    }
+}
@@ -0,0 +1,165 @@
+//! 公開 API が返すエラー
+//!
+//! 各段階のエラーをまとめ、失敗した原因を呼び出し側で判別できるようにする。
+//! 正規表現の誤りは Parse と RequiresBacktracking、グロブの誤りは Glob、
+//! 実装の誤りは CodeGen と Verify と Eval となる。
+//! 各段階のエラーは source で辿れる。`{:#}` で表示すると、その詳細も続けて表示する。
+use super::{
+    codegen::CodeGenError, dfa::DfaError, evaluator::EvalError, glob::GlobError,
+    parser::ParseError, verifier::VerifyError,
+};
+use std::{
+    error,
+    fmt::{self, Display},
+};
+
+/// 正規表現のコンパイルと評価のエラー
+///
+/// # 利用例
+///
+/// ```
+/// use regex::{Error, ParseError, Regex};
+/// let e = Regex::new("a(b").unwrap_err();
+/// assert!(matches!(e, Error::Parse(ParseError::NoRightParen)));
+/// assert_eq!(e.to_string(), "Error: invalid pattern");
+/// assert_eq!(format!("{e:#}"), "Error: invalid pattern: ParseError: no right parenthesis");
+/// ```
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// 正規表現の構文の誤り
+    Parse(ParseError),
+    /// コード生成の失敗
+    CodeGen(CodeGenError),
+    /// 命令列の検証エラー。コード生成器の誤りによる空ループなど
+    Verify(VerifyError),
+    /// 評価の失敗
+    Eval(EvalError),
+    /// 後方参照や先読みと後読みなどを含む式を、幅優先探索か最左最長一致で評価しようとした。
+    /// DFA に変換しようとした場合も含む
+    RequiresBacktracking,
+    /// DFA の構成の失敗
+    Dfa(DfaError),
+    /// グロブの構文の誤り
+    Glob(GlobError),
+}
+
+impl Error {
+    /// パースエラーが発生した位置 (文字単位のインデックス)
+    pub fn position(&self) -> Option<usize> {
+        match self {
+            Error::Parse(e) => e.position(),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Parse(_) => write!(f, "Error: invalid pattern")?,
+            Error::CodeGen(_) => write!(f, "Error: code generation failed")?,
+            Error::Verify(_) => write!(f, "Error: invalid code")?,
+            Error::Eval(_) => write!(f, "Error: evaluation failed")?,
+            Error::RequiresBacktracking => write!(
+                f,
+                "Error: backreferences and lookaround require the depth-first evaluator"
+            )?,
+            Error::Dfa(_) => write!(f, "Error: DFA construction failed")?,
+            Error::Glob(_) => write!(f, "Error: invalid glob")?,
+        }
+        // {:#} では原因となったエラーも表示する
+        match error::Error::source(self) {
+            Some(source) if f.alternate() => write!(f, ": {source}"),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Parse(e) => Some(e),
+            Error::CodeGen(e) => Some(e),
+            Error::Verify(e) => Some(e),
+            Error::Eval(e) => Some(e),
+            Error::RequiresBacktracking => None,
+            Error::Dfa(e) => Some(e),
+            Error::Glob(e) => Some(e),
+        }
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(e: ParseError) -> Self {
+        Error::Parse(e)
+    }
+}
+
+impl From<CodeGenError> for Error {
+    fn from(e: CodeGenError) -> Self {
+        Error::CodeGen(e)
+    }
+}
+
+impl From<VerifyError> for Error {
+    fn from(e: VerifyError) -> Self {
+        Error::Verify(e)
+    }
+}
+
+impl From<EvalError> for Error {
+    fn from(e: EvalError) -> Self {
+        Error::Eval(e)
+    }
+}
+
+impl From<DfaError> for Error {
+    fn from(e: DfaError) -> Self {
+        Error::Dfa(e)
+    }
+}
+
+impl From<GlobError> for Error {
+    fn from(e: GlobError) -> Self {
+        Error::Glob(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{compile, Regex, RegexBuilder};
+    use std::error::Error as _;
+
+    #[test]
+    fn test_error() {
+        let e = compile("a|*").unwrap_err();
+        assert!(matches!(e, Error::Parse(ParseError::NoPrev(2))));
+        assert_eq!(e.position(), Some(2));
+        assert_eq!(e.to_string(), "Error: invalid pattern");
+        assert!(e.source().unwrap().is::<ParseError>());
+        assert_eq!(
+            format!("{e:#}"),
+            "Error: invalid pattern: ParseError: no previous expression: pos = 2"
+        );
+
+        let e = Error::from(VerifyError::EmptyLoop(0));
+        assert!(matches!(e, Error::Verify(VerifyError::EmptyLoop(0))));
+        assert_eq!(e.position(), None);
+
+        let longest = |expr: &str| RegexBuilder::new(expr).longest(true).build();
+        let e = longest("(a)\\1").unwrap_err();
+        assert!(matches!(e, Error::RequiresBacktracking));
+        assert!(e.source().is_none());
+        assert_eq!(
+            format!("{e:#}"),
+            "Error: backreferences and lookaround require the depth-first evaluator"
+        );
+
+        // Box<dyn Error> に変換できる
+        let expr = "(";
+        let boxed: Box<dyn error::Error + Send + Sync> = Regex::new(expr).unwrap_err().into();
+        assert!(boxed.downcast_ref::<Error>().is_some());
+    }
+}
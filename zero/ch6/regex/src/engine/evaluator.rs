@@ -1,96 +1,468 @@
 //! 評価機
 //!
 //! コード生成器が生成した命令列と、マッチを行う文字列を受け取り結果を返す
-use super::Instruction;
+use super::{
+    parser::MAX_DEPTH,
+    trace::{NopTracer, Tracer},
+    Error, Instruction, Look,
+};
 use crate::helper::safe_add;
 use std::{
-    collections::VecDeque,
-    error::Error,
+    collections::{HashMap, HashSet, VecDeque},
+    error,
     fmt::{self, Display},
 };
 
+/// 評価エラーを表す型
 #[derive(Debug)]
 pub enum EvalError {
     PCOverFlow,
     SPOverFlow,
     InvalidPC,
     InvalidContext,
+    // トレーサが評価を打ち切った
+    Aborted,
+    // 先読みと後読みの入れ子が深すぎる
+    TooDeep,
 }
 
 impl Display for EvalError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "EvalError: {:?}", self)
+        match self {
+            EvalError::TooDeep => {
+                write!(
+                    f,
+                    "EvalError: lookaround nesting too deep: max = {MAX_DEPTH}"
+                )
+            }
+            EvalError::Aborted => write!(f, "EvalError: aborted by the tracer"),
+            _ => write!(f, "EvalError: {:?}", self),
+        }
     }
 }
 
-impl Error for EvalError {}
+impl error::Error for EvalError {}
 
 /// 命令列の評価を行う関数
 ///
 /// inst が命令列で、line が入力文字列
 /// is_depth が true なら 深さ優先探索、false なら幅優先探索
-pub fn eval(inst: &[Instruction], line: &[char], is_depth: bool)
-            -> Result<bool, EvalError> {
+pub fn eval(inst: &[Instruction], line: &[char], is_depth: bool) -> Result<bool, Error> {
+    eval_traced(inst, line, is_depth, &mut NopTracer)
+}
+
+/// 命令を1つ実行するたびに tracer へ通知しながら評価を行う関数
+///
+/// スレッド ID は 0 から始まり、Split で分岐するたびに新しい ID が割り当てられる
+pub fn eval_traced<T: Tracer>(
+    inst: &[Instruction],
+    line: &[char],
+    is_depth: bool,
+    tracer: &mut T,
+) -> Result<bool, Error> {
+    Ok(eval_from(inst, line, 0, is_depth, tracer)?.is_some())
+}
+
+/// 文字列の start 番目の文字から評価を行う関数
+///
+/// マッチした場合は、マッチが終了した位置 (文字単位のインデックス) を返す。
+/// 複数の位置でマッチを終えられる場合は、優先順位が最も高いものを返す
+pub fn eval_from<T: Tracer>(
+    inst: &[Instruction],
+    line: &[char],
+    start: usize,
+    is_depth: bool,
+    tracer: &mut T,
+) -> Result<Option<usize>, Error> {
     if is_depth {
-        eval_depth(inst, line, 0, 0)
+        Ok(eval_depth(inst, line, start, tracer)?)
     } else {
-        eval_width(inst, line)
+        eval_width(inst, line, start, tracer)
+    }
+}
+
+/// 文字を消費する命令が、文字 c を消費できるかを判定
+fn consumes(inst: &Instruction, c: char) -> bool {
+    match inst {
+        Instruction::Char(d) => *d == c,
+        Instruction::Class(class) => class.matches(c),
+        _ => false,
+    }
+}
+
+/// 文字を消費しない命令が、文字列の sp 番目の位置で成功するかを判定
+fn asserts(inst: &Instruction, line: &[char], sp: usize) -> bool {
+    match inst {
+        Instruction::End => sp == line.len(),
+        Instruction::LineStart => sp == 0 || line.get(sp - 1) == Some(&'\n'),
+        Instruction::LineEnd => sp == line.len() || line.get(sp) == Some(&'\n'),
+        _ => false,
     }
 }
 
-/// 深さ優先探索で再帰的にマッチングを行う評価器
-fn eval_depth(
+/// 深さ優先探索でマッチングを行う評価器
+///
+/// 長い文字列でスタックオーバーフローしないよう、再帰の代わりに
+/// バックトラック先 (pc, sp, スレッド ID) を明示的なスタックに保存する。
+/// アトミックグループの中で保存した分岐は、グループを抜けるときに捨てる
+fn eval_depth<T: Tracer>(
     inst: &[Instruction],
     line: &[char],
-    mut pc: usize,
-    mut sp: usize,
-) -> Result<bool, EvalError> {
-    loop {
-        let next = if let Some(i) = inst.get(pc) {
-            i
-        } else {
-            return Err(EvalError::InvalidPC);
-        };
+    start: usize,
+    tracer: &mut T,
+) -> Result<Option<usize>, EvalError> {
+    let mut backtrack = Backtrack {
+        inst,
+        line,
+        tracer,
+        threads: 1,
+        depth: 0,
+        marks: Vec::new(),
+        widths: HashMap::new(),
+    };
+    backtrack.run(0, start, 0, None, &mut Vec::new())
+}
 
-        match next {
-            Instruction::Char(c) => {
-                if let Some(sp_c) = line.get(sp) {
-                    if c == sp_c {
+/// eval_depth のバックトラック先
+enum Frame {
+    // Split の2つ目の分岐 (pc, sp, スレッド ID)
+    Branch(usize, usize, usize),
+    // Save で上書きしたキャプチャの位置 (スロット, 以前の値)
+    Restore(usize, Option<usize>),
+    // AtomicStart で置いたバックトラック先の区切り
+    Barrier,
+    // Mark で上書きしたレジスタの値 (レジスタ, 以前の値)
+    Unmark(usize, Option<usize>),
+}
+
+/// eval_depth の状態
+struct Backtrack<'a, T> {
+    inst: &'a [Instruction],
+    line: &'a [char],
+    tracer: &'a mut T,
+    threads: usize,
+    // 評価中の先読みと後読みの入れ子の深さ
+    depth: usize,
+    // Mark で保存した位置
+    marks: Vec<Option<usize>>,
+    // 後読みの本体の先頭の pc ごとの、本体が消費する文字数の範囲
+    widths: HashMap<usize, (usize, Option<usize>)>,
+}
+
+impl<T: Tracer> Backtrack<'_, T> {
+    /// pc の命令から、文字列の sp 番目の位置で評価を始める
+    ///
+    /// until を指定した場合は、その位置で Match に到達した場合のみ成功とする。
+    /// slots はキャプチャの位置で、成功した場合はマッチした経路での値となる
+    fn run(
+        &mut self,
+        mut pc: usize,
+        mut sp: usize,
+        mut thread: usize,
+        until: Option<usize>,
+        slots: &mut Vec<Option<usize>>,
+    ) -> Result<Option<usize>, EvalError> {
+        let mut stack = Vec::new();
+
+        loop {
+            let next = if let Some(i) = self.inst.get(pc) {
+                i
+            } else {
+                return Err(EvalError::InvalidPC);
+            };
+            if self.tracer.step(pc, sp, thread, next).is_break() {
+                return Err(EvalError::Aborted);
+            }
+
+            let matched = match next {
+                Instruction::Char(_) | Instruction::Class(_) => {
+                    if self.line.get(sp).is_some_and(|c| consumes(next, *c)) {
                         safe_add(&mut pc, &1, || EvalError::PCOverFlow)?;
                         safe_add(&mut sp, &1, || EvalError::SPOverFlow)?;
+                        true
                     } else {
-                        return Ok(false);
+                        false
+                    }
+                }
+                Instruction::Match => {
+                    if until.is_none_or(|until| until == sp) {
+                        return Ok(Some(sp));
+                    }
+                    false
+                }
+                Instruction::Jump(addr) => {
+                    pc = *addr;
+                    true
+                }
+                Instruction::Split(addr1, addr2) => {
+                    // 1つ目の分岐は現在のスレッドで評価し、
+                    // 2つ目の分岐は新しいスレッドとしてバックトラック先に保存
+                    stack.push(Frame::Branch(*addr2, sp, self.threads));
+                    self.threads += 1;
+                    pc = *addr1;
+                    true
+                }
+                Instruction::End | Instruction::LineStart | Instruction::LineEnd => {
+                    if asserts(next, self.line, sp) {
+                        safe_add(&mut pc, &1, || EvalError::PCOverFlow)?;
+                        true
+                    } else {
+                        false
+                    }
+                }
+                Instruction::Save(slot) => {
+                    save(slots, &mut stack, *slot, Some(sp));
+                    safe_add(&mut pc, &1, || EvalError::PCOverFlow)?;
+                    true
+                }
+                Instruction::Backref(n) => {
+                    // キャプチャしていないグループへの後方参照は失敗する
+                    let start = slots.get(2 * n).copied().flatten();
+                    let end = slots.get(2 * n + 1).copied().flatten();
+                    match (start, end) {
+                        (Some(start), Some(end)) if start <= end => {
+                            let rest = &self.line[sp.min(self.line.len())..];
+                            if rest.starts_with(&self.line[start..end]) {
+                                safe_add(&mut pc, &1, || EvalError::PCOverFlow)?;
+                                safe_add(&mut sp, &(end - start), || EvalError::SPOverFlow)?;
+                                true
+                            } else {
+                                false
+                            }
+                        }
+                        _ => false,
+                    }
+                }
+                Instruction::Look(look, addr) => {
+                    let found = self.look(*look, pc + 1, sp, thread, slots)?;
+                    if found.is_some() != look.negated() {
+                        // 肯定の先読みと後読みでキャプチャした位置は、その後も参照できる
+                        for (slot, value) in found.into_iter().flatten().enumerate() {
+                            if slots.get(slot).copied().flatten() != value {
+                                save(slots, &mut stack, slot, value);
+                            }
+                        }
+                        pc = *addr;
+                        true
+                    } else {
+                        false
+                    }
+                }
+                Instruction::AtomicStart => {
+                    stack.push(Frame::Barrier);
+                    safe_add(&mut pc, &1, || EvalError::PCOverFlow)?;
+                    true
+                }
+                Instruction::AtomicEnd => {
+                    cut(&mut stack);
+                    safe_add(&mut pc, &1, || EvalError::PCOverFlow)?;
+                    true
+                }
+                Instruction::Mark(n) => {
+                    if self.marks.len() <= *n {
+                        self.marks.resize(n + 1, None);
+                    }
+                    stack.push(Frame::Unmark(*n, self.marks[*n]));
+                    self.marks[*n] = Some(sp);
+                    safe_add(&mut pc, &1, || EvalError::PCOverFlow)?;
+                    true
+                }
+                Instruction::Progress(n) => {
+                    // 繰り返しの本体が文字を消費していなければ失敗する
+                    if self.marks.get(*n).copied().flatten() != Some(sp) {
+                        safe_add(&mut pc, &1, || EvalError::PCOverFlow)?;
+                        true
+                    } else {
+                        false
+                    }
+                }
+            };
+
+            if !matched {
+                loop {
+                    match stack.pop() {
+                        Some(Frame::Branch(p, s, t)) => {
+                            pc = p;
+                            sp = s;
+                            thread = t;
+                            break;
+                        }
+                        Some(Frame::Restore(slot, value)) => slots[slot] = value,
+                        Some(Frame::Unmark(n, value)) => self.marks[n] = value,
+                        // アトミックグループの本体が失敗した
+                        Some(Frame::Barrier) => (),
+                        None => return Ok(None),
                     }
-                } else {
-                    return Ok(false);
                 }
             }
-            Instruction::Match => {
-                return Ok(true);
+        }
+    }
+
+    /// pc から始まる先読みと後読みの本体を、文字列の sp 番目の位置で評価する
+    ///
+    /// 後読みは sp で終わるマッチを、sp に近い開始位置から順に探す。
+    /// 開始位置は、本体が消費し得る文字数の範囲に限る。
+    /// 本体がマッチした場合は、そのときのキャプチャの位置を返す
+    fn look(
+        &mut self,
+        look: Look,
+        pc: usize,
+        sp: usize,
+        thread: usize,
+        slots: &[Option<usize>],
+    ) -> Result<Option<Vec<Option<usize>>>, EvalError> {
+        if self.depth >= MAX_DEPTH {
+            return Err(EvalError::TooDeep);
+        }
+
+        let (starts, until) = if look.is_behind() {
+            let (min, max) = *self
+                .widths
+                .entry(pc)
+                .or_insert_with(|| width(self.inst, pc));
+            let Some(last) = sp.checked_sub(min) else {
+                return Ok(None);
+            };
+            (max.map_or(0, |max| sp.saturating_sub(max))..=last, Some(sp))
+        } else {
+            (sp..=sp, None)
+        };
+        self.depth += 1;
+        let mut found = None;
+        for start in starts.rev() {
+            let mut inner = slots.to_vec();
+            if self.run(pc, start, thread, until, &mut inner)?.is_some() {
+                found = Some(inner);
+                break;
             }
-            Instruction::Jump(addr) => {
-                pc = *addr;
+        }
+
+        self.depth -= 1;
+        Ok(found)
+    }
+}
+
+/// pc から始まる先読みと後読みの本体が消費する文字数の最小値と最大値
+///
+/// 本体がループか後方参照を含み、最大値が定まらない場合は None とする。
+/// 入れ子の先読みと後読みは文字を消費しない
+fn width(inst: &[Instruction], pc: usize) -> (usize, Option<usize>) {
+    if pc >= inst.len() {
+        return (0, None);
+    }
+
+    // 進み先と、そのときに消費する文字数。後方参照は None
+    let edges = |pc: usize| match inst.get(pc) {
+        Some(Instruction::Char(_) | Instruction::Class(_)) => vec![(pc + 1, Some(1))],
+        Some(Instruction::Match) | None => Vec::new(),
+        Some(Instruction::Jump(addr)) => vec![(*addr, Some(0))],
+        Some(Instruction::Split(addr1, addr2)) => vec![(*addr1, Some(0)), (*addr2, Some(0))],
+        Some(Instruction::Backref(_)) => vec![(pc + 1, None)],
+        Some(Instruction::Look(_, addr)) => vec![(*addr, Some(0))],
+        Some(_) => vec![(pc + 1, Some(0))],
+    };
+
+    // 最小値は、消費する文字数を重みとした最短経路
+    let mut dist = vec![usize::MAX; inst.len()];
+    let mut queue = VecDeque::from([(pc, 0)]);
+    let mut min = 0;
+    while let Some((pc, d)) = queue.pop_front() {
+        if dist.get(pc).is_none_or(|&e| e <= d) {
+            continue;
+        }
+        dist[pc] = d;
+        if inst[pc] == Instruction::Match {
+            min = d;
+            break;
+        }
+        for (next, w) in edges(pc) {
+            if w == Some(1) {
+                queue.push_back((next, d + 1));
+            } else {
+                queue.push_front((next, d));
             }
-            Instruction::Split(addr1, addr2) => {
-                if eval_depth(inst, line, *addr1, sp)? ||
-                    eval_depth(inst, line, *addr2, sp)? {
-                    return Ok(true);
-                } else {
-                    return Ok(false);
+        }
+    }
+
+    // 最大値は、ループがなければ後行順に求めた最長経路
+    #[derive(Clone, Copy, PartialEq)]
+    enum Mark {
+        New,
+        Active,
+        Done,
+    }
+    let mut mark = vec![Mark::New; inst.len()];
+    // 各命令から Match までに消費する文字数の最大値。Match に到達しない場合は None
+    let mut longest = vec![None; inst.len()];
+    let mut stack = vec![(pc, 0)];
+    mark[pc] = Mark::Active;
+    while let Some((pc, i)) = stack.pop() {
+        let next = edges(pc);
+        if let Some(&(next, _)) = next.get(i) {
+            stack.push((pc, i + 1));
+            match mark.get(next) {
+                Some(Mark::New) => {
+                    mark[next] = Mark::Active;
+                    stack.push((next, 0));
                 }
+                Some(Mark::Active) | None => return (min, None),
+                Some(Mark::Done) => (),
             }
+            continue;
+        }
+
+        mark[pc] = Mark::Done;
+        if inst[pc] == Instruction::Match {
+            longest[pc] = Some(0);
+        }
+        for (next, w) in next {
+            match (w, longest[next]) {
+                (_, None) => (),
+                (None, Some(_)) => return (min, None),
+                (Some(w), Some(l)) => {
+                    longest[pc] = Some(longest[pc].map_or(w + l, |m: usize| m.max(w + l)))
+                }
+            }
+        }
+    }
+    (min, longest[pc])
+}
+
+/// 直近の区切りより後ろにある分岐を捨て、区切りを取り除く
+///
+/// キャプチャの位置とレジスタを元に戻すためのバックトラック先は、
+/// アトミックグループより前に戻ったときに必要となるため残す
+fn cut(stack: &mut Vec<Frame>) {
+    let mut restores = Vec::new();
+    while let Some(frame) = stack.pop() {
+        match frame {
+            Frame::Branch(..) => (),
+            Frame::Restore(..) | Frame::Unmark(..) => restores.push(frame),
+            Frame::Barrier => break,
         }
     }
+    stack.extend(restores.into_iter().rev());
+}
+
+/// キャプチャの位置を書き換え、元に戻すためのバックトラック先を保存
+fn save(slots: &mut Vec<Option<usize>>, stack: &mut Vec<Frame>, slot: usize, value: Option<usize>) {
+    if slots.len() <= slot {
+        slots.resize(slot + 1, None);
+    }
+    stack.push(Frame::Restore(slot, slots[slot]));
+    slots[slot] = value;
 }
 
 fn pop_ctx(
     pc: &mut usize,
     sp: &mut usize,
-    ctx: &mut VecDeque<(usize, usize)>,
+    thread: &mut usize,
+    ctx: &mut VecDeque<(usize, usize, usize)>,
 ) -> Result<(), EvalError> {
-    if let Some((p, s)) = ctx.pop_back() {
+    if let Some((p, s, t)) = ctx.pop_back() {
         *pc = p;
         *sp = s;
+        *thread = t;
         Ok(())
     } else {
         Err(EvalError::InvalidContext)
@@ -98,55 +470,206 @@ fn pop_ctx(
 }
 
 /// 幅優先探索で再帰的にマッチングを行う評価器
-fn eval_width(inst: &[Instruction], line: &[char]) -> Result<bool, EvalError> {
+///
+/// ctx には (pc, sp, スレッド ID) を保存する
+fn eval_width<T: Tracer>(
+    inst: &[Instruction],
+    line: &[char],
+    start: usize,
+    tracer: &mut T,
+) -> Result<Option<usize>, Error> {
     let mut ctx = VecDeque::new();
     let mut pc = 0;
-    let mut sp = 0;
+    let mut sp = start;
+    let mut thread = 0;
+    let mut threads = 1;
+    // Progress に到達した (pc, sp)。同じ位置で再び到達したスレッドは空ループとして失敗させる
+    let mut progressed = HashSet::new();
 
     loop {
         let next = if let Some(i) = inst.get(pc) {
             i
         } else {
-            return Err(EvalError::InvalidPC);
+            return Err(EvalError::InvalidPC.into());
         };
+        if tracer.step(pc, sp, thread, next).is_break() {
+            return Err(EvalError::Aborted.into());
+        }
 
         match next {
-            Instruction::Char(c) => {
+            Instruction::Char(_) | Instruction::Class(_) => {
                 if let Some(sp_c) = line.get(sp) {
-                    if c == sp_c {
+                    if consumes(next, *sp_c) {
                         safe_add(&mut pc, &1, || EvalError::PCOverFlow)?;
                         safe_add(&mut sp, &1, || EvalError::SPOverFlow)?;
                     } else {
                         if ctx.is_empty() {
-                            return Ok(false);
+                            return Ok(None);
                         } else {
-                            pop_ctx(&mut pc, &mut sp, &mut ctx)?;
+                            pop_ctx(&mut pc, &mut sp, &mut thread, &mut ctx)?;
                         }
                     }
                 } else {
                     if ctx.is_empty() {
-                        return Ok(false);
+                        return Ok(None);
                     } else {
-                        pop_ctx(&mut pc, &mut sp, &mut ctx)?;
+                        pop_ctx(&mut pc, &mut sp, &mut thread, &mut ctx)?;
                     }
                 }
             }
             Instruction::Match => {
-                return Ok(true);
+                return Ok(Some(sp));
             }
             Instruction::Jump(addr) => {
                 pc = *addr;
             }
             Instruction::Split(addr1, addr2) => {
                 pc = *addr1;
-                ctx.push_back((*addr2, sp));
+                ctx.push_back((*addr2, sp, threads));
+                threads += 1;
                 continue;
             }
+            Instruction::End | Instruction::LineStart | Instruction::LineEnd => {
+                if asserts(next, line, sp) {
+                    safe_add(&mut pc, &1, || EvalError::PCOverFlow)?;
+                } else if ctx.is_empty() {
+                    return Ok(None);
+                } else {
+                    pop_ctx(&mut pc, &mut sp, &mut thread, &mut ctx)?;
+                }
+            }
+            Instruction::Mark(_) => {
+                safe_add(&mut pc, &1, || EvalError::PCOverFlow)?;
+            }
+            Instruction::Progress(_) => {
+                if progressed.insert((pc, sp)) {
+                    safe_add(&mut pc, &1, || EvalError::PCOverFlow)?;
+                } else if ctx.is_empty() {
+                    return Ok(None);
+                } else {
+                    pop_ctx(&mut pc, &mut sp, &mut thread, &mut ctx)?;
+                }
+            }
+            Instruction::Save(_)
+            | Instruction::Backref(_)
+            | Instruction::Look(..)
+            | Instruction::AtomicStart
+            | Instruction::AtomicEnd => {
+                return Err(Error::RequiresBacktracking);
+            }
         }
 
         if !ctx.is_empty() {
-            ctx.push_back((pc, sp));
-            pop_ctx(&mut pc, &mut sp, &mut ctx)?;
+            ctx.push_back((pc, sp, thread));
+            pop_ctx(&mut pc, &mut sp, &mut thread, &mut ctx)?;
+        }
+    }
+}
+
+/// 最左最長一致でマッチングを行う評価器
+///
+/// 文字列の各位置で到達しうる pc の集合を1文字ずつ同時に進め、
+/// Match に到達した最も後ろの位置を返す。
+/// 同じ位置で同じ pc に到達したスレッドは1つにまとめるため、
+/// 命令数と文字数の積に比例する時間で評価できる
+pub fn eval_longest<T: Tracer>(
+    inst: &[Instruction],
+    line: &[char],
+    start: usize,
+    tracer: &mut T,
+) -> Result<Option<usize>, Error> {
+    let mut sim = Longest {
+        inst,
+        line,
+        seen: vec![usize::MAX; inst.len()],
+        last: None,
+        threads: 1,
+        tracer,
+    };
+
+    // 文字を消費する命令で待機しているスレッド (pc, スレッド ID)
+    let mut current = Vec::new();
+    sim.add(start, 0, 0, &mut current)?;
+
+    let mut sp = start;
+    while !current.is_empty() {
+        let Some(c) = line.get(sp) else { break };
+        let mut next_sp = sp;
+        safe_add(&mut next_sp, &1, || EvalError::SPOverFlow)?;
+
+        let mut next = Vec::new();
+        for (pc, thread) in current {
+            if consumes(&inst[pc], *c) {
+                sim.add(next_sp, pc + 1, thread, &mut next)?;
+            }
+        }
+        current = next;
+        sp = next_sp;
+    }
+    Ok(sim.last)
+}
+
+/// eval_longest の状態
+struct Longest<'a, T> {
+    inst: &'a [Instruction],
+    line: &'a [char],
+    // 各 pc に最後に到達した位置
+    seen: Vec<usize>,
+    // Match に到達した最も後ろの位置
+    last: Option<usize>,
+    threads: usize,
+    tracer: &'a mut T,
+}
+
+impl<T: Tracer> Longest<'_, T> {
+    /// 位置 sp で pc に到達したスレッドを、文字を消費する命令まで進めて list に加える
+    ///
+    /// 文字を消費しない命令は明示的なスタックでたどる
+    fn add(
+        &mut self,
+        sp: usize,
+        pc: usize,
+        thread: usize,
+        list: &mut Vec<(usize, usize)>,
+    ) -> Result<(), Error> {
+        let mut stack = vec![(pc, thread)];
+        while let Some((pc, thread)) = stack.pop() {
+            let Some(next) = self.inst.get(pc) else {
+                return Err(EvalError::InvalidPC.into());
+            };
+            if self.seen[pc] == sp {
+                continue;
+            }
+            self.seen[pc] = sp;
+            if self.tracer.step(pc, sp, thread, next).is_break() {
+                return Err(EvalError::Aborted.into());
+            }
+
+            match next {
+                Instruction::Char(_) | Instruction::Class(_) => list.push((pc, thread)),
+                Instruction::Match => self.last = Some(sp),
+                Instruction::Jump(addr) => stack.push((*addr, thread)),
+                Instruction::Split(addr1, addr2) => {
+                    stack.push((*addr2, self.threads));
+                    self.threads += 1;
+                    stack.push((*addr1, thread));
+                }
+                Instruction::End | Instruction::LineStart | Instruction::LineEnd => {
+                    if asserts(next, self.line, sp) {
+                        stack.push((pc + 1, thread));
+                    }
+                }
+                // 同じ位置で同じ pc に到達したスレッドはまとめるため、空ループにならない
+                Instruction::Mark(_) | Instruction::Progress(_) => stack.push((pc + 1, thread)),
+                Instruction::Save(_)
+                | Instruction::Backref(_)
+                | Instruction::Look(..)
+                | Instruction::AtomicStart
+                | Instruction::AtomicEnd => {
+                    return Err(Error::RequiresBacktracking);
+                }
+            }
         }
+        Ok(())
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,513 @@
+//! 正規表現の字句の分類と説明文の生成
+//!
+//! コードレビューなどで式を読みやすくするため、式を字句に分けて種類ごとに色付けできるようにし、
+//! AST を辿って各部分を英語または日本語で説明する。
+//! 説明の各行にも字句と同じ種類を付与し、式と同じ色で表示できるようにする。
+use super::{
+    parser::{self, AST},
+    Error, Look,
+};
+use std::{
+    fmt::{self, Display},
+    ops::Range,
+};
+
+/// 説明文の言語
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Language {
+    #[default]
+    English,
+    Japanese,
+}
+
+/// 字句の種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TokenKind {
+    /// `a` などの文字
+    Literal,
+    /// `\*` などのエスケープされた文字
+    Escape,
+    /// `+` `*` `?` と強欲な量指定子の `++` など
+    Quantifier,
+    /// `(` `)` と `(?=` `(?>` などのカッコ
+    Group,
+    /// `|`
+    Alternation,
+    /// `\1` などの後方参照
+    Backref,
+}
+
+/// 式の字句
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    kind: TokenKind,
+    span: Range<usize>,
+}
+
+impl Token {
+    pub fn kind(&self) -> TokenKind {
+        self.kind
+    }
+
+    /// 字句の範囲 (文字単位のインデックス)
+    pub fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+}
+
+/// 式を字句に分ける
+///
+/// 字句の範囲は重ならず、すべてをつなげると式全体となる。
+/// 式に誤りがある場合はErrを返す
+///
+/// # 利用例
+///
+/// ```
+/// use regex::TokenKind;
+/// let tokens = regex::tokenize("a\\++").unwrap();
+/// let kinds = tokens.iter().map(|t| t.kind()).collect::<Vec<_>>();
+/// assert_eq!(kinds, [TokenKind::Literal, TokenKind::Escape, TokenKind::Quantifier]);
+/// ```
+pub fn tokenize(expr: &str) -> Result<Vec<Token>, Error> {
+    parser::parse(expr)?;
+
+    let chars = expr.chars().collect::<Vec<_>>();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    // 直前の字句が量指定子かどうか。量指定子に続く + は強欲な量指定子とする
+    let mut quantifier = false;
+    while i < chars.len() {
+        let (kind, len) = match chars[i] {
+            '*' | '+' | '?' => (TokenKind::Quantifier, 1),
+            '(' => match chars[i + 1..] {
+                ['?', '<', '=' | '!', ..] => (TokenKind::Group, 4),
+                ['?', '=' | '!' | '>', ..] => (TokenKind::Group, 3),
+                _ => (TokenKind::Group, 1),
+            },
+            ')' => (TokenKind::Group, 1),
+            '|' => (TokenKind::Alternation, 1),
+            '\\' if chars[i + 1].is_ascii_digit() => (TokenKind::Backref, 2),
+            '\\' => (TokenKind::Escape, 2),
+            _ => (TokenKind::Literal, 1),
+        };
+
+        // 強欲な量指定子は直前の量指定子とつなげる
+        let possessive = quantifier && chars[i] == '+';
+        match tokens.last_mut() {
+            Some(Token { span, .. }) if possessive => span.end += 1,
+            _ => tokens.push(Token {
+                kind,
+                span: i..i + len,
+            }),
+        }
+        quantifier = kind == TokenKind::Quantifier && !possessive;
+        i += len;
+    }
+    Ok(tokens)
+}
+
+/// 式の説明
+///
+/// 1 つの AST のノードに対応し、子を持つノードは見出しと子の説明からなる。
+/// Display は子を 2 文字ずつ字下げした木として出力する
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Explanation {
+    kind: Option<TokenKind>,
+    label: String,
+    summary: String,
+    children: Vec<Explanation>,
+}
+
+impl Explanation {
+    /// 対応する字句の種類。文字の並びの場合は None
+    pub fn kind(&self) -> Option<TokenKind> {
+        self.kind
+    }
+
+    /// 子を持つ場合は `one or more of:` などの見出し、持たない場合は説明
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    /// 子を含めた 1 行の説明。`one or more of: 'a' followed by 'b'` など
+    pub fn summary(&self) -> &str {
+        &self.summary
+    }
+
+    pub fn children(&self) -> &[Explanation] {
+        &self.children
+    }
+
+    fn leaf(kind: TokenKind, label: String) -> Self {
+        Explanation {
+            kind: Some(kind),
+            summary: label.clone(),
+            label,
+            children: Vec::new(),
+        }
+    }
+
+    /// 見出しと 1 つの子からなる説明
+    fn unary(kind: TokenKind, label: String, child: Explanation) -> Self {
+        Explanation {
+            kind: Some(kind),
+            summary: format!("{label} {}", child.summary),
+            label,
+            children: vec![child],
+        }
+    }
+
+    /// 並びや選択の要素として、子を持つ場合はカッコで囲んだ説明
+    fn operand(&self) -> String {
+        if self.children.is_empty() {
+            self.summary.clone()
+        } else {
+            format!("({})", self.summary)
+        }
+    }
+
+    fn write_tree(&self, f: &mut fmt::Formatter<'_>, depth: usize) -> fmt::Result {
+        writeln!(f, "{:indent$}{}", "", self.label, indent = depth * 2)?;
+        for child in self.children.iter() {
+            child.write_tree(f, depth + 1)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_tree(f, 0)
+    }
+}
+
+/// 式をパースし、各部分の説明を返す
+///
+/// 式に誤りがある場合はErrを返す
+///
+/// # 利用例
+///
+/// ```
+/// use regex::Language;
+/// let e = regex::explain("(ab)+", Language::English).unwrap();
+/// assert_eq!(e.summary(), "one or more of: 'a' followed by 'b'");
+/// assert_eq!(e.to_string(), "one or more of:\n  in sequence:\n    'a'\n    'b'\n");
+///
+/// let e = regex::explain("a|b", Language::Japanese).unwrap();
+/// assert_eq!(e.summary(), "「a」または「b」のいずれか");
+/// ```
+pub fn explain(expr: &str, lang: Language) -> Result<Explanation, Error> {
+    let ast = parser::parse(expr)?;
+    Ok(explain_ast(&ast, lang))
+}
+
+/// AST のノードを説明
+///
+/// AST の深さはパース時に制限されているため、再帰で辿る
+fn explain_ast(ast: &AST, lang: Language) -> Explanation {
+    let en = lang == Language::English;
+    match ast {
+        AST::Char(c) => Explanation::leaf(TokenKind::Literal, quote(*c, lang)),
+        AST::Class(class) => {
+            let label = if en {
+                format!("a character in {class}")
+            } else {
+                format!("{class} の文字")
+            };
+            Explanation::leaf(TokenKind::Literal, label)
+        }
+        AST::End => Explanation::leaf(
+            TokenKind::Literal,
+            text(lang, "the end of the text", "文字列の末尾").to_string(),
+        ),
+        AST::LineStart => Explanation::leaf(
+            TokenKind::Literal,
+            text(lang, "the start of a line", "行頭").to_string(),
+        ),
+        AST::LineEnd => Explanation::leaf(
+            TokenKind::Literal,
+            text(lang, "the end of a line", "行末").to_string(),
+        ),
+        AST::Backref(n) => {
+            let label = if en {
+                format!("the text matched by group {n}")
+            } else {
+                format!("グループ {n} と同じ文字列")
+            };
+            Explanation::leaf(TokenKind::Backref, label)
+        }
+        AST::Plus(e) | AST::Star(e) | AST::Question(e) => Explanation::unary(
+            TokenKind::Quantifier,
+            quantifier(ast, false, lang),
+            explain_ast(e, lang),
+        ),
+        AST::Atomic(e) => match e.as_ref() {
+            // 強欲な量指定子
+            AST::Plus(inner) | AST::Star(inner) | AST::Question(inner) => Explanation::unary(
+                TokenKind::Quantifier,
+                quantifier(e, true, lang),
+                explain_ast(inner, lang),
+            ),
+            _ => Explanation::unary(
+                TokenKind::Group,
+                text(lang, "atomic group:", "アトミックグループ:").to_string(),
+                explain_ast(e, lang),
+            ),
+        },
+        AST::Capture(n, e) => {
+            let label = if en {
+                format!("group {n}:")
+            } else {
+                format!("グループ {n}:")
+            };
+            Explanation::unary(TokenKind::Group, label, explain_ast(e, lang))
+        }
+        AST::Look(look, e) => {
+            let label = match look {
+                Look::Ahead => text(lang, "if followed by:", "直後が次に一致する場合:"),
+                Look::NotAhead => text(lang, "if not followed by:", "直後が次に一致しない場合:"),
+                Look::Behind => text(lang, "if preceded by:", "直前が次に一致する場合:"),
+                Look::NotBehind => text(lang, "if not preceded by:", "直前が次に一致しない場合:"),
+            };
+            Explanation::unary(TokenKind::Group, label.to_string(), explain_ast(e, lang))
+        }
+        AST::Seq(_) => {
+            // 入れ子の並びは 1 つの並びとみなす
+            let mut items = Vec::new();
+            flatten_seq(ast, &mut items);
+            match items.len() {
+                0 => Explanation::leaf(
+                    TokenKind::Literal,
+                    text(lang, "nothing", "空文字列").to_string(),
+                ),
+                1 => explain_ast(items[0], lang),
+                _ => {
+                    let children = items
+                        .iter()
+                        .map(|e| explain_ast(e, lang))
+                        .collect::<Vec<_>>();
+                    let operands = children.iter().map(|e| e.operand()).collect::<Vec<_>>();
+                    Explanation {
+                        kind: None,
+                        label: text(lang, "in sequence:", "次の並び:").to_string(),
+                        summary: operands.join(text(lang, " followed by ", "、続いて")),
+                        children,
+                    }
+                }
+            }
+        }
+        AST::Or(..) => {
+            let mut items = Vec::new();
+            flatten_or(ast, &mut items);
+            let children = items
+                .iter()
+                .map(|e| explain_ast(e, lang))
+                .collect::<Vec<_>>();
+            let operands = children.iter().map(|e| e.operand()).collect::<Vec<_>>();
+            let (init, last) = operands.split_at(operands.len() - 1);
+            let summary = if en {
+                format!("either {} or {}", init.join(", "), last[0])
+            } else if init.len() == 1 {
+                format!("{}または{}のいずれか", init[0], last[0])
+            } else {
+                format!("{}、{}のいずれか", init.join("、"), last[0])
+            };
+            Explanation {
+                kind: Some(TokenKind::Alternation),
+                label: text(lang, "either:", "次のいずれか:").to_string(),
+                summary,
+                children,
+            }
+        }
+    }
+}
+
+/// 言語に応じた文言
+fn text(lang: Language, en: &'static str, ja: &'static str) -> &'static str {
+    match lang {
+        Language::English => en,
+        Language::Japanese => ja,
+    }
+}
+
+/// 文字を引用符で囲む。制御文字はエスケープする
+fn quote(c: char, lang: Language) -> String {
+    match lang {
+        Language::English => format!("'{}'", c.escape_debug()),
+        Language::Japanese if c.is_control() => format!("「{}」", c.escape_debug()),
+        Language::Japanese => format!("「{c}」"),
+    }
+}
+
+/// + * ? の見出し
+fn quantifier(ast: &AST, possessive: bool, lang: Language) -> String {
+    let label = match (ast, lang) {
+        (AST::Plus(_), Language::English) => "one or more",
+        (AST::Star(_), Language::English) => "zero or more",
+        (_, Language::English) => "optionally",
+        (AST::Plus(_), Language::Japanese) => "次の 1 回以上の",
+        (AST::Star(_), Language::Japanese) => "次の 0 回以上の",
+        (_, Language::Japanese) => "省略可能",
+    };
+    match (ast, lang, possessive) {
+        (AST::Question(_), Language::English, false) => format!("{label}:"),
+        (AST::Question(_), Language::English, true) => format!("{label} (possessive):"),
+        (_, Language::English, false) => format!("{label} of:"),
+        (_, Language::English, true) => format!("{label} (possessive) of:"),
+        (AST::Question(_), Language::Japanese, false) => format!("{label}:"),
+        (AST::Question(_), Language::Japanese, true) => format!("{label} (強欲):"),
+        (_, Language::Japanese, false) => format!("{label}繰り返し:"),
+        (_, Language::Japanese, true) => format!("{label}強欲な繰り返し:"),
+    }
+}
+
+/// 入れ子の Seq を展開
+fn flatten_seq<'a>(ast: &'a AST, items: &mut Vec<&'a AST>) {
+    match ast {
+        AST::Seq(v) => v.iter().for_each(|e| flatten_seq(e, items)),
+        _ => items.push(ast),
+    }
+}
+
+/// 入れ子の Or を展開。1 要素の Seq は要素そのものとする
+fn flatten_or<'a>(ast: &'a AST, items: &mut Vec<&'a AST>) {
+    match ast {
+        AST::Or(e1, e2) => {
+            flatten_or(e1, items);
+            flatten_or(e2, items);
+        }
+        AST::Seq(v) if v.len() == 1 && matches!(v[0], AST::Or(..)) => flatten_or(&v[0], items),
+        _ => items.push(ast),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(expr: &str) -> Vec<(TokenKind, Range<usize>)> {
+        tokenize(expr)
+            .unwrap()
+            .into_iter()
+            .map(|t| (t.kind(), t.span()))
+            .collect()
+    }
+
+    fn summary(expr: &str, lang: Language) -> String {
+        explain(expr, lang).unwrap().summary().to_string()
+    }
+
+    #[test]
+    fn test_tokenize() {
+        use TokenKind::*;
+        assert_eq!(
+            kinds("a(b|c)*"),
+            [
+                (Literal, 0..1),
+                (Group, 1..2),
+                (Literal, 2..3),
+                (Alternation, 3..4),
+                (Literal, 4..5),
+                (Group, 5..6),
+                (Quantifier, 6..7)
+            ]
+        );
+        assert_eq!(
+            kinds("a*+b+++"),
+            [
+                (Literal, 0..1),
+                (Quantifier, 1..3),
+                (Literal, 3..4),
+                (Quantifier, 4..6),
+                (Quantifier, 6..7)
+            ]
+        );
+        assert_eq!(
+            kinds("(?<!x)(?>y)"),
+            [
+                (Group, 0..4),
+                (Literal, 4..5),
+                (Group, 5..6),
+                (Group, 6..9),
+                (Literal, 9..10),
+                (Group, 10..11)
+            ]
+        );
+        assert_eq!(
+            kinds("(a)\\1\\("),
+            [
+                (Group, 0..1),
+                (Literal, 1..2),
+                (Group, 2..3),
+                (Backref, 3..5),
+                (Escape, 5..7)
+            ]
+        );
+        assert_eq!(kinds("あ?"), [(Literal, 0..1), (Quantifier, 1..2)]);
+        assert!(tokenize("a(").is_err());
+    }
+
+    #[test]
+    fn test_explain_english() {
+        let en = Language::English;
+        assert_eq!(summary("(ab)+", en), "one or more of: 'a' followed by 'b'");
+        assert_eq!(
+            summary("(ab)+c", en),
+            "(one or more of: 'a' followed by 'b') followed by 'c'"
+        );
+        assert_eq!(
+            summary("a(b|c)d", en),
+            "'a' followed by (either 'b' or 'c') followed by 'd'"
+        );
+        assert_eq!(
+            summary("ab|c|d?", en),
+            "either ('a' followed by 'b'), 'c' or (optionally: 'd')"
+        );
+        assert_eq!(
+            summary("a*+b?+", en),
+            "(zero or more (possessive) of: 'a') followed by (optionally (possessive): 'b')"
+        );
+        assert_eq!(
+            summary("(a)\\1", en),
+            "(group 1: 'a') followed by the text matched by group 1"
+        );
+        assert_eq!(
+            summary("(?=a)(?<!)", en),
+            "(if followed by: 'a') followed by (if not preceded by: nothing)"
+        );
+        assert_eq!(summary("(?>a|b)", en), "atomic group: either 'a' or 'b'");
+        assert_eq!(summary("\n", en), "'\\n'");
+    }
+
+    #[test]
+    fn test_explain_japanese() {
+        let ja = Language::Japanese;
+        assert_eq!(
+            summary("(ab)+", ja),
+            "次の 1 回以上の繰り返し: 「a」、続いて「b」"
+        );
+        assert_eq!(summary("a|b|c", ja), "「a」、「b」、「c」のいずれか");
+        assert_eq!(summary("a?+", ja), "省略可能 (強欲): 「a」");
+        assert_eq!(
+            summary("(a)\\1", ja),
+            "(グループ 1: 「a」)、続いてグループ 1 と同じ文字列"
+        );
+        assert_eq!(summary("(?!a)", ja), "直後が次に一致しない場合: 「a」");
+    }
+
+    #[test]
+    fn test_explain_tree() {
+        let e = explain("x(a|bc)*", Language::English).unwrap();
+        assert_eq!(e.kind(), None);
+        assert_eq!(e.children()[1].kind(), Some(TokenKind::Quantifier));
+        assert_eq!(
+            e.children()[1].children()[0].kind(),
+            Some(TokenKind::Alternation)
+        );
+        assert_eq!(
+            e.to_string(),
+            "in sequence:\n  'x'\n  zero or more of:\n    either:\n      'a'\n      in sequence:\n        'b'\n        'c'\n"
+        );
+        assert!(explain("a**(", Language::English).is_err());
+    }
+}
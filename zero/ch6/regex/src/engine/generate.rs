@@ -0,0 +1,349 @@
+//! パターンにマッチする文字列の生成
+//!
+//! DFA の各状態から、残りの長さで受理状態に到達できるかを表にしておき、
+//! 到達できる遷移だけをたどることで、必ずマッチする文字列を作る。
+//! 生成する文字列は、DFA と同じく文字列 **全体** がパターンにマッチする。
+use super::{dfa::Dfa, Error};
+
+/// xorshift による疑似乱数生成器
+///
+/// 同じシードからは常に同じ乱数列を生成する
+#[derive(Debug, Clone)]
+pub struct Xor64 {
+    x: u64,
+}
+
+impl Xor64 {
+    pub fn new(seed: u64) -> Xor64 {
+        let x = seed ^ 88172645463325252;
+        // 状態が 0 になると 0 しか生成しなくなる
+        Xor64 {
+            x: if x == 0 { 88172645463325252 } else { x },
+        }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let x = self.x;
+        let x = x ^ (x << 13);
+        let x = x ^ (x >> 7);
+        let x = x ^ (x << 17);
+        self.x = x;
+        x
+    }
+
+    /// 0 以上 n 未満の乱数。n は 0 より大きいこと
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+}
+
+/// 正規表現にマッチする長さ max_len 以下の文字列をランダムに 1 つ生成
+///
+/// # 利用例
+///
+/// ```
+/// use regex::{generate, Xor64};
+/// let mut rng = Xor64::new(1234);
+/// let s = generate("ab(c|d)*", &mut rng, 8).unwrap().unwrap();
+/// assert!(s.starts_with("ab") && s.len() <= 8);
+/// ```
+///
+/// # 返り値
+///
+/// マッチする長さ max_len 以下の文字列がない場合は Ok(None) を返す。
+/// 正規表現にエラーがある場合や、DFA の状態数が多すぎる場合は Err を返す
+pub fn generate(expr: &str, rng: &mut Xor64, max_len: usize) -> Result<Option<String>, Error> {
+    Ok(Generator::new(Dfa::new(expr)?, max_len).generate(rng))
+}
+
+/// 正規表現にマッチする長さ max_len 以下の文字列をすべて列挙
+///
+/// # 利用例
+///
+/// ```
+/// let all = regex::enumerate("a(b|c)?", 2).unwrap().collect::<Vec<_>>();
+/// assert_eq!(all, ["a", "ab", "ac"]);
+/// ```
+pub fn enumerate(expr: &str, max_len: usize) -> Result<Enumerate, Error> {
+    Ok(Enumerate::new(Dfa::new(expr)?, max_len))
+}
+
+/// DFA が受理する文字列をランダムに生成するための型
+#[derive(Debug, Clone)]
+pub struct Generator {
+    dfa: Dfa,
+    max_len: usize,
+    // within[r][s] は状態 s から r 文字以内で受理状態に到達できるかどうか
+    within: Vec<Vec<bool>>,
+}
+
+impl Generator {
+    pub fn new(dfa: Dfa, max_len: usize) -> Generator {
+        let mut within = vec![(0..dfa.len())
+            .map(|s| dfa.is_accepting(s))
+            .collect::<Vec<_>>()];
+        for r in 1..=max_len {
+            let prev = &within[r - 1];
+            let row = (0..dfa.len())
+                .map(|s| prev[s] || (0..dfa.classes().len()).any(|k| prev[dfa.next(s, k)]))
+                .collect();
+            within.push(row);
+        }
+        Generator {
+            dfa,
+            max_len,
+            within,
+        }
+    }
+
+    /// 受理する長さ max_len 以下の文字列をランダムに 1 つ生成
+    ///
+    /// 各状態では、終了するか、受理状態に到達できる区間のどれかに進むかを等確率で選ぶ。
+    /// 区間から文字を選ぶ際は、ASCII の表示可能文字を含む区間ならその中から選ぶ
+    pub fn generate(&self, rng: &mut Xor64) -> Option<String> {
+        if !self.within[self.max_len][0] {
+            return None;
+        }
+
+        let mut result = String::new();
+        let mut state = 0;
+        let mut options = Vec::new();
+        for r in (0..=self.max_len).rev() {
+            options.clear();
+            if self.dfa.is_accepting(state) {
+                options.push(None);
+            }
+            if r > 0 {
+                let row = &self.within[r - 1];
+                options.extend(
+                    (0..self.dfa.classes().len())
+                        .filter(|&k| row[self.dfa.next(state, k)])
+                        .map(Some),
+                );
+            }
+
+            // within の定義から options は空にならない
+            match options[rng.below(options.len())] {
+                None => break,
+                Some(k) => {
+                    result.push(pick(self.dfa.classes()[k], rng));
+                    state = self.dfa.next(state, k);
+                }
+            }
+        }
+        Some(result)
+    }
+}
+
+/// 区間から文字を 1 つ選ぶ
+fn pick((lo, hi): (char, char), rng: &mut Xor64) -> char {
+    let (lo, hi) = if lo <= '~' && ' ' <= hi {
+        (lo.max(' '), hi.min('~'))
+    } else {
+        (lo, hi)
+    };
+    let n = u32::from(hi) - u32::from(lo) + 1;
+    // DFA の区間はサロゲートを含まない
+    char::from_u32(u32::from(lo) + rng.below(n as usize) as u32).unwrap_or(lo)
+}
+
+/// DFA が受理する文字列を、短い順、同じ長さならコードポイントの辞書順に列挙するイテレータ
+#[derive(Debug, Clone)]
+pub struct Enumerate {
+    dfa: Dfa,
+    max_len: usize,
+    // exact[r][s] は状態 s からちょうど r 文字で受理状態に到達できるかどうか
+    exact: Vec<Vec<bool>>,
+    // 列挙中の文字列の長さ
+    len: usize,
+    // 列挙中の文字列と、各文字を読む前の状態。states は path より 1 つ長い
+    path: Vec<char>,
+    states: Vec<usize>,
+    // path が長さ len の文字列を返した後かどうか
+    started: bool,
+}
+
+impl Enumerate {
+    pub fn new(dfa: Dfa, max_len: usize) -> Enumerate {
+        let mut exact = vec![(0..dfa.len())
+            .map(|s| dfa.is_accepting(s))
+            .collect::<Vec<_>>()];
+        for r in 1..=max_len {
+            let prev = &exact[r - 1];
+            let row = (0..dfa.len())
+                .map(|s| (0..dfa.classes().len()).any(|k| prev[dfa.next(s, k)]))
+                .collect();
+            exact.push(row);
+        }
+        Enumerate {
+            dfa,
+            max_len,
+            exact,
+            len: 0,
+            path: Vec::new(),
+            states: vec![0],
+            started: false,
+        }
+    }
+
+    /// 状態 state から、残り r 文字で受理状態に到達できる from 以上の最小の文字と遷移先
+    fn first_from(&self, state: usize, r: usize, from: char) -> Option<(char, usize)> {
+        let classes = self.dfa.classes();
+        let begin = classes.partition_point(|&(_, hi)| hi < from);
+        (begin..classes.len()).find_map(|k| {
+            let next = self.dfa.next(state, k);
+            self.exact[r - 1][next].then(|| (classes[k].0.max(from), next))
+        })
+    }
+
+    /// 長さ len に達するまで、最小の文字で path を伸ばす
+    fn fill(&mut self) {
+        while self.path.len() < self.len {
+            let d = self.path.len();
+            // exact の定義から必ず見つかる
+            let (c, next) = self.first_from(self.states[d], self.len - d, '\0').unwrap();
+            self.path.push(c);
+            self.states.push(next);
+        }
+    }
+
+    /// path を同じ長さの次の文字列に進める。なければ false を返す
+    fn advance(&mut self) -> bool {
+        while let Some(c) = self.path.pop() {
+            self.states.pop();
+            let d = self.path.len();
+            let found = successor(c).and_then(|n| self.first_from(self.states[d], self.len - d, n));
+            if let Some((c, next)) = found {
+                self.path.push(c);
+                self.states.push(next);
+                self.fill();
+                return true;
+            }
+        }
+        false
+    }
+}
+
+impl Iterator for Enumerate {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        if self.started {
+            // 同じ長さの次の文字列
+            if self.advance() {
+                return Some(self.path.iter().collect());
+            }
+            self.started = false;
+            self.len += 1;
+        }
+
+        // 次の長さの最初の文字列
+        while self.len <= self.max_len {
+            if self.exact[self.len][0] {
+                self.fill();
+                self.started = true;
+                return Some(self.path.iter().collect());
+            }
+            self.len += 1;
+        }
+        None
+    }
+}
+
+/// コードポイント順で次の文字。サロゲートは飛ばす
+fn successor(c: char) -> Option<char> {
+    match c {
+        '\u{d7ff}' => Some('\u{e000}'),
+        _ => char::from_u32(u32::from(c) + 1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{RegexBuilder, Syntax};
+
+    fn ere(expr: &str) -> Dfa {
+        let re = RegexBuilder::new(expr).syntax(Syntax::Ere).build().unwrap();
+        Dfa::from_program(re.program()).unwrap()
+    }
+
+    #[test]
+    fn test_xor64() {
+        let mut a = Xor64::new(1);
+        let mut b = Xor64::new(1);
+        let xs = (0..10).map(|_| a.next_u64()).collect::<Vec<_>>();
+        assert_eq!(xs, (0..10).map(|_| b.next_u64()).collect::<Vec<_>>());
+        assert_ne!(Xor64::new(2).next_u64(), xs[0]);
+
+        // 内部状態が 0 になるシード
+        let mut rng = Xor64::new(88172645463325252);
+        assert_ne!(rng.next_u64(), 0);
+        assert!((0..100).all(|_| rng.below(3) < 3));
+    }
+
+    #[test]
+    fn test_generate() {
+        for expr in [
+            "ab(c|d)*e?",
+            "[[:digit:]]{3}-[[:digit:]]{4}",
+            "(^a|b$)\n[^a-z]+",
+            "x{2,}|",
+        ] {
+            let dfa = ere(expr);
+            let gen = Generator::new(dfa.clone(), 12);
+            let mut rng = Xor64::new(42);
+            for _ in 0..200 {
+                let s = gen.generate(&mut rng).unwrap();
+                assert!(dfa.is_match(&s), "{expr}: {s:?}");
+                assert!(s.chars().count() <= 12);
+            }
+        }
+
+        // 同じシードからは同じ文字列
+        let gen = |seed| {
+            let mut rng = Xor64::new(seed);
+            (0..5)
+                .map(|_| generate("a(b|cd)*", &mut rng, 10).unwrap().unwrap())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(gen(7), gen(7));
+
+        // 長さの上限内にマッチする文字列がない
+        let mut rng = Xor64::new(0);
+        assert_eq!(generate("abcd", &mut rng, 3).unwrap(), None);
+        assert_eq!(
+            generate("abcd", &mut rng, 4).unwrap().as_deref(),
+            Some("abcd")
+        );
+        assert_eq!(Generator::new(ere("a^b"), 10).generate(&mut rng), None);
+        assert!(generate("(a", &mut rng, 3).is_err());
+    }
+
+    #[test]
+    fn test_enumerate() {
+        let all = |expr, max_len| enumerate(expr, max_len).unwrap().collect::<Vec<_>>();
+        assert_eq!(all("(a|b)*c", 2), ["c", "ac", "bc"]);
+        assert_eq!(all("a*", 3), ["", "a", "aa", "aaa"]);
+        assert_eq!(all("abc", 2), Vec::<String>::new());
+        assert_eq!(all("ba|ab|a", 5), ["a", "ab", "ba"]);
+
+        let all = |expr, max_len| Enumerate::new(ere(expr), max_len).collect::<Vec<_>>();
+        assert_eq!(
+            all("[0-2]{2}", 2),
+            ["00", "01", "02", "10", "11", "12", "20", "21", "22"]
+        );
+        assert_eq!(all("a$\n^b|\n", 3), ["\n", "a\nb"]);
+
+        // サロゲートを除くすべての文字
+        let n = Enumerate::new(ere("[^a]"), 1).count();
+        assert_eq!(n, 0x110000 - 0x800 - 2);
+        let mut it = Enumerate::new(ere("[\u{d7fe}-\u{e001}]"), 1);
+        assert_eq!(it.next().as_deref(), Some("\u{d7fe}"));
+        assert_eq!(it.next().as_deref(), Some("\u{d7ff}"));
+        assert_eq!(it.next().as_deref(), Some("\u{e000}"));
+        assert_eq!(it.next().as_deref(), Some("\u{e001}"));
+        assert_eq!(it.next(), None);
+        assert_eq!(it.next(), None);
+    }
+}
@@ -0,0 +1,277 @@
+//! シェル形式のグロブ
+//!
+//! グロブを正規表現と同じ AST に変換し、同じコード生成器と評価器でマッチングを行う。
+//! グロブはパス全体とマッチする必要があるため、末尾に [`AST::End`] を置く。
+//! `*a*a*b` のようなグロブでもパスの長さに比例する時間で判定できるよう、最左最長一致で評価する。
+//!
+//! ```text
+//! *        / 以外の 0 文字以上
+//! ?        / 以外の 1 文字
+//! **/      0 個以上のディレクトリ (パスの要素全体が ** の場合)
+//! /**      末尾の場合は、そのディレクトリ以下のすべて
+//! [a-c]    文字クラス
+//! [!a-c]   否定した文字クラス。[^a-c] も同じ。/ にはマッチしない
+//! \c       文字 c そのもの
+//! ```
+use super::{
+    codegen,
+    parser::AST,
+    program::{Program, FLAG_LONGEST},
+    verifier, CharClass, Error,
+};
+use std::{
+    error,
+    fmt::{self, Display},
+};
+
+/// グロブのパースエラーを表す型
+#[derive(Debug)]
+pub enum GlobError {
+    // 閉じカッコなし
+    NoRightBracket(usize),
+    // エスケープ対象の文字がない
+    NoEscapeChar,
+}
+
+impl Display for GlobError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GlobError::NoRightBracket(pos) => {
+                write!(f, "GlobError: no right bracket: pos = {pos}")
+            }
+            GlobError::NoEscapeChar => write!(f, "GlobError: no character after escape"),
+        }
+    }
+}
+
+impl error::Error for GlobError {}
+
+/// コンパイル済みのグロブ
+#[derive(Debug, Clone)]
+pub struct Glob {
+    glob: String,
+    prog: Program,
+}
+
+impl Glob {
+    /// グロブをコンパイル
+    ///
+    /// # 利用例
+    ///
+    /// ```
+    /// use regex::Glob;
+    /// let glob = Glob::new("src/**/test_?.rs").unwrap();
+    /// assert!(glob.is_match("src/test_a.rs").unwrap());
+    /// assert!(glob.is_match("src/engine/sub/test_b.rs").unwrap());
+    /// assert!(!glob.is_match("src/test_ab.rs").unwrap());
+    /// ```
+    pub fn new(glob: &str) -> Result<Glob, Error> {
+        let ast = parse(glob)?;
+        let code = codegen::get_code(&ast)?;
+        verifier::verify(&code)?;
+        Ok(Glob {
+            glob: glob.to_string(),
+            prog: Program::with_flags(code, FLAG_LONGEST),
+        })
+    }
+
+    /// コンパイル前のグロブを返す
+    pub fn as_str(&self) -> &str {
+        &self.glob
+    }
+
+    /// コンパイル済みのプログラムを返す
+    pub fn program(&self) -> &Program {
+        &self.prog
+    }
+
+    /// パス全体がグロブにマッチするかを判定
+    pub fn is_match(&self, path: &str) -> Result<bool, Error> {
+        self.prog.do_matching(path, true)
+    }
+}
+
+/// 任意の1文字にマッチするクラス
+fn any() -> AST {
+    AST::Class(CharClass::new(&[], true))
+}
+
+/// / 以外の1文字にマッチするクラス
+fn not_slash() -> AST {
+    AST::Class(CharClass::new(&[('/', '/')], true))
+}
+
+/// グロブをパースし、AST に変換
+pub fn parse(glob: &str) -> Result<AST, GlobError> {
+    let chars = glob.chars().collect::<Vec<char>>();
+    let mut seq = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        match chars[i] {
+            '*' => {
+                let mut j = i;
+                while chars.get(j) == Some(&'*') {
+                    j += 1;
+                }
+
+                // ** がパスの要素全体である場合のみ、/ をまたいでマッチする
+                let whole =
+                    (i == 0 || chars[i - 1] == '/') && (j == chars.len() || chars[j] == '/');
+                if j - i >= 2 && whole {
+                    if j == chars.len() {
+                        seq.push(AST::Star(Box::new(any())));
+                    } else {
+                        // (任意の文字列/)?
+                        let dirs = AST::Seq(vec![AST::Star(Box::new(any())), AST::Char('/')]);
+                        seq.push(AST::Question(Box::new(dirs)));
+                        j += 1;
+                    }
+                } else {
+                    seq.push(AST::Star(Box::new(not_slash())));
+                }
+                i = j;
+            }
+            '?' => {
+                seq.push(not_slash());
+                i += 1;
+            }
+            '[' => {
+                let (class, next) = parse_bracket(&chars, i)?;
+                seq.push(AST::Class(class));
+                i = next;
+            }
+            '\\' => {
+                let c = chars.get(i + 1).ok_or(GlobError::NoEscapeChar)?;
+                seq.push(AST::Char(*c));
+                i += 2;
+            }
+            c => {
+                seq.push(AST::Char(c));
+                i += 1;
+            }
+        }
+    }
+
+    seq.push(AST::End);
+    Ok(AST::Seq(seq))
+}
+
+/// start 文字目の `[` から始まるブラケット表現をパース
+///
+/// クラスと、閉じカッコの次の位置を返す
+fn parse_bracket(chars: &[char], start: usize) -> Result<(CharClass, usize), GlobError> {
+    let mut i = start + 1;
+    let negated = matches!(chars.get(i), Some('!' | '^'));
+    if negated {
+        i += 1;
+    }
+
+    let mut ranges = Vec::new();
+    // 先頭の ] は文字として扱う
+    let mut first = true;
+    loop {
+        let c = match chars.get(i) {
+            None => return Err(GlobError::NoRightBracket(start)),
+            Some(']') if !first => break,
+            Some('\\') => {
+                i += 1;
+                *chars.get(i).ok_or(GlobError::NoEscapeChar)?
+            }
+            Some(c) => *c,
+        };
+        first = false;
+        i += 1;
+
+        // a-c の形の範囲。末尾の - は文字として扱う
+        if chars.get(i) == Some(&'-') && chars.get(i + 1).is_some_and(|&d| d != ']') {
+            let hi = if chars[i + 1] == '\\' {
+                i += 1;
+                *chars.get(i + 1).ok_or(GlobError::NoEscapeChar)?
+            } else {
+                chars[i + 1]
+            };
+            ranges.push((c, hi));
+            i += 2;
+        } else {
+            ranges.push((c, c));
+        }
+    }
+
+    // パスの区切りには、否定したクラスもマッチしない
+    if negated {
+        ranges.push(('/', '/'));
+    }
+    Ok((CharClass::new(&ranges, negated), i + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_match(glob: &str, path: &str) -> bool {
+        Glob::new(glob).unwrap().is_match(path).unwrap()
+    }
+
+    #[test]
+    fn test_glob() {
+        assert!(is_match("*.rs", "main.rs"));
+        assert!(is_match("*.rs", ".rs"));
+        assert!(!is_match("*.rs", "src/main.rs"));
+        assert!(!is_match("*.rs", "main.rsx"));
+
+        assert!(is_match("test_?.txt", "test_a.txt"));
+        assert!(!is_match("test_?.txt", "test_.txt"));
+        assert!(!is_match("a?b", "a/b"));
+
+        assert!(is_match("src/**/test_?.txt", "src/test_1.txt"));
+        assert!(is_match("src/**/test_?.txt", "src/a/b/test_1.txt"));
+        assert!(!is_match("src/**/test_?.txt", "src/a/btest_1.txt"));
+        assert!(is_match("**/*.rs", "a/b/c.rs"));
+        assert!(is_match("**/*.rs", "c.rs"));
+        assert!(is_match("target/**", "target/debug/regex"));
+        assert!(!is_match("target/**", "src/target/x"));
+        // パスの要素全体でない ** は * と同じ
+        assert!(is_match("a**b", "axxb"));
+        assert!(!is_match("a**b", "a/b"));
+
+        assert!(is_match("[!a-c]", "d"));
+        assert!(!is_match("[!a-c]", "b"));
+        assert!(!is_match("[!a-c]", "/"));
+        assert!(is_match("[^a-c]x", "zx"));
+        assert!(is_match("[]a]", "]"));
+        assert!(is_match("[a-]", "-"));
+        assert!(is_match("[\\]]", "]"));
+        assert!(is_match("\\*", "*"));
+        assert!(!is_match("\\*", "a"));
+        assert!(is_match("あ*", "あいう"));
+        assert!(is_match("", ""));
+        assert!(!is_match("", "a"));
+    }
+
+    #[test]
+    fn test_linear() {
+        // バックトラックしないため、* が多くても指数時間にならない
+        let glob = format!("{}b", "*a".repeat(20));
+        let path = "a".repeat(1000);
+        assert!(!is_match(&glob, &path));
+        assert!(is_match(&glob, &(path + "b")));
+    }
+
+    #[test]
+    fn test_error() {
+        assert!(matches!(parse("a[bc"), Err(GlobError::NoRightBracket(1))));
+        assert!(matches!(parse("[]"), Err(GlobError::NoRightBracket(0))));
+        assert!(matches!(parse("a\\"), Err(GlobError::NoEscapeChar)));
+    }
+
+    #[test]
+    fn test_program() {
+        // 命令列は正規表現と同じ形式で、バイナリ形式に変換できる
+        let glob = Glob::new("[!.]*.rs").unwrap();
+        let loaded = Program::from_bytes(&glob.program().to_bytes()).unwrap();
+        assert_eq!(&loaded, glob.program());
+        assert!(loaded.do_matching("main.rs", false).unwrap());
+        assert!(!loaded.do_matching(".main.rs", false).unwrap());
+    }
+}
@@ -0,0 +1,254 @@
+//! AST、命令列、実行トレースを JSON に変換
+//!
+//! ブラウザなどで可視化するための形式で、外部のクレートを使わずに出力する。
+use super::{parser::AST, trace::Tracer, Instruction, Match};
+use std::{fmt::Write, ops::ControlFlow};
+
+/// AST を JSON に変換
+///
+/// 各ノードは `{"type": "or", "label": "|", "children": [...]}` の形式となる
+pub fn ast_to_json(ast: &AST) -> String {
+    let mut out = String::new();
+    write_ast(&mut out, ast);
+    out
+}
+
+/// 命令列を JSON に変換
+///
+/// 各命令は `{"pc": 0, "op": "split", "text": "split 0001, 0003", "next": [1, 3]}` の形式となる。
+/// next は実行後に進みうるプログラムカウンタで、先読みと後読みでは本体と後続の順に並ぶ
+pub fn code_to_json(code: &[Instruction]) -> String {
+    let mut out = String::from("[");
+    for (pc, inst) in code.iter().enumerate() {
+        if pc != 0 {
+            out.push(',');
+        }
+        let text = inst.to_string();
+        let op = text.split(' ').next().unwrap_or_default();
+        let next = match inst {
+            Instruction::Match => Vec::new(),
+            Instruction::Jump(addr) => vec![*addr],
+            Instruction::Split(addr1, addr2) => vec![*addr1, *addr2],
+            Instruction::Look(_, addr) => vec![pc + 1, *addr],
+            _ => vec![pc + 1],
+        };
+        let next = next
+            .iter()
+            .map(|n| n.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        let _ = write!(
+            out,
+            "{{\"pc\":{pc},\"op\":{},\"text\":{},\"next\":[{next}]}}",
+            string(op),
+            string(&text)
+        );
+    }
+    out.push(']');
+    out
+}
+
+/// [`JsonTracer::new`] で記録するステップ数の上限
+pub const MAX_TRACE_STEPS: usize = 10_000;
+
+/// 実行した命令を JSON の配列として記録するトレーサ
+///
+/// sp は文字単位のインデックス。
+/// 上限を超えると評価を打ち切るため、指数時間を要する式でもすぐに終わる
+#[derive(Debug)]
+pub struct JsonTracer {
+    steps: String,
+    count: usize,
+    max_steps: usize,
+}
+
+impl Default for JsonTracer {
+    fn default() -> Self {
+        JsonTracer::with_max_steps(MAX_TRACE_STEPS)
+    }
+}
+
+impl JsonTracer {
+    pub fn new() -> Self {
+        JsonTracer::default()
+    }
+
+    /// 最大 max_steps ステップまで記録するトレーサを生成
+    pub fn with_max_steps(max_steps: usize) -> Self {
+        JsonTracer {
+            steps: String::new(),
+            count: 0,
+            max_steps,
+        }
+    }
+
+    /// マッチの結果とともに `{"match": {"start": 1, "end": 3}, "steps": [...], "truncated": false}` を返す
+    ///
+    /// マッチしなかった場合、match は null となる。
+    /// 上限で評価を打ち切った場合、truncated は true となる
+    pub fn finish(self, m: Option<Match>) -> String {
+        let m = m.map_or("null".to_string(), |m| {
+            format!("{{\"start\":{},\"end\":{}}}", m.start(), m.end())
+        });
+        let truncated = self.count > self.max_steps;
+        format!(
+            "{{\"match\":{m},\"steps\":[{}],\"truncated\":{truncated}}}",
+            self.steps
+        )
+    }
+}
+
+impl Tracer for JsonTracer {
+    fn step(
+        &mut self,
+        pc: usize,
+        sp: usize,
+        thread: usize,
+        _inst: &Instruction,
+    ) -> ControlFlow<()> {
+        self.count += 1;
+        if self.count > self.max_steps {
+            return ControlFlow::Break(());
+        }
+        if !self.steps.is_empty() {
+            self.steps.push(',');
+        }
+        let _ = write!(
+            self.steps,
+            "{{\"thread\":{thread},\"pc\":{pc},\"sp\":{sp}}}"
+        );
+        ControlFlow::Continue(())
+    }
+}
+
+/// AST のノードを出力
+///
+/// AST の深さはパース時に制限されているため、再帰で辿る
+fn write_ast(out: &mut String, ast: &AST) {
+    let (kind, label, children): (&str, String, Vec<&AST>) = match ast {
+        AST::Char(c) => ("char", c.to_string(), Vec::new()),
+        AST::Plus(e) => ("plus", "+".to_string(), vec![e]),
+        AST::Star(e) => ("star", "*".to_string(), vec![e]),
+        AST::Question(e) => ("question", "?".to_string(), vec![e]),
+        AST::Or(e1, e2) => ("or", "|".to_string(), vec![e1, e2]),
+        AST::Seq(v) => ("seq", "seq".to_string(), v.iter().collect()),
+        AST::Class(class) => ("class", class.to_string(), Vec::new()),
+        AST::End => ("end", "end".to_string(), Vec::new()),
+        AST::LineStart => ("line_start", "^".to_string(), Vec::new()),
+        AST::LineEnd => ("line_end", "$".to_string(), Vec::new()),
+        AST::Capture(n, e) => ("capture", n.to_string(), vec![e]),
+        AST::Backref(n) => ("backref", n.to_string(), Vec::new()),
+        AST::Look(look, e) => ("look", look.to_string(), vec![e]),
+        AST::Atomic(e) => ("atomic", "?>".to_string(), vec![e]),
+    };
+
+    let _ = write!(
+        out,
+        "{{\"type\":\"{kind}\",\"label\":{},\"children\":[",
+        string(&label)
+    );
+    for (i, child) in children.into_iter().enumerate() {
+        if i != 0 {
+            out.push(',');
+        }
+        write_ast(out, child);
+    }
+    out.push_str("]}");
+}
+
+/// JSON の文字列リテラルに変換
+fn string(s: &str) -> String {
+    let mut result = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            c if c < ' ' => {
+                let _ = write!(result, "\\u{:04x}", c as u32);
+            }
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{codegen::get_code, parser::parse, trace_to_json, Error, EvalError, Regex};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_ast_to_json() {
+        let ast = parse("a|\"*").unwrap();
+        assert_eq!(
+            ast_to_json(&ast),
+            "{\"type\":\"or\",\"label\":\"|\",\"children\":[\
+             {\"type\":\"seq\",\"label\":\"seq\",\"children\":[{\"type\":\"char\",\"label\":\"a\",\"children\":[]}]},\
+             {\"type\":\"seq\",\"label\":\"seq\",\"children\":[{\"type\":\"star\",\"label\":\"*\",\"children\":[\
+             {\"type\":\"char\",\"label\":\"\\\"\",\"children\":[]}]}]}]}"
+        );
+
+        let ast = parse("(?<!a)").unwrap();
+        assert!(ast_to_json(&ast).contains("{\"type\":\"look\",\"label\":\"not_behind\","));
+    }
+
+    #[test]
+    fn test_code_to_json() {
+        let code = get_code(&parse("a|b").unwrap()).unwrap();
+        assert_eq!(
+            code_to_json(&code),
+            "[{\"pc\":0,\"op\":\"split\",\"text\":\"split 0001, 0003\",\"next\":[1,3]},\
+             {\"pc\":1,\"op\":\"char\",\"text\":\"char a\",\"next\":[2]},\
+             {\"pc\":2,\"op\":\"jump\",\"text\":\"jump 0004\",\"next\":[4]},\
+             {\"pc\":3,\"op\":\"char\",\"text\":\"char b\",\"next\":[4]},\
+             {\"pc\":4,\"op\":\"match\",\"text\":\"match\",\"next\":[]}]"
+        );
+        assert_eq!(string("\t\\\u{1}"), "\"\\u0009\\\\\\u0001\"");
+    }
+
+    #[test]
+    fn test_json_tracer() {
+        let re = Regex::new("b").unwrap();
+        let mut tracer = JsonTracer::new();
+        let m = re.find_traced("ab", &mut tracer).unwrap();
+        assert_eq!(
+            tracer.finish(m),
+            "{\"match\":{\"start\":1,\"end\":2},\"steps\":[\
+             {\"thread\":0,\"pc\":0,\"sp\":0},\
+             {\"thread\":0,\"pc\":0,\"sp\":1},{\"thread\":0,\"pc\":1,\"sp\":2}],\"truncated\":false}"
+        );
+        assert_eq!(
+            JsonTracer::new().finish(None),
+            "{\"match\":null,\"steps\":[],\"truncated\":false}"
+        );
+    }
+
+    #[test]
+    fn test_json_tracer_limit() {
+        let re = Regex::new("b").unwrap();
+        let mut tracer = JsonTracer::with_max_steps(2);
+        assert!(matches!(
+            re.find_traced("ab", &mut tracer),
+            Err(Error::Eval(EvalError::Aborted))
+        ));
+        assert_eq!(
+            tracer.finish(None),
+            "{\"match\":null,\"steps\":[\
+             {\"thread\":0,\"pc\":0,\"sp\":0},{\"thread\":0,\"pc\":0,\"sp\":1}],\"truncated\":true}"
+        );
+
+        // 指数時間を要する式でも、上限で評価を打ち切るためすぐに終わる
+        let start = Instant::now();
+        let json = trace_to_json("(a|a)*b", &"a".repeat(100), true).unwrap();
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert!(json.starts_with("{\"match\":null,"));
+        assert_eq!(json.matches("\"thread\"").count(), MAX_TRACE_STEPS);
+        assert!(json.ends_with("],\"truncated\":true}"));
+        // 幅優先探索も同じ
+        let json = trace_to_json("(a|a)*b", &"a".repeat(100), false).unwrap();
+        assert!(json.ends_with("],\"truncated\":true}"));
+    }
+}
@@ -1,8 +1,15 @@
 //! 正規表現の式をパースし、抽象構文木に変換
-use std::{error::Error, fmt::{self, Display}, mem::take};
+use super::{posix, CharClass, Look};
+use std::{
+    error::Error,
+    fmt::{self, Display},
+    mem::{replace, take},
+    ops::Range,
+};
 
 /// 抽象木を表現するための型
-#[derive(Debug)]
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum AST {
     // a, J などの文字のパターン
     Char(char),
@@ -16,6 +23,34 @@ pub enum AST {
     Or(Box<AST>, Box<AST>),
     // 正規表現の列を表す
     Seq(Vec<AST>),
+    // [a-c] などの文字クラス
+    Class(CharClass),
+    // 文字列の末尾
+    End,
+    // 行頭 (POSIX の ^)
+    LineStart,
+    // 行末 (POSIX の $)
+    LineEnd,
+    // 後方参照されるグループ。番号は参照されるグループだけを左から 1 から数えたもの
+    Capture(usize, Box<AST>),
+    // \1 などの後方参照
+    Backref(usize),
+    // (?=...) などの先読みと後読み
+    Look(Look, Box<AST>),
+    // (?>...) のアトミックグループ。a*+ などの強欲な量指定子は、量指定子を囲むアトミックグループとなる
+    Atomic(Box<AST>),
+}
+
+/// 正規表現の構文
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Syntax {
+    /// 本ライブラリ独自の構文
+    #[default]
+    Native,
+    /// POSIX 拡張正規表現 (grep -E)
+    Ere,
+    /// POSIX 基本正規表現 (grep -G)
+    Bre,
 }
 
 /// パースエラーを表すための型
@@ -29,8 +64,24 @@ pub enum ParseError {
     NoPrev(usize),
     // 閉じカッコなし
     NoRightParen,
+    // エスケープ対象の文字がない
+    NoEscapeChar,
+    // 入れ子が深すぎる
+    TooDeep,
     // 空のパターン
     Empty,
+    // ブラケット表現の閉じカッコなし
+    NoRightBracket(usize),
+    // 未定義の文字クラス名、または1文字でない照合要素
+    InvalidBracket(usize),
+    // 範囲の始点が終点より大きい
+    InvalidRange(usize),
+    // 不正な繰り返し回数の指定
+    InvalidInterval(usize),
+    // 繰り返しを展開した式が大きすぎる
+    TooLarge,
+    // 存在しないか空のグループへの後方参照
+    InvalidBackref(usize),
 }
 
 impl Display for ParseError {
@@ -48,19 +99,79 @@ impl Display for ParseError {
             ParseError::NoRightParen => {
                 write!(f, "ParseError: no right parenthesis")
             }
+            ParseError::NoEscapeChar => {
+                write!(f, "ParseError: no character after escape")
+            }
+            ParseError::TooDeep => {
+                write!(f, "ParseError: nesting too deep: max = {MAX_DEPTH}")
+            }
             ParseError::Empty => {
                 write!(f, "ParseError: empty expression")
             }
+            ParseError::NoRightBracket(pos) => {
+                write!(f, "ParseError: no right bracket: pos = {pos}")
+            }
+            ParseError::InvalidBracket(pos) => {
+                write!(f, "ParseError: invalid bracket expression: pos = {pos}")
+            }
+            ParseError::InvalidRange(pos) => {
+                write!(f, "ParseError: invalid range end: pos = {pos}")
+            }
+            ParseError::InvalidInterval(pos) => {
+                write!(f, "ParseError: invalid interval: pos = {pos}")
+            }
+            ParseError::TooLarge => {
+                write!(f, "ParseError: expression too large: max = {MAX_SIZE}")
+            }
+            ParseError::InvalidBackref(pos) => {
+                write!(f, "ParseError: invalid back reference: pos = {pos}")
+            }
+        }
+    }
+}
+
+impl ParseError {
+    /// エラーが発生した位置 (文字単位のインデックス)
+    pub fn position(&self) -> Option<usize> {
+        match self {
+            ParseError::InvalidEscape(pos, _)
+            | ParseError::InvalidRightParen(pos)
+            | ParseError::NoPrev(pos)
+            | ParseError::NoRightBracket(pos)
+            | ParseError::InvalidBracket(pos)
+            | ParseError::InvalidRange(pos)
+            | ParseError::InvalidInterval(pos)
+            | ParseError::InvalidBackref(pos) => Some(*pos),
+            ParseError::NoRightParen
+            | ParseError::NoEscapeChar
+            | ParseError::TooDeep
+            | ParseError::Empty
+            | ParseError::TooLarge => None,
         }
     }
 }
 
 impl Error for ParseError {}
 
+/// AST の入れ子の深さの上限
+///
+/// コード生成などの再帰処理でスタックオーバーフローしないよう制限する
+pub const MAX_DEPTH: usize = 256;
+
+/// AST のノード数の上限
+///
+/// `{m,n}` による繰り返しの展開で、命令列が巨大にならないよう制限する
+pub const MAX_SIZE: usize = 100_000;
+
 /// 特殊文字のエスケープ
 fn parse_escape(pos: usize, c: char) -> Result<AST, ParseError> {
     match c {
-        '\\' | '(' | '|' | '+' | '*' | '?' => Ok(AST::Char(c)),
+        '\\' | '(' | ')' | '|' | '+' | '*' | '?' => Ok(AST::Char(c)),
+        'd' | 'D' | 's' | 'S' | 'w' | 'W' => {
+            let lower = c.to_ascii_lowercase();
+            let (_, ranges) = CLASS_ESCAPES.iter().find(|(e, _)| *e == lower).unwrap();
+            Ok(AST::Class(CharClass::new(ranges, c.is_ascii_uppercase())))
+        }
         _ => {
             let err = ParseError::InvalidEscape(pos, c);
             Err(err)
@@ -68,7 +179,15 @@ fn parse_escape(pos: usize, c: char) -> Result<AST, ParseError> {
     }
 }
 
+/// \d \s \w が表す文字の範囲 (ASCII の範囲のみ)。大文字の \D \S \W はその否定
+pub(super) const CLASS_ESCAPES: &[(char, &[(char, char)])] = &[
+    ('d', &[('0', '9')]),
+    ('s', &[('\t', '\r'), (' ', ' ')]),
+    ('w', &[('0', '9'), ('A', 'Z'), ('_', '_'), ('a', 'z')]),
+];
+
 /// + * ? を表す型
+#[allow(clippy::upper_case_acronyms)]
 enum PSQ {
     Plus,
     Star,
@@ -99,7 +218,7 @@ fn parse_plus_star_question(
 /// | で結合された式を AST に変換
 ///
 /// 例) a | bc | def は AST::Or("a", AST::Or("bc", "def")) となる
-fn fold_or(mut seq_or: Vec<AST>) -> Option<AST> {
+pub(super) fn fold_or(mut seq_or: Vec<AST>) -> Option<AST> {
     if seq_or.len() > 1 {
         let mut ast = seq_or.pop().unwrap();
         seq_or.reverse();
@@ -112,8 +231,129 @@ fn fold_or(mut seq_or: Vec<AST>) -> Option<AST> {
     }
 }
 
+/// AST の入れ子の深さが上限以下であるかを検査
+///
+/// 深い AST でもスタックオーバーフローしないよう、明示的なスタックで走査する
+pub(super) fn check_depth(ast: &AST) -> Result<(), ParseError> {
+    let mut stack = vec![(ast, 1)];
+    while let Some((node, depth)) = stack.pop() {
+        if depth > MAX_DEPTH {
+            return Err(ParseError::TooDeep);
+        }
+        match node {
+            AST::Char(_)
+            | AST::Class(_)
+            | AST::End
+            | AST::LineStart
+            | AST::LineEnd
+            | AST::Backref(_) => (),
+            AST::Plus(e)
+            | AST::Star(e)
+            | AST::Question(e)
+            | AST::Capture(_, e)
+            | AST::Look(_, e)
+            | AST::Atomic(e) => stack.push((e, depth + 1)),
+            AST::Or(e1, e2) => {
+                stack.push((e1, depth + 1));
+                stack.push((e2, depth + 1));
+            }
+            AST::Seq(v) => stack.extend(v.iter().map(|e| (e, depth + 1))),
+        }
+    }
+    Ok(())
+}
+
+/// 空文字列にマッチし得るかどうか
+///
+/// 後方参照は空文字列をキャプチャしたグループを参照し得るため、先読みと後読みは
+/// 文字を消費しないため、空文字列にマッチし得るものとして扱う
+pub(super) fn nullable(ast: &AST) -> bool {
+    match ast {
+        AST::Char(_) | AST::Class(_) => false,
+        AST::End | AST::LineStart | AST::LineEnd => true,
+        AST::Backref(_) | AST::Look(..) => true,
+        AST::Star(_) | AST::Question(_) => true,
+        AST::Plus(e) | AST::Capture(_, e) | AST::Atomic(e) => nullable(e),
+        AST::Or(e1, e2) => nullable(e1) || nullable(e2),
+        AST::Seq(v) => v.iter().all(nullable),
+    }
+}
+
+/// syntax で指定した構文の正規表現を抽象構文木に変換
+pub fn parse_with(expr: &str, syntax: Syntax) -> Result<AST, ParseError> {
+    match syntax {
+        Syntax::Native => parse(expr),
+        Syntax::Ere | Syntax::Bre => posix::parse(expr, syntax),
+    }
+}
+
+/// 後方参照されるグループの番号を列挙
+///
+/// `\1` から `\9` までの後方参照を探し、参照されるグループの番号を昇順に返す
+fn referenced_groups(expr: &str) -> Vec<usize> {
+    let mut groups = Vec::new();
+    let mut escape = false;
+    for c in expr.chars() {
+        if escape {
+            if let Some(n) = c.to_digit(10).filter(|n| *n > 0) {
+                groups.push(n as usize);
+            }
+            escape = false;
+        } else {
+            escape = c == '\\';
+        }
+    }
+    groups.sort_unstable();
+    groups.dedup();
+    groups
+}
+
+/// カッコの種類
+enum Group {
+    // 左から数えた番号のグループ
+    Capture(usize),
+    // 先読みと後読み
+    Look(Look),
+    // アトミックグループ
+    Atomic,
+}
+
+/// `(` の直後にある `?=` などの指定を読み取り、カッコの種類と指定の長さを返す
+fn parse_group(rest: &[char]) -> Option<(Group, usize)> {
+    match rest {
+        ['?', '=', ..] => Some((Group::Look(Look::Ahead), 2)),
+        ['?', '!', ..] => Some((Group::Look(Look::NotAhead), 2)),
+        ['?', '<', '=', ..] => Some((Group::Look(Look::Behind), 3)),
+        ['?', '<', '!', ..] => Some((Group::Look(Look::NotBehind), 3)),
+        ['?', '>', ..] => Some((Group::Atomic, 2)),
+        _ => None,
+    }
+}
+
+/// 直前の量指定子を強欲にする
+///
+/// `a*+` は `(?>a*)` と同じ意味となる
+fn parse_possessive(seq: &mut [AST]) {
+    if let Some(last) = seq.last_mut() {
+        let prev = replace(last, AST::Seq(Vec::new()));
+        *last = AST::Atomic(Box::new(prev));
+    }
+}
+
 /// 正規表現を抽象構文木に変換
+///
+/// `\1` から `\9` の後方参照と、`(?=...)` `(?!...)` `(?<=...)` `(?<!...)` の
+/// 先読みと後読み、`(?>...)` のアトミックグループと `*+` `++` `?+` の強欲な量指定子は
+/// 深さ優先探索の評価器でのみ評価できる
 pub fn parse(expr: &str) -> Result<AST, ParseError> {
+    parse_spanned(expr).map(|(ast, _)| ast)
+}
+
+/// 正規表現を抽象構文木に変換し、+ * ? の範囲とともに返す
+///
+/// 範囲は文字単位のインデックスで、AST を後行順に辿ったときに
+/// Plus, Star, Question が現れる順に並ぶ
+pub fn parse_spanned(expr: &str) -> Result<(AST, Vec<Range<usize>>), ParseError> {
     // 内部状態を表す型
     enum ParseState {
         // 文字列処理中
@@ -122,8 +362,26 @@ pub fn parse(expr: &str) -> Result<AST, ParseError> {
         Escape,
     }
 
+    let chars = expr.chars().collect::<Vec<_>>();
+    // 後方参照されるグループの番号
+    let referenced = referenced_groups(expr);
+    // 空であったグループの番号
+    let mut empty = Vec::new();
+    // 後方参照の位置と番号
+    let mut backrefs = Vec::new();
+    // これまでに開いたグループの数
+    let mut groups = 0;
+    // 読み飛ばす文字数
+    let mut skip = 0;
+    // 直前の文字が量指定子かどうか。量指定子に続く + は強欲な量指定子とする
+    let mut quantifier = false;
+
     // 現在の Seq のコンテキスト
     let mut seq = Vec::new();
+    // 現在の Seq の各要素の開始位置
+    let mut starts = Vec::new();
+    // + * ? の範囲
+    let mut spans = Vec::new();
     // 現在の Or のコンテキスト
     let mut seq_or = Vec::new();
     // コンテキストのスタック
@@ -131,35 +389,77 @@ pub fn parse(expr: &str) -> Result<AST, ParseError> {
     // 現在の状態
     let mut state = ParseState::Char;
 
-    for (i, c) in expr.chars().enumerate() {
+    for (i, &c) in chars.iter().enumerate() {
+        if skip > 0 {
+            skip -= 1;
+            continue;
+        }
+        let prev_quantifier = replace(&mut quantifier, false);
         match &state {
             ParseState::Char => {
                 match c {
-                    '+' => parse_plus_star_question(&mut seq, PSQ::Plus, i)?,
-                    '*' => parse_plus_star_question(&mut seq, PSQ::Star, i)?,
-                    '?' => parse_plus_star_question(&mut seq, PSQ::Question, i)?,
+                    '+' if prev_quantifier => parse_possessive(&mut seq),
+                    '*' | '+' | '?' => {
+                        let psq = match c {
+                            '+' => PSQ::Plus,
+                            '*' => PSQ::Star,
+                            _ => PSQ::Question,
+                        };
+                        parse_plus_star_question(&mut seq, psq, i)?;
+                        spans.push(starts[starts.len() - 1]..i + 1);
+                        quantifier = true;
+                    }
                     '(' => {
                         // 現在のコンテキストをスタックに保存し、現在のコンテキストを空にする
                         let prev = take(&mut seq);
                         let prev_or = take(&mut seq_or);
-                        stack.push((prev, prev_or));
+                        let prev_starts = take(&mut starts);
+                        let group = if let Some((group, len)) = parse_group(&chars[i + 1..]) {
+                            skip = len;
+                            group
+                        } else {
+                            groups += 1;
+                            Group::Capture(groups)
+                        };
+                        stack.push((prev, prev_or, prev_starts, i, group));
                     }
                     ')' => {
                         // 現在のコンテキストをスタックからポップ
-                        if let Some((mut prev, prev_or)) = stack.pop() {
+                        if let Some((mut prev, prev_or, mut prev_starts, open, group)) = stack.pop()
+                        {
                             // "()" のように式が空の場合はプッシュしない
                             if !seq.is_empty() {
                                 seq_or.push(AST::Seq(seq));
                             }
 
                             // Or を生成
-                            if let Some(ast) = fold_or(seq_or) {
-                                prev.push(ast);
+                            let ast = fold_or(seq_or);
+                            let len = prev.len();
+                            match group {
+                                Group::Capture(n) => match (ast, referenced.binary_search(&n)) {
+                                    (Some(ast), Ok(rank)) => {
+                                        prev.push(AST::Capture(rank + 1, Box::new(ast)))
+                                    }
+                                    (Some(ast), Err(_)) => prev.push(ast),
+                                    (None, _) => empty.push(n),
+                                },
+                                Group::Look(look) => {
+                                    let ast = ast.unwrap_or(AST::Seq(Vec::new()));
+                                    prev.push(AST::Look(look, Box::new(ast)));
+                                }
+                                // "(?>)" のように式が空の場合はプッシュしない
+                                Group::Atomic => {
+                                    prev.extend(ast.map(|ast| AST::Atomic(Box::new(ast))))
+                                }
+                            }
+                            if prev.len() > len {
+                                prev_starts.push(open);
                             }
 
                             // 以前のコンテキストを現在のコンテキストにする
                             seq = prev;
                             seq_or = prev_or;
+                            starts = prev_starts;
                         } else {
                             // "abc)" のように開きカッコがないのに閉じカッコがある場合はエラー
                             return Err(ParseError::InvalidRightParen(i));
@@ -172,26 +472,52 @@ pub fn parse(expr: &str) -> Result<AST, ParseError> {
                         } else {
                             let prev = take(&mut seq);
                             seq_or.push(AST::Seq(prev));
+                            starts.clear();
                         }
                     }
                     '\\' => state = ParseState::Escape,
-                    _ => seq.push(AST::Char(c)),
+                    _ => {
+                        seq.push(AST::Char(c));
+                        starts.push(i);
+                    }
                 }
             }
             ParseState::Escape => {
                 // エスケープシーケンス処理
-                let ast = parse_escape(i, c)?;
-                seq.push(ast);
+                if let Some(n) = c.to_digit(10).filter(|n| *n > 0) {
+                    // 後方参照。まだ開いていないグループは参照できない
+                    let n = n as usize;
+                    if n > groups {
+                        return Err(ParseError::InvalidBackref(i));
+                    }
+                    let rank = referenced.binary_search(&n).unwrap();
+                    seq.push(AST::Backref(rank + 1));
+                    backrefs.push((i, n));
+                } else {
+                    let ast = parse_escape(i, c)?;
+                    seq.push(ast);
+                }
+                starts.push(i - 1);
                 state = ParseState::Char;
             }
         }
     }
 
+    // `abc\` のようにエスケープ対象の文字がない場合はエラー
+    if let ParseState::Escape = state {
+        return Err(ParseError::NoEscapeChar);
+    }
+
     // 閉じカッコが足りない場合はエラー
     if !stack.is_empty() {
         return Err(ParseError::NoRightParen);
     }
 
+    // "()\1" のように空のグループを参照する場合はエラー
+    if let Some((pos, _)) = backrefs.iter().find(|(_, n)| empty.contains(n)) {
+        return Err(ParseError::InvalidBackref(*pos));
+    }
+
     // "()" のように式が空の場合はプッシュしない
     if !seq.is_empty() {
         seq_or.push(AST::Seq(seq));
@@ -199,7 +525,8 @@ pub fn parse(expr: &str) -> Result<AST, ParseError> {
 
     // Or を生成し、成功した場合はそれを返す
     if let Some(ast) = fold_or(seq_or) {
-        Ok(ast)
+        check_depth(&ast)?;
+        Ok((ast, spans))
     } else {
         Err(ParseError::Empty)
     }
@@ -0,0 +1,728 @@
+//! POSIX の基本正規表現 (BRE) と拡張正規表現 (ERE) のパーサ
+//!
+//! GNU grep と同じく、BRE でも `\|`, `\+`, `\?` を使える。
+//!
+//! ```text
+//! ERE              BRE              意味
+//! (e)              \(e\)            グループ
+//! e1|e2            e1\|e2           選択
+//! e* e+ e?         e* e\+ e\?       繰り返し
+//! e{m} e{m,n}      e\{m\} e\{m,n\}  m 回、m 回以上 n 回以下 ({m,} と {,n} も可)
+//! .                .                改行以外の任意の1文字
+//! [a-c] [^a-c]     [a-c] [^a-c]     ブラケット表現。否定したものは改行にマッチしない
+//! [[:alpha:]]      [[:alpha:]]      文字クラス (ASCII の範囲のみ)
+//! ^ $              ^ $              行頭と行末
+//! ```
+//!
+//! BRE の `^` は式やグループの先頭、`$` は末尾でのみアンカーとなり、それ以外では文字そのもの。
+//! 式の先頭の `*` は、BRE では文字そのもの、ERE では GNU grep と同じく無視する。
+//!
+//! POSIX では空文字列にマッチする式の繰り返しを許すため、
+//! `(a?)*` のような式は空文字列にマッチしない部分の繰り返しに書き換える。
+use super::{
+    class::CharClass,
+    parser::{check_depth, fold_or, nullable, ParseError, Syntax, AST, MAX_DEPTH, MAX_SIZE},
+};
+
+/// 繰り返し回数の上限 (POSIX の RE_DUP_MAX)
+const DUP_MAX: u32 = 255;
+
+/// POSIX の文字クラス名と、対応する文字の範囲
+const CLASSES: &[(&str, &[(char, char)])] = &[
+    ("alnum", &[('0', '9'), ('A', 'Z'), ('a', 'z')]),
+    ("alpha", &[('A', 'Z'), ('a', 'z')]),
+    ("blank", &[(' ', ' '), ('\t', '\t')]),
+    ("cntrl", &[('\0', '\x1f'), ('\x7f', '\x7f')]),
+    ("digit", &[('0', '9')]),
+    ("graph", &[('!', '~')]),
+    ("lower", &[('a', 'z')]),
+    ("print", &[(' ', '~')]),
+    ("punct", &[('!', '/'), (':', '@'), ('[', '`'), ('{', '~')]),
+    ("space", &[('\t', '\r'), (' ', ' ')]),
+    ("upper", &[('A', 'Z')]),
+    ("xdigit", &[('0', '9'), ('A', 'F'), ('a', 'f')]),
+];
+
+/// POSIX の正規表現を抽象構文木に変換
+///
+/// syntax には Syntax::Ere か Syntax::Bre を指定する。
+/// 空の式や空の選択肢は、空文字列にマッチする
+pub fn parse(expr: &str, syntax: Syntax) -> Result<AST, ParseError> {
+    let mut parser = Parser {
+        chars: expr.chars().collect(),
+        pos: 0,
+        bre: syntax == Syntax::Bre,
+        depth: 0,
+    };
+
+    let ast = parser.parse_alt()?;
+    // 対応する開きカッコのない閉じカッコで止まった場合
+    if parser.pos < parser.chars.len() {
+        return Err(ParseError::InvalidRightParen(parser.pos));
+    }
+
+    check_depth(&ast)?;
+    let ast = allow_empty_loops(ast);
+    check_depth(&ast)?;
+    if size(&ast) > MAX_SIZE {
+        return Err(ParseError::TooLarge);
+    }
+    Ok(ast)
+}
+
+/// ブラケット表現の要素
+enum Elem {
+    Char(char),
+    Class(&'static [(char, char)]),
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    bre: bool,
+    // 現在のグループの入れ子の深さ
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn peek_at(&self, n: usize) -> Option<char> {
+        self.chars.get(self.pos + n).copied()
+    }
+
+    /// 現在の位置が、BRE では `\c`、ERE では `c` かどうか
+    fn at_op(&self, c: char) -> bool {
+        if self.bre {
+            self.peek() == Some('\\') && self.peek_at(1) == Some(c)
+        } else {
+            self.peek() == Some(c)
+        }
+    }
+
+    /// 演算子の長さ
+    fn op_len(&self) -> usize {
+        if self.bre {
+            2
+        } else {
+            1
+        }
+    }
+
+    /// 現在の位置が選択肢の終わりかどうか
+    fn at_branch_end(&self) -> bool {
+        self.peek().is_none() || self.at_op('|') || self.at_op(')')
+    }
+
+    /// 現在の位置が限量子の始まりかどうか
+    fn at_quantifier(&self) -> bool {
+        match self.peek() {
+            Some('*') => true,
+            Some('+' | '?') => !self.bre,
+            // ERE で繰り返し回数として読めない { は文字そのもの
+            Some('{') => !self.bre && self.scan_interval(self.pos + 1).is_some(),
+            Some('\\') => self.bre && matches!(self.peek_at(1), Some('+' | '?' | '{')),
+            _ => false,
+        }
+    }
+
+    /// | で区切られた選択肢の列をパース
+    fn parse_alt(&mut self) -> Result<AST, ParseError> {
+        let mut branches = vec![self.parse_branch()?];
+        while self.at_op('|') {
+            self.pos += self.op_len();
+            branches.push(self.parse_branch()?);
+        }
+        Ok(fold_or(branches).unwrap())
+    }
+
+    /// 選択肢を1つパース
+    fn parse_branch(&mut self) -> Result<AST, ParseError> {
+        let mut seq = Vec::new();
+        while !self.at_branch_end() {
+            if !self.at_quantifier() {
+                let ast = self.parse_atom(seq.is_empty())?;
+                seq.push(ast);
+                continue;
+            }
+
+            // BRE では先頭や先頭の ^ の直後の限量子は文字そのもの
+            let no_prev = seq.is_empty() || (self.bre && seq == [AST::LineStart]);
+            if no_prev && self.bre {
+                let c = if self.peek() == Some('\\') {
+                    self.pos += 1;
+                    self.peek().unwrap()
+                } else {
+                    '*'
+                };
+                self.pos += 1;
+                seq.push(AST::Char(c));
+                continue;
+            }
+
+            let (min, max) = self.parse_quantifier()?;
+            // ERE では先頭の限量子を無視する
+            if let Some(prev) = seq.pop().filter(|_| !no_prev) {
+                seq.push(repeat(prev, min, max)?);
+            }
+        }
+        Ok(AST::Seq(seq))
+    }
+
+    /// 限量子をパースし、繰り返し回数の下限と上限を返す
+    fn parse_quantifier(&mut self) -> Result<(u32, Option<u32>), ParseError> {
+        let start = self.pos;
+        if self.bre && self.peek() == Some('\\') {
+            self.pos += 1;
+        }
+        let c = self.peek().unwrap();
+        self.pos += 1;
+
+        match c {
+            '*' => Ok((0, None)),
+            '+' => Ok((1, None)),
+            '?' => Ok((0, Some(1))),
+            _ => {
+                let (min, max, next) = self
+                    .scan_interval(self.pos)
+                    .ok_or(ParseError::InvalidInterval(start))?;
+                if min > DUP_MAX || max.is_some_and(|n| n > DUP_MAX || n < min) {
+                    return Err(ParseError::InvalidInterval(start));
+                }
+                self.pos = next;
+                Ok((min, max))
+            }
+        }
+    }
+
+    /// at 文字目から始まる繰り返し回数 `m,n}` を読む
+    ///
+    /// 読めた場合は下限、上限、閉じカッコの次の位置を返す
+    fn scan_interval(&self, mut at: usize) -> Option<(u32, Option<u32>, usize)> {
+        let number = |at: &mut usize| {
+            let mut n: Option<u32> = None;
+            while let Some(d) = self.chars.get(*at).and_then(|c| c.to_digit(10)) {
+                n = Some(n.unwrap_or(0).saturating_mul(10).saturating_add(d));
+                *at += 1;
+            }
+            n
+        };
+
+        let min = number(&mut at);
+        let max = if self.chars.get(at) == Some(&',') {
+            at += 1;
+            number(&mut at)
+        } else {
+            Some(min?)
+        };
+
+        if self.bre {
+            if self.chars.get(at) != Some(&'\\') {
+                return None;
+            }
+            at += 1;
+        }
+        if self.chars.get(at) != Some(&'}') {
+            return None;
+        }
+        Some((min.unwrap_or(0), max, at + 1))
+    }
+
+    /// 限量子の対象となる式を1つパース
+    ///
+    /// first は選択肢の先頭かどうか
+    fn parse_atom(&mut self, first: bool) -> Result<AST, ParseError> {
+        let pos = self.pos;
+        let c = self.peek().unwrap();
+        self.pos += 1;
+
+        match c {
+            '.' => Ok(AST::Class(CharClass::new(&[('\n', '\n')], true))),
+            '[' => self.parse_bracket(pos),
+            '^' if !self.bre || first => Ok(AST::LineStart),
+            '$' if !self.bre || self.at_branch_end() => Ok(AST::LineEnd),
+            '(' if !self.bre => self.parse_group(),
+            '\\' => {
+                let c = self.peek().ok_or(ParseError::NoEscapeChar)?;
+                self.pos += 1;
+                match c {
+                    '(' if self.bre => self.parse_group(),
+                    '.' | '[' | '\\' | '*' | '^' | '$' => Ok(AST::Char(c)),
+                    // BRE の \{ \} \+ \? は演算子。ここに来るのは先頭の \{ の対の \} のみ
+                    '}' if self.bre => Ok(AST::Char(c)),
+                    '(' | ')' | '|' | '+' | '?' | '{' | '}' if !self.bre => Ok(AST::Char(c)),
+                    _ => Err(ParseError::InvalidEscape(pos + 1, c)),
+                }
+            }
+            _ => Ok(AST::Char(c)),
+        }
+    }
+
+    /// 開きカッコの後ろから、対応する閉じカッコまでをパース
+    fn parse_group(&mut self) -> Result<AST, ParseError> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(ParseError::TooDeep);
+        }
+
+        let ast = self.parse_alt()?;
+        if !self.at_op(')') {
+            return Err(ParseError::NoRightParen);
+        }
+        self.pos += self.op_len();
+        self.depth -= 1;
+        Ok(ast)
+    }
+
+    /// start 文字目の `[` から始まるブラケット表現をパース
+    ///
+    /// ブラケット表現の中では \ は文字そのもの
+    fn parse_bracket(&mut self, start: usize) -> Result<AST, ParseError> {
+        let negated = self.peek() == Some('^');
+        if negated {
+            self.pos += 1;
+        }
+
+        let mut ranges = Vec::new();
+        // 先頭の ] は文字として扱う
+        let mut first = true;
+        loop {
+            match self.peek() {
+                None => return Err(ParseError::NoRightBracket(start)),
+                Some(']') if !first => break,
+                _ => (),
+            }
+            first = false;
+
+            let lo = match self.parse_elem(start)? {
+                Elem::Char(c) => c,
+                Elem::Class(class) => {
+                    ranges.extend_from_slice(class);
+                    continue;
+                }
+            };
+
+            // a-c の形の範囲。末尾の - は文字として扱う
+            if self.peek() == Some('-') && self.peek_at(1).is_some_and(|c| c != ']') {
+                let pos = self.pos;
+                self.pos += 1;
+                match self.parse_elem(start)? {
+                    Elem::Char(hi) if lo <= hi => ranges.push((lo, hi)),
+                    _ => return Err(ParseError::InvalidRange(pos)),
+                }
+            } else {
+                ranges.push((lo, lo));
+            }
+        }
+        self.pos += 1;
+
+        // 否定したブラケット表現は改行にマッチしない
+        if negated {
+            ranges.push(('\n', '\n'));
+        }
+        Ok(AST::Class(CharClass::new(&ranges, negated)))
+    }
+
+    /// ブラケット表現の要素を1つパース
+    ///
+    /// `[:name:]` は文字クラス、`[.c.]` と `[=c=]` は1文字の照合要素のみ扱う
+    fn parse_elem(&mut self, start: usize) -> Result<Elem, ParseError> {
+        let c = self.peek().unwrap();
+        let kind = match (c, self.peek_at(1)) {
+            ('[', Some(k @ (':' | '.' | '='))) => k,
+            _ => {
+                self.pos += 1;
+                return Ok(Elem::Char(c));
+            }
+        };
+
+        let pos = self.pos;
+        let begin = pos + 2;
+        let len = self.chars[begin..]
+            .windows(2)
+            .position(|w| w == [kind, ']'])
+            .ok_or(ParseError::NoRightBracket(start))?;
+        let name = &self.chars[begin..begin + len];
+        self.pos = begin + len + 2;
+
+        match (kind, name) {
+            (':', _) => {
+                let name = name.iter().collect::<String>();
+                CLASSES
+                    .iter()
+                    .find(|(n, _)| *n == name)
+                    .map(|(_, class)| Elem::Class(class))
+                    .ok_or(ParseError::InvalidBracket(pos))
+            }
+            (_, [c]) => Ok(Elem::Char(*c)),
+            _ => Err(ParseError::InvalidBracket(pos)),
+        }
+    }
+}
+
+/// e を min 回以上 max 回以下繰り返す式に展開
+///
+/// max が None の場合は上限なし
+fn repeat(e: AST, min: u32, max: Option<u32>) -> Result<AST, ParseError> {
+    match (min, max) {
+        (0, None) => return Ok(AST::Star(Box::new(e))),
+        (1, None) => return Ok(AST::Plus(Box::new(e))),
+        (0, Some(1)) => return Ok(AST::Question(Box::new(e))),
+        _ => (),
+    }
+
+    let copies = max.unwrap_or(min + 1) as usize;
+    if size(&e).saturating_mul(copies) > MAX_SIZE {
+        return Err(ParseError::TooLarge);
+    }
+
+    let mut seq = vec![e.clone(); min as usize];
+    match max {
+        None => seq.push(AST::Star(Box::new(e))),
+        Some(max) => {
+            for _ in min..max {
+                seq.push(AST::Question(Box::new(e.clone())));
+            }
+        }
+    }
+    Ok(AST::Seq(seq))
+}
+
+/// AST のノード数
+///
+/// 深い AST でもスタックオーバーフローしないよう、明示的なスタックで走査する
+fn size(ast: &AST) -> usize {
+    let mut n = 0;
+    let mut stack = vec![ast];
+    while let Some(node) = stack.pop() {
+        n += 1;
+        match node {
+            AST::Plus(e) | AST::Star(e) | AST::Question(e) => stack.push(e),
+            AST::Or(e1, e2) => {
+                stack.push(e1);
+                stack.push(e2);
+            }
+            AST::Seq(v) => stack.extend(v),
+            _ => (),
+        }
+    }
+    n
+}
+
+/// ast が空文字列にマッチする条件を表す式
+///
+/// アンカーのみからなる式を返す。空文字列にマッチしない場合は None
+fn empty_part(ast: &AST) -> Option<AST> {
+    match ast {
+        AST::Char(_) | AST::Class(_) => None,
+        AST::End | AST::LineStart | AST::LineEnd => Some(ast.clone()),
+        AST::Backref(_) | AST::Look(..) => Some(ast.clone()),
+        AST::Star(_) | AST::Question(_) => Some(AST::Seq(Vec::new())),
+        AST::Plus(e) | AST::Capture(_, e) | AST::Atomic(e) => empty_part(e),
+        AST::Or(e1, e2) => match (empty_part(e1), empty_part(e2)) {
+            (Some(a), Some(b)) => Some(AST::Or(Box::new(a), Box::new(b))),
+            (a, b) => a.or(b),
+        },
+        AST::Seq(v) => v
+            .iter()
+            .map(empty_part)
+            .collect::<Option<Vec<_>>>()
+            .map(AST::Seq),
+    }
+}
+
+/// ast がマッチする文字列のうち、空でないものにのみマッチする式
+///
+/// 空でない文字列にマッチしない場合は None
+fn nonempty_part(ast: &AST) -> Option<AST> {
+    match ast {
+        AST::Char(_) | AST::Class(_) => Some(ast.clone()),
+        AST::End | AST::LineStart | AST::LineEnd => None,
+        AST::Backref(_) | AST::Look(..) => None,
+        AST::Question(e) | AST::Capture(_, e) | AST::Atomic(e) => nonempty_part(e),
+        AST::Star(e) | AST::Plus(e) => nonempty_part(e).map(|e| AST::Plus(Box::new(e))),
+        AST::Or(e1, e2) => match (nonempty_part(e1), nonempty_part(e2)) {
+            (Some(a), Some(b)) => Some(AST::Or(Box::new(a), Box::new(b))),
+            (a, b) => a.or(b),
+        },
+        AST::Seq(v) => nonempty_seq(v),
+    }
+}
+
+/// 列 v のうち、空でない文字列にのみマッチする式
+///
+/// 先頭が空でない文字列にマッチする場合と、先頭が空文字列にマッチし残りが空でない場合に分ける
+fn nonempty_seq(v: &[AST]) -> Option<AST> {
+    let (head, rest) = v.split_first()?;
+    if !nullable(head) {
+        return Some(AST::Seq(v.to_vec()));
+    }
+
+    let first = nonempty_part(head).map(|h| {
+        let mut seq = vec![h];
+        seq.extend_from_slice(rest);
+        AST::Seq(seq)
+    });
+    let second = nonempty_seq(rest).map(|r| match empty_part(head) {
+        Some(AST::Seq(e)) if e.is_empty() => r,
+        Some(e) => AST::Seq(vec![e, r]),
+        None => unreachable!("head is nullable"),
+    });
+
+    match (first, second) {
+        (Some(a), Some(b)) => Some(AST::Or(Box::new(a), Box::new(b))),
+        (a, b) => a.or(b),
+    }
+}
+
+/// 空文字列にマッチしうる式の繰り返しを、空でない部分の繰り返しに書き換える
+///
+/// e* は (e の空でない部分)* と同じ文字列にマッチし、空ループを生じない
+fn allow_empty_loops(ast: AST) -> AST {
+    match ast {
+        AST::Star(e) | AST::Plus(e) if nullable(&e) => {
+            let e = allow_empty_loops(*e);
+            match nonempty_part(&e) {
+                Some(e) => AST::Star(Box::new(e)),
+                None => AST::Seq(Vec::new()),
+            }
+        }
+        AST::Star(e) => AST::Star(Box::new(allow_empty_loops(*e))),
+        AST::Plus(e) => AST::Plus(Box::new(allow_empty_loops(*e))),
+        AST::Question(e) => AST::Question(Box::new(allow_empty_loops(*e))),
+        AST::Or(e1, e2) => AST::Or(
+            Box::new(allow_empty_loops(*e1)),
+            Box::new(allow_empty_loops(*e2)),
+        ),
+        AST::Seq(v) => AST::Seq(v.into_iter().map(allow_empty_loops).collect()),
+        _ => ast,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{codegen, evaluator, verifier};
+
+    fn ere(expr: &str) -> AST {
+        parse(expr, Syntax::Ere).unwrap()
+    }
+
+    fn bre(expr: &str) -> AST {
+        parse(expr, Syntax::Bre).unwrap()
+    }
+
+    /// 文字列全体とマッチするかを判定
+    fn full_match(ast: &AST, line: &str) -> bool {
+        let code = codegen::get_code(&AST::Seq(vec![ast.clone(), AST::End])).unwrap();
+        verifier::verify(&code).unwrap();
+        let line = line.chars().collect::<Vec<char>>();
+        evaluator::eval(&code, &line, true).unwrap()
+    }
+
+    #[test]
+    fn test_ere() {
+        let c = |c| AST::Char(c);
+        assert_eq!(ere("ab"), AST::Seq(vec![c('a'), c('b')]));
+        assert_eq!(ere("(a)"), AST::Seq(vec![AST::Seq(vec![c('a')])]));
+        assert_eq!(
+            ere("^a$"),
+            AST::Seq(vec![AST::LineStart, c('a'), AST::LineEnd])
+        );
+        assert_eq!(ere("\\(\\{"), AST::Seq(vec![c('('), c('{')]));
+        // 繰り返し回数として読めない { は文字そのもの
+        assert_eq!(ere("a{"), AST::Seq(vec![c('a'), c('{')]));
+        assert_eq!(ere("a{x}"), ere("a\\{x\\}"));
+        // 先頭の限量子は無視する
+        assert_eq!(ere("*a"), ere("a"));
+        assert_eq!(ere(""), AST::Seq(Vec::new()));
+
+        assert!(full_match(&ere("a|b|"), ""));
+        assert!(full_match(&ere("(ab|c)+d"), "abcabd"));
+        assert!(full_match(&ere("a{2,3}"), "aaa"));
+        assert!(!full_match(&ere("a{2,3}"), "aaaa"));
+        assert!(!full_match(&ere("a{2,3}"), "a"));
+        assert!(full_match(&ere("a{2,}"), "aaaaa"));
+        assert!(full_match(&ere("a{,2}"), ""));
+        assert!(full_match(&ere("(ab){0}c"), "c"));
+        assert!(full_match(&ere("x{2}{3}"), "xxxxxx"));
+        assert!(full_match(&ere("a.c"), "abc"));
+        assert!(!full_match(&ere("a.c"), "a\nc"));
+
+        assert!(matches!(
+            parse("a)", Syntax::Ere),
+            Err(ParseError::InvalidRightParen(1))
+        ));
+        assert!(matches!(
+            parse("(a", Syntax::Ere),
+            Err(ParseError::NoRightParen)
+        ));
+        assert!(matches!(
+            parse("a{2,1}", Syntax::Ere),
+            Err(ParseError::InvalidInterval(1))
+        ));
+        assert!(matches!(
+            parse("a{256}", Syntax::Ere),
+            Err(ParseError::InvalidInterval(1))
+        ));
+        assert!(matches!(
+            parse("\\w", Syntax::Ere),
+            Err(ParseError::InvalidEscape(1, 'w'))
+        ));
+        // 後方参照は最左最長一致で評価できないため、POSIX の構文では使えない
+        assert!(matches!(
+            parse("(a)\\1", Syntax::Ere),
+            Err(ParseError::InvalidEscape(4, '1'))
+        ));
+        assert!(matches!(
+            parse("a\\", Syntax::Ere),
+            Err(ParseError::NoEscapeChar)
+        ));
+        assert!(matches!(
+            parse("((a{255}){255}){255}", Syntax::Ere),
+            Err(ParseError::TooLarge)
+        ));
+        assert!(matches!(
+            parse(&"(".repeat(300), Syntax::Ere),
+            Err(ParseError::TooDeep)
+        ));
+    }
+
+    #[test]
+    fn test_bre() {
+        let c = |c| AST::Char(c);
+        assert_eq!(
+            bre("a|b+?(c){1}"),
+            AST::Seq("a|b+?(c){1}".chars().map(c).collect())
+        );
+        assert_eq!(bre("\\(a\\)"), AST::Seq(vec![AST::Seq(vec![c('a')])]));
+        // ^ と $ は先頭と末尾でのみアンカー
+        assert_eq!(
+            bre("^a^$b$"),
+            AST::Seq(vec![
+                AST::LineStart,
+                c('a'),
+                c('^'),
+                c('$'),
+                c('b'),
+                AST::LineEnd
+            ])
+        );
+        assert_eq!(
+            bre("\\(^a$\\)"),
+            AST::Seq(vec![AST::Seq(vec![AST::LineStart, c('a'), AST::LineEnd])])
+        );
+        assert_eq!(bre("x\\|^a"), ere("x|^a"));
+        // 先頭の * は文字そのもの
+        assert_eq!(bre("*a"), AST::Seq(vec![c('*'), c('a')]));
+        assert_eq!(bre("^*"), AST::Seq(vec![AST::LineStart, c('*')]));
+        assert_eq!(bre("\\(*\\)"), AST::Seq(vec![AST::Seq(vec![c('*')])]));
+        assert_eq!(
+            bre("\\{1\\}a"),
+            AST::Seq(vec![c('{'), c('1'), c('}'), c('a')])
+        );
+
+        assert!(full_match(&bre("\\(ab\\|c\\)\\+d"), "abcabd"));
+        assert!(full_match(&bre("a\\{2\\}b\\?"), "aa"));
+        assert!(full_match(&bre("a*\\{2\\}"), "aaa"));
+
+        assert!(matches!(
+            parse("a\\{2", Syntax::Bre),
+            Err(ParseError::InvalidInterval(1))
+        ));
+        assert!(matches!(
+            parse("a\\{2}", Syntax::Bre),
+            Err(ParseError::InvalidInterval(1))
+        ));
+        assert!(matches!(
+            parse("\\(a", Syntax::Bre),
+            Err(ParseError::NoRightParen)
+        ));
+        assert!(matches!(
+            parse("a\\)", Syntax::Bre),
+            Err(ParseError::InvalidRightParen(1))
+        ));
+        assert!(matches!(
+            parse("\\1", Syntax::Bre),
+            Err(ParseError::InvalidEscape(1, '1'))
+        ));
+    }
+
+    #[test]
+    fn test_bracket() {
+        let class = |expr| match ere(expr) {
+            AST::Seq(mut v) => match v.pop() {
+                Some(AST::Class(class)) => class,
+                e => panic!("{e:?}"),
+            },
+            e => panic!("{e:?}"),
+        };
+
+        assert_eq!(class("[a-cx]").ranges(), &[('a', 'c'), ('x', 'x')]);
+        assert_eq!(
+            class("[]a-]").ranges(),
+            &[('-', '-'), (']', ']'), ('a', 'a')]
+        );
+        // ブラケット表現の中では \ は文字そのもの
+        assert_eq!(class("[\\n]").ranges(), &[('\\', '\\'), ('n', 'n')]);
+        assert_eq!(
+            class("[[:digit:][:upper:]_]").ranges(),
+            &[('0', '9'), ('A', 'Z'), ('_', '_')]
+        );
+        assert_eq!(class("[[.-.]-0]").ranges(), &[('-', '0')]);
+        assert_eq!(class("[[=a=]]").ranges(), &[('a', 'a')]);
+
+        let negated = class("[^[:space:]]");
+        assert!(negated.negated());
+        assert!(negated.matches('a') && !negated.matches(' ') && !negated.matches('\n'));
+
+        assert!(matches!(
+            parse("[a", Syntax::Ere),
+            Err(ParseError::NoRightBracket(0))
+        ));
+        assert!(matches!(
+            parse("x[]", Syntax::Bre),
+            Err(ParseError::NoRightBracket(1))
+        ));
+        assert!(matches!(
+            parse("[[:foo:]]", Syntax::Ere),
+            Err(ParseError::InvalidBracket(1))
+        ));
+        assert!(matches!(
+            parse("[[:alpha:]", Syntax::Ere),
+            Err(ParseError::NoRightBracket(0))
+        ));
+        assert!(matches!(
+            parse("[[.ab.]]", Syntax::Ere),
+            Err(ParseError::InvalidBracket(1))
+        ));
+        assert!(matches!(
+            parse("[c-a]", Syntax::Ere),
+            Err(ParseError::InvalidRange(2))
+        ));
+        assert!(matches!(
+            parse("[a-[:digit:]]", Syntax::Ere),
+            Err(ParseError::InvalidRange(2))
+        ));
+    }
+
+    #[test]
+    fn test_empty_loops() {
+        // 空文字列にマッチする式の繰り返しも、空ループにならない
+        for (expr, line, expected) in [
+            ("(a?)*", "aaa", true),
+            ("(a*b*)+c", "abbac", true),
+            ("(a|b?)*c", "c", true),
+            ("(^|a)*b", "aab", true),
+            ("(a?b?)*", "abba", true),
+            ("(^)*", "", true),
+            ("(a?)*", "ab", false),
+            ("(a$)*", "aa", false),
+            ("(a$)*", "a", true),
+        ] {
+            assert_eq!(full_match(&ere(expr), line), expected, "{expr} {line}");
+        }
+    }
+}
@@ -0,0 +1,290 @@
+//! AST を正規表現の文字列に変換
+//!
+//! 出力は正規形で、カッコは AST の構造を保つのに必要な箇所にのみ付与する。
+//! パーサが出力する形の AST であれば、`parse(&to_pattern(&ast))` は元の AST と一致する。
+use super::{
+    parser::{AST, CLASS_ESCAPES},
+    CharClass,
+};
+
+/// AST を正規表現の文字列に変換
+pub fn to_pattern(ast: &AST) -> String {
+    let mut printer = Printer::default();
+    printer.write_alt(ast);
+    printer.out
+}
+
+/// 特殊文字をエスケープして出力
+fn write_char(out: &mut String, c: char) {
+    if matches!(c, '\\' | '(' | ')' | '|' | '+' | '*' | '?') {
+        out.push('\\');
+    }
+    out.push(c);
+}
+
+/// 文字クラスを出力。\d などで表せるクラスはエスケープで出力する
+fn write_class(out: &mut String, class: &CharClass) {
+    let escape = CLASS_ESCAPES
+        .iter()
+        .find(|(_, ranges)| *class == CharClass::new(ranges, class.negated()));
+    match escape {
+        Some((e, _)) if class.negated() => {
+            out.push('\\');
+            out.push(e.to_ascii_uppercase());
+        }
+        Some((e, _)) => {
+            out.push('\\');
+            out.push(*e);
+        }
+        None => out.push_str(&class.to_string()),
+    }
+}
+
+/// 出力中の状態
+///
+/// 冗長なカッコを取り除くとグループの番号が変わるため、
+/// 出力したグループを数えて後方参照の番号を付け直す
+#[derive(Default)]
+struct Printer {
+    out: String,
+    // 出力したグループの数
+    groups: usize,
+    // Capture の番号ごとの、出力したグループの番号
+    captures: Vec<usize>,
+}
+
+impl Printer {
+    /// 式全体、またはカッコの中身を出力
+    ///
+    /// パーサは | で区切られた各項を Seq とし、右結合の Or で結合する
+    fn write_alt(&mut self, ast: &AST) {
+        match ast {
+            AST::Or(e1, e2) => {
+                self.write_alt(e1);
+                self.out.push('|');
+                self.write_alt(e2);
+            }
+            AST::Seq(v) => {
+                for e in v {
+                    self.write_elem(e);
+                }
+            }
+            e => self.write_elem(e),
+        }
+    }
+
+    /// Seq の要素を出力
+    ///
+    /// Seq の要素として現れる Seq と Or は、カッコで囲まれていたもの
+    fn write_elem(&mut self, ast: &AST) {
+        match ast {
+            AST::Char(c) => write_char(&mut self.out, *c),
+            AST::Class(class) => write_class(&mut self.out, class),
+            AST::End => self.out.push_str("\\z"),
+            AST::LineStart => self.out.push('^'),
+            AST::LineEnd => self.out.push('$'),
+            AST::Plus(e) => {
+                self.write_elem(e);
+                self.out.push('+');
+            }
+            AST::Star(e) => {
+                self.write_elem(e);
+                self.out.push('*');
+            }
+            AST::Question(e) => {
+                self.write_elem(e);
+                self.out.push('?');
+            }
+            AST::Or(..) | AST::Seq(_) => self.write_group(ast),
+            AST::Capture(n, e) => {
+                if self.captures.len() < *n {
+                    self.captures.resize(*n, 0);
+                }
+                self.captures[n - 1] = self.groups + 1;
+                self.write_group(e);
+            }
+            AST::Backref(n) => {
+                let n = self.captures.get(n - 1).copied().unwrap_or(*n);
+                self.out.push_str(&format!("\\{n}"));
+            }
+            AST::Look(look, e) => {
+                self.out.push_str(look.prefix());
+                self.write_alt(e);
+                self.out.push(')');
+            }
+            // 量指定子を囲むものは強欲な量指定子として出力
+            AST::Atomic(e) if matches!(**e, AST::Plus(_) | AST::Star(_) | AST::Question(_)) => {
+                self.write_elem(e);
+                self.out.push('+');
+            }
+            AST::Atomic(e) => {
+                self.out.push_str("(?>");
+                self.write_alt(e);
+                self.out.push(')');
+            }
+        }
+    }
+
+    /// カッコで囲んだグループを出力
+    fn write_group(&mut self, ast: &AST) {
+        self.groups += 1;
+        self.out.push('(');
+        self.write_alt(ast);
+        self.out.push(')');
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{generate::Xor64, parser::parse};
+
+    /// 特殊文字とマルチバイト文字を含む文字集合
+    const CHARS: &[char] = &['a', 'b', '\\', '(', ')', '|', '+', '*', '?', 'あ'];
+
+    /// 生成するクラス。\d などのエスケープで表せるもの
+    const CLASSES: &[&str] = &["\\d", "\\W", "\\s"];
+
+    /// パーサが出力する形の AST を生成
+    ///
+    /// size は入れ子の深さの上限
+    fn gen_alt(rng: &mut Xor64, size: usize) -> AST {
+        let seq = AST::Seq((0..=rng.below(3)).map(|_| gen_elem(rng, size)).collect());
+        if size > 0 && rng.below(4) == 0 {
+            AST::Or(Box::new(seq), Box::new(gen_alt(rng, size - 1)))
+        } else {
+            seq
+        }
+    }
+
+    fn gen_elem(rng: &mut Xor64, size: usize) -> AST {
+        match if size == 0 { 0 } else { rng.below(6) } {
+            0 if rng.below(4) == 0 => {
+                let AST::Seq(mut v) = parse(CLASSES[rng.below(CLASSES.len())]).unwrap() else {
+                    unreachable!()
+                };
+                v.pop().unwrap()
+            }
+            0 | 1 => AST::Char(CHARS[rng.below(CHARS.len())]),
+            // a*+ などは強欲な量指定子となるため、量指定子を直接 + で繰り返す AST は生成しない
+            2 => match gen_elem(rng, size - 1) {
+                e @ (AST::Plus(_) | AST::Star(_) | AST::Question(_)) => AST::Atomic(Box::new(e)),
+                e => AST::Plus(Box::new(e)),
+            },
+            3 => AST::Star(Box::new(gen_elem(rng, size - 1))),
+            4 => AST::Question(Box::new(gen_elem(rng, size - 1))),
+            _ => gen_alt(rng, size - 1),
+        }
+    }
+
+    /// AST を縮小した候補を返す
+    fn shrink(ast: &AST) -> Vec<AST> {
+        let mut result = Vec::new();
+        match ast {
+            AST::Or(e1, e2) => {
+                result.push((**e1).clone());
+                result.push((**e2).clone());
+                for e in shrink(e2) {
+                    result.push(AST::Or(e1.clone(), Box::new(e)));
+                }
+            }
+            AST::Seq(v) => {
+                for i in 0..v.len() {
+                    if v.len() > 1 {
+                        let mut w = v.clone();
+                        w.remove(i);
+                        result.push(AST::Seq(w));
+                    }
+                    for e in shrink_elem(&v[i]) {
+                        let mut w = v.clone();
+                        w[i] = e;
+                        result.push(AST::Seq(w));
+                    }
+                }
+            }
+            _ => (),
+        }
+        result
+    }
+
+    fn shrink_elem(ast: &AST) -> Vec<AST> {
+        match ast {
+            AST::Char(_) => Vec::new(),
+            AST::Plus(e) | AST::Star(e) | AST::Question(e) | AST::Atomic(e) => vec![(**e).clone()],
+            _ => shrink(ast),
+        }
+    }
+
+    /// 失敗する入力を縮小して、最小の反例で panic する
+    fn check<F: Fn(&AST) -> bool>(ast: AST, prop: F) {
+        if prop(&ast) {
+            return;
+        }
+        let mut ast = ast;
+        while let Some(smaller) = shrink(&ast).into_iter().find(|e| !prop(e)) {
+            ast = smaller;
+        }
+        panic!(
+            "counterexample: {:?}, pattern = {:?}",
+            ast,
+            to_pattern(&ast)
+        );
+    }
+
+    fn round_trip(ast: &AST) -> bool {
+        matches!(parse(&to_pattern(ast)), Ok(e) if &e == ast)
+    }
+
+    #[test]
+    fn test_to_pattern() {
+        for expr in [
+            "abc|(de|cd)+",
+            "a(bc)+|c(def)*",
+            "((ab+)+)+",
+            "a**b",
+            "\\(\\)\\*\\\\",
+            "(a|b)?c",
+        ] {
+            assert_eq!(to_pattern(&parse(expr).unwrap()), expr);
+        }
+
+        // 空のカッコは取り除かれる
+        assert_eq!(to_pattern(&parse("a()b").unwrap()), "ab");
+
+        // 空のカッコを取り除いた分、後方参照の番号を付け直す
+        for (expr, expected) in [
+            ("()(b)\\2", "(b)\\1"),
+            ("(a)()(c)\\3\\1", "(a)(c)\\2\\1"),
+            ("(?=ab)(?!c)(?<=(d))\\1(?<!)", "(?=ab)(?!c)(?<=(d))\\1(?<!)"),
+            ("a*+b++c?+(?>d|e)(?>f*)+", "a*+b++c?+(?>d|e)(?>f*)+"),
+            ("(?>a+)a+++(?>)", "(?>a+)a+++"),
+        ] {
+            let pattern = to_pattern(&parse(expr).unwrap());
+            assert_eq!(pattern, expected);
+            assert!(round_trip(&parse(&pattern).unwrap()), "{expr}");
+        }
+    }
+
+    #[test]
+    fn test_round_trip_property() {
+        let mut rng = Xor64::new(1234);
+        for i in 0..2000 {
+            let ast = gen_alt(&mut rng, i % 6);
+            check(ast, round_trip);
+        }
+    }
+
+    #[test]
+    fn test_round_trip_random_string() {
+        // パーサが受理する任意の文字列について、
+        // 出力した文字列を再度パースすると同じ AST になる
+        let mut rng = Xor64::new(5678);
+        for _ in 0..5000 {
+            let len = rng.below(12);
+            let expr: String = (0..len).map(|_| CHARS[rng.below(CHARS.len())]).collect();
+            if let Ok(ast) = parse(&expr) {
+                assert!(round_trip(&ast), "expr = {:?}", expr);
+            }
+        }
+    }
+}
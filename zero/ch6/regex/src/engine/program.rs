@@ -0,0 +1,832 @@
+//! コンパイル済みプログラム
+//!
+//! 命令列とキャプチャ数、フラグをまとめて保持し、
+//! バージョン付きのバイナリ形式への書き出しと読み込みを行う。
+//!
+//! ## バイナリ形式
+//!
+//! 数値はすべてリトルエンディアン。
+//!
+//! ```text
+//! magic    : b"RGXP"
+//! version  : u16
+//! flags    : u32
+//! captures : u32
+//! len      : u64  (命令数)
+//! code     : 命令 × len
+//!
+//! flags = 以下のビットの論理和
+//!   0x0001: 大文字と小文字を区別しない
+//!   0x0002: 最左最長一致で評価する
+//!
+//! 命令 = tag: u8 と、それに続くオペランド
+//!   0: char   c: u32
+//!   1: match
+//!   2: jump   addr: u64
+//!   3: split  addr1: u64, addr2: u64
+//!   4: class  negated: u8, n: u32, (lo: u32, hi: u32) × n
+//!   5: end
+//!   6: line_start
+//!   7: line_end
+//!   8: save   slot: u64
+//!   9: backref  n: u64
+//!  10: look   kind: u8, addr: u64
+//!  11: atomic_start
+//!  12: atomic_end
+//!  13: mark   n: u64
+//!  14: progress  n: u64
+//!
+//! kind = 0: 肯定先読み, 1: 否定先読み, 2: 肯定後読み, 3: 否定後読み
+//! ```
+//!
+//! バージョン 1 は tag 0 から 3、バージョン 2 は tag 0 から 5、
+//! バージョン 3 は tag 0 から 7、バージョン 4 は tag 0 から 10、
+//! バージョン 5 は tag 0 から 12 のみを持つ。
+//! 古いバージョンの形式も読み込める。
+use super::{
+    class::CharClass,
+    dot, evaluator,
+    trace::{NopTracer, Tracer},
+    verifier::{verify, VerifyError},
+    Error, Instruction, Look,
+};
+use std::{
+    borrow::Cow,
+    error,
+    fmt::{self, Display},
+};
+
+/// バイナリ形式の先頭に置くマジックナンバー
+const MAGIC: &[u8; 4] = b"RGXP";
+
+/// バイナリ形式のバージョン
+pub const VERSION: u16 = 6;
+
+/// 大文字と小文字を区別しない
+pub const FLAG_IGNORE_CASE: u32 = 1;
+
+/// 最左最長一致で評価する
+///
+/// 評価器は探索の方法によらず、到達しうる状態の集合を同時に進める方式で評価する
+pub const FLAG_LONGEST: u32 = 2;
+
+/// 定義済みのフラグ
+const KNOWN_FLAGS: u32 = FLAG_IGNORE_CASE | FLAG_LONGEST;
+
+const TAG_CHAR: u8 = 0;
+const TAG_MATCH: u8 = 1;
+const TAG_JUMP: u8 = 2;
+const TAG_SPLIT: u8 = 3;
+const TAG_CLASS: u8 = 4;
+const TAG_END: u8 = 5;
+const TAG_LINE_START: u8 = 6;
+const TAG_LINE_END: u8 = 7;
+const TAG_SAVE: u8 = 8;
+const TAG_BACKREF: u8 = 9;
+const TAG_LOOK: u8 = 10;
+const TAG_ATOMIC_START: u8 = 11;
+const TAG_ATOMIC_END: u8 = 12;
+const TAG_MARK: u8 = 13;
+const TAG_PROGRESS: u8 = 14;
+
+/// コンパイル済みのプログラム
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Program {
+    code: Cow<'static, [Instruction]>,
+    captures: u32,
+    flags: u32,
+    // 深さ優先探索でのみ評価できる命令を含むかどうか
+    backtracking: bool,
+}
+
+/// 読み込みエラーを表す型
+#[derive(Debug)]
+pub enum LoadError {
+    // マジックナンバーが一致しない
+    InvalidMagic,
+    // 未対応のバージョン
+    UnsupportedVersion(u16),
+    // 未定義のフラグ
+    UnknownFlags(u32),
+    // 入力が途中で終わっている
+    UnexpectedEof,
+    // 命令列の後ろに余分なデータがある
+    TrailingBytes(usize),
+    // 未定義の命令タグ
+    InvalidTag(usize, u8),
+    // Unicode スカラ値でない文字
+    InvalidChar(usize, u32),
+    // 文字クラスの否定フラグが 0 でも 1 でもない、または範囲の始点が終点より大きい
+    InvalidClass(usize),
+    // 先読みと後読みの種類が不正
+    InvalidLook(usize, u8),
+    // ヘッダのキャプチャ数 (第1要素) が命令列から求めた数 (第2要素) と一致しない
+    InvalidCaptures(u32, u32),
+    // 命令列の検証エラー
+    Verify(VerifyError),
+}
+
+impl Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::InvalidMagic => write!(f, "LoadError: invalid magic number"),
+            LoadError::UnsupportedVersion(v) => {
+                write!(f, "LoadError: unsupported version: {v}")
+            }
+            LoadError::UnknownFlags(flags) => {
+                write!(f, "LoadError: unknown flags: {flags:#010x}")
+            }
+            LoadError::UnexpectedEof => write!(f, "LoadError: unexpected end of input"),
+            LoadError::TrailingBytes(n) => {
+                write!(f, "LoadError: {n} trailing bytes after code")
+            }
+            LoadError::InvalidTag(pc, tag) => {
+                write!(
+                    f,
+                    "LoadError: invalid instruction tag: pc = {pc}, tag = {tag}"
+                )
+            }
+            LoadError::InvalidChar(pc, c) => {
+                write!(f, "LoadError: invalid char: pc = {pc}, value = {c:#x}")
+            }
+            LoadError::InvalidClass(pc) => {
+                write!(f, "LoadError: invalid char class: pc = {pc}")
+            }
+            LoadError::InvalidLook(pc, kind) => {
+                write!(
+                    f,
+                    "LoadError: invalid lookaround kind: pc = {pc}, kind = {kind}"
+                )
+            }
+            LoadError::InvalidCaptures(header, code) => {
+                write!(
+                    f,
+                    "LoadError: capture count mismatch: header = {header}, code = {code}"
+                )
+            }
+            LoadError::Verify(e) => write!(f, "LoadError: {e}"),
+        }
+    }
+}
+
+impl error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            LoadError::Verify(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl Program {
+    /// 命令列からプログラムを生成
+    pub(crate) fn new(code: Vec<Instruction>) -> Self {
+        Program::with_flags(code, 0)
+    }
+
+    /// 命令列とフラグからプログラムを生成
+    ///
+    /// FLAG_IGNORE_CASE が指定された場合は、命令列中の文字を小文字に変換する
+    pub(crate) fn with_flags(mut code: Vec<Instruction>, flags: u32) -> Self {
+        if flags & FLAG_IGNORE_CASE != 0 {
+            for inst in code.iter_mut() {
+                match inst {
+                    Instruction::Char(c) => *c = fold_case(*c),
+                    Instruction::Class(class) => *class = class.map(fold_case),
+                    _ => (),
+                }
+            }
+        }
+        Program::from_code(Cow::Owned(code), flags)
+    }
+
+    /// 静的な命令列からプログラムを生成
+    ///
+    /// `regex!` マクロが生成するコードのように、命令列を複製せずに用いる。
+    /// 命令列の検証に失敗した場合はパニックする
+    pub fn from_static(code: &'static [Instruction]) -> Self {
+        if let Err(e) = verify(code) {
+            panic!("invalid code: {e}");
+        }
+        Program::from_code(Cow::Borrowed(code), 0)
+    }
+
+    /// 命令列からキャプチャ数などを求めてプログラムを生成
+    fn from_code(code: Cow<'static, [Instruction]>, flags: u32) -> Self {
+        // 後方参照されるグループの数
+        let captures = code
+            .iter()
+            .filter_map(|inst| match inst {
+                Instruction::Save(slot) => Some(slot / 2),
+                _ => None,
+            })
+            .max()
+            .unwrap_or(0);
+        let backtracking = code.iter().any(Instruction::requires_backtracking);
+        Program {
+            code,
+            captures: u32::try_from(captures).unwrap_or(u32::MAX),
+            flags,
+            backtracking,
+        }
+    }
+
+    /// 命令列を返す
+    pub fn code(&self) -> &[Instruction] {
+        &self.code
+    }
+
+    /// キャプチャグループの数を返す
+    pub fn captures(&self) -> u32 {
+        self.captures
+    }
+
+    /// フラグを返す
+    pub fn flags(&self) -> u32 {
+        self.flags
+    }
+
+    /// 後方参照や先読みと後読みを含み、深さ優先探索でのみ評価できるかどうか
+    pub fn requires_backtracking(&self) -> bool {
+        self.backtracking
+    }
+
+    /// is_depth とフラグで指定された評価方法で評価できるかを検査
+    ///
+    /// 深さ優先探索でのみ評価できるプログラムを、幅優先探索や最左最長一致で
+    /// 評価しようとした場合は、文字列によらず Err を返す
+    fn check_backtracking(&self, is_depth: bool) -> Result<(), Error> {
+        if self.backtracking && (!is_depth || self.flags & FLAG_LONGEST != 0) {
+            Err(Error::RequiresBacktracking)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// 文字列とマッチング
+    ///
+    /// 引数と返り値は [`crate::do_matching`] と同じ。
+    pub fn do_matching(&self, line: &str, is_depth: bool) -> Result<bool, Error> {
+        self.check_backtracking(is_depth)?;
+        let line = self.prepare(line);
+        if self.flags & FLAG_LONGEST != 0 {
+            Ok(self
+                .eval_from(&line, 0, is_depth, &mut NopTracer)?
+                .is_some())
+        } else {
+            Ok(evaluator::eval(&self.code, &line, is_depth)?)
+        }
+    }
+
+    /// 命令を1つ実行するたびに tracer へ通知しながらマッチング
+    ///
+    /// # 利用例
+    ///
+    /// ```
+    /// use regex::{compile, PrintTracer};
+    /// let prog = compile("a|b").unwrap();
+    /// let mut tracer = PrintTracer::new(std::io::stderr());
+    /// assert!(prog.do_matching_traced("b", true, &mut tracer).unwrap());
+    /// ```
+    pub fn do_matching_traced<T: Tracer>(
+        &self,
+        line: &str,
+        is_depth: bool,
+        tracer: &mut T,
+    ) -> Result<bool, Error> {
+        self.check_backtracking(is_depth)?;
+        let line = self.prepare(line);
+        if self.flags & FLAG_LONGEST != 0 {
+            Ok(self.eval_from(&line, 0, is_depth, tracer)?.is_some())
+        } else {
+            Ok(evaluator::eval_traced(&self.code, &line, is_depth, tracer)?)
+        }
+    }
+
+    /// 文字列を評価器に渡す文字の列に変換
+    ///
+    /// FLAG_IGNORE_CASE が指定されている場合は小文字に変換する。
+    /// 変換後も文字数は変わらない
+    pub(crate) fn prepare(&self, line: &str) -> Vec<char> {
+        line.chars().map(|c| self.fold(c)).collect()
+    }
+
+    /// 文字を評価器に渡す文字に変換
+    pub(crate) fn fold(&self, c: char) -> char {
+        if self.flags & FLAG_IGNORE_CASE != 0 {
+            fold_case(c)
+        } else {
+            c
+        }
+    }
+
+    /// 改行文字を消費する命令を含むかどうか
+    ///
+    /// 含まない場合、マッチが複数の行にまたがることはない
+    pub fn matches_newline(&self) -> bool {
+        self.code.iter().any(|inst| match inst {
+            Instruction::Char(c) => *c == '\n',
+            Instruction::Class(class) => class.matches('\n'),
+            _ => false,
+        })
+    }
+
+    /// prepare で変換した文字の列の start 番目から評価し、マッチの終了位置を返す
+    ///
+    /// FLAG_LONGEST が指定されている場合は、is_depth によらず最も長いマッチの終了位置を返す
+    pub(crate) fn eval_from<T: Tracer>(
+        &self,
+        line: &[char],
+        start: usize,
+        is_depth: bool,
+        tracer: &mut T,
+    ) -> Result<Option<usize>, Error> {
+        self.check_backtracking(is_depth)?;
+        if self.flags & FLAG_LONGEST != 0 {
+            Ok(evaluator::eval_longest(&self.code, line, start, tracer)?)
+        } else {
+            Ok(evaluator::eval_from(
+                &self.code, line, start, is_depth, tracer,
+            )?)
+        }
+    }
+
+    /// 命令列を Graphviz の DOT 形式に変換
+    pub fn to_dot(&self) -> String {
+        dot::code_to_dot(&self.code)
+    }
+
+    /// バイナリ形式に変換
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend_from_slice(MAGIC);
+        buf.extend_from_slice(&VERSION.to_le_bytes());
+        buf.extend_from_slice(&self.flags.to_le_bytes());
+        buf.extend_from_slice(&self.captures.to_le_bytes());
+        buf.extend_from_slice(&(self.code.len() as u64).to_le_bytes());
+
+        for inst in self.code.iter() {
+            match inst {
+                Instruction::Char(c) => {
+                    buf.push(TAG_CHAR);
+                    buf.extend_from_slice(&u32::from(*c).to_le_bytes());
+                }
+                Instruction::Match => buf.push(TAG_MATCH),
+                Instruction::Jump(addr) => {
+                    buf.push(TAG_JUMP);
+                    buf.extend_from_slice(&(*addr as u64).to_le_bytes());
+                }
+                Instruction::Split(addr1, addr2) => {
+                    buf.push(TAG_SPLIT);
+                    buf.extend_from_slice(&(*addr1 as u64).to_le_bytes());
+                    buf.extend_from_slice(&(*addr2 as u64).to_le_bytes());
+                }
+                Instruction::Class(class) => {
+                    buf.push(TAG_CLASS);
+                    buf.push(class.negated() as u8);
+                    buf.extend_from_slice(&(class.ranges().len() as u32).to_le_bytes());
+                    for &(lo, hi) in class.ranges() {
+                        buf.extend_from_slice(&u32::from(lo).to_le_bytes());
+                        buf.extend_from_slice(&u32::from(hi).to_le_bytes());
+                    }
+                }
+                Instruction::End => buf.push(TAG_END),
+                Instruction::LineStart => buf.push(TAG_LINE_START),
+                Instruction::LineEnd => buf.push(TAG_LINE_END),
+                Instruction::Save(slot) => {
+                    buf.push(TAG_SAVE);
+                    buf.extend_from_slice(&(*slot as u64).to_le_bytes());
+                }
+                Instruction::Backref(n) => {
+                    buf.push(TAG_BACKREF);
+                    buf.extend_from_slice(&(*n as u64).to_le_bytes());
+                }
+                Instruction::Look(look, addr) => {
+                    buf.push(TAG_LOOK);
+                    buf.push(match look {
+                        Look::Ahead => 0,
+                        Look::NotAhead => 1,
+                        Look::Behind => 2,
+                        Look::NotBehind => 3,
+                    });
+                    buf.extend_from_slice(&(*addr as u64).to_le_bytes());
+                }
+                Instruction::AtomicStart => buf.push(TAG_ATOMIC_START),
+                Instruction::AtomicEnd => buf.push(TAG_ATOMIC_END),
+                Instruction::Mark(n) => {
+                    buf.push(TAG_MARK);
+                    buf.extend_from_slice(&(*n as u64).to_le_bytes());
+                }
+                Instruction::Progress(n) => {
+                    buf.push(TAG_PROGRESS);
+                    buf.extend_from_slice(&(*n as u64).to_le_bytes());
+                }
+            }
+        }
+        buf
+    }
+
+    /// バイナリ形式から読み込み
+    ///
+    /// ヘッダを検証したうえで、命令列を検証器にかける。
+    /// キャプチャ数は命令列から求め、ヘッダの値と一致しなければエラーを返す。
+    /// ジャンプ先が範囲外の場合などは評価前にエラーを返す
+    pub fn from_bytes(bytes: &[u8]) -> Result<Program, LoadError> {
+        let mut r = Reader { bytes, pos: 0 };
+
+        if r.take(MAGIC.len())? != MAGIC {
+            return Err(LoadError::InvalidMagic);
+        }
+
+        let version = r.read_u16()?;
+        if !(1..=VERSION).contains(&version) {
+            return Err(LoadError::UnsupportedVersion(version));
+        }
+
+        let flags = r.read_u32()?;
+        if flags & !KNOWN_FLAGS != 0 {
+            return Err(LoadError::UnknownFlags(flags));
+        }
+
+        let captures = r.read_u32()?;
+        let len = r.read_u64()?;
+
+        let mut code = Vec::new();
+        let mut pc = 0;
+        while (pc as u64) < len {
+            let inst = match r.read_u8()? {
+                TAG_CHAR => Instruction::Char(r.read_char(pc)?),
+                TAG_MATCH => Instruction::Match,
+                TAG_JUMP => Instruction::Jump(r.read_addr()?),
+                TAG_SPLIT => {
+                    let addr1 = r.read_addr()?;
+                    let addr2 = r.read_addr()?;
+                    Instruction::Split(addr1, addr2)
+                }
+                TAG_CLASS if version >= 2 => Instruction::Class(r.read_class(pc)?),
+                TAG_END if version >= 2 => Instruction::End,
+                TAG_LINE_START if version >= 3 => Instruction::LineStart,
+                TAG_LINE_END if version >= 3 => Instruction::LineEnd,
+                TAG_SAVE if version >= 4 => Instruction::Save(r.read_addr()?),
+                TAG_BACKREF if version >= 4 => Instruction::Backref(r.read_addr()?),
+                TAG_LOOK if version >= 4 => {
+                    let look = match r.read_u8()? {
+                        0 => Look::Ahead,
+                        1 => Look::NotAhead,
+                        2 => Look::Behind,
+                        3 => Look::NotBehind,
+                        kind => return Err(LoadError::InvalidLook(pc, kind)),
+                    };
+                    Instruction::Look(look, r.read_addr()?)
+                }
+                TAG_ATOMIC_START if version >= 5 => Instruction::AtomicStart,
+                TAG_ATOMIC_END if version >= 5 => Instruction::AtomicEnd,
+                TAG_MARK if version >= 6 => Instruction::Mark(r.read_addr()?),
+                TAG_PROGRESS if version >= 6 => Instruction::Progress(r.read_addr()?),
+                tag => return Err(LoadError::InvalidTag(pc, tag)),
+            };
+            code.push(inst);
+            pc += 1;
+        }
+
+        if r.pos != bytes.len() {
+            return Err(LoadError::TrailingBytes(bytes.len() - r.pos));
+        }
+
+        verify(&code).map_err(LoadError::Verify)?;
+
+        let prog = Program::with_flags(code, flags);
+        if prog.captures != captures {
+            return Err(LoadError::InvalidCaptures(captures, prog.captures));
+        }
+        Ok(prog)
+    }
+}
+
+/// 大文字と小文字を区別しない比較のため、文字を小文字に変換
+///
+/// 文字数が変わらないよう、小文字が1文字に定まらない場合はそのまま返す
+pub(crate) fn fold_case(c: char) -> char {
+    let mut lower = c.to_lowercase();
+    match (lower.next(), lower.next()) {
+        (Some(l), None) => l,
+        _ => c,
+    }
+}
+
+/// バイト列を先頭から読み進めるための型
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], LoadError> {
+        let end = self.pos.checked_add(n).ok_or(LoadError::UnexpectedEof)?;
+        let s = self
+            .bytes
+            .get(self.pos..end)
+            .ok_or(LoadError::UnexpectedEof)?;
+        self.pos = end;
+        Ok(s)
+    }
+
+    fn read_u8(&mut self) -> Result<u8, LoadError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn read_u16(&mut self) -> Result<u16, LoadError> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn read_u32(&mut self) -> Result<u32, LoadError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn read_u64(&mut self) -> Result<u64, LoadError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    /// 文字を読み込む
+    ///
+    /// Unicode スカラ値でない場合は、pc 番目の命令のエラーとする
+    fn read_char(&mut self, pc: usize) -> Result<char, LoadError> {
+        let n = self.read_u32()?;
+        char::from_u32(n).ok_or(LoadError::InvalidChar(pc, n))
+    }
+
+    /// 文字クラスを読み込む
+    ///
+    /// 範囲の数が大きくても、実際に読み込めた分だけメモリを確保する
+    fn read_class(&mut self, pc: usize) -> Result<CharClass, LoadError> {
+        let negated = match self.read_u8()? {
+            0 => false,
+            1 => true,
+            _ => return Err(LoadError::InvalidClass(pc)),
+        };
+        let n = self.read_u32()?;
+        let mut ranges = Vec::new();
+        for _ in 0..n {
+            let lo = self.read_char(pc)?;
+            let hi = self.read_char(pc)?;
+            if lo > hi {
+                return Err(LoadError::InvalidClass(pc));
+            }
+            ranges.push((lo, hi));
+        }
+        Ok(CharClass::new(&ranges, negated))
+    }
+
+    /// ジャンプ先アドレスを読み込む
+    ///
+    /// usize に収まらない値は範囲外として検証器に検出させる
+    fn read_addr(&mut self) -> Result<usize, LoadError> {
+        let addr = self.read_u64()?;
+        Ok(usize::try_from(addr).unwrap_or(usize::MAX))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::compile;
+
+    #[test]
+    fn test_round_trip() {
+        for expr in ["abc|(de|cd)+", "a?a?aa", "(a*)*b", "あい*"] {
+            let prog = compile(expr).unwrap();
+            let loaded = Program::from_bytes(&prog.to_bytes()).unwrap();
+            assert_eq!(prog, loaded);
+            assert!(loaded.do_matching("aab", true).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_ignore_case() {
+        let prog = Program::with_flags(compile("aB|İ").unwrap().code().to_vec(), FLAG_IGNORE_CASE);
+        assert!(prog.do_matching("Ab", true).unwrap());
+        assert!(prog.do_matching("İ", false).unwrap());
+
+        let loaded = Program::from_bytes(&prog.to_bytes()).unwrap();
+        assert_eq!(loaded.flags(), FLAG_IGNORE_CASE);
+        assert!(loaded.do_matching("AB", true).unwrap());
+
+        let mut unknown = prog.to_bytes();
+        unknown[9] = 0x80;
+        assert!(matches!(
+            Program::from_bytes(&unknown),
+            Err(LoadError::UnknownFlags(0x8000_0001))
+        ));
+    }
+
+    #[test]
+    fn test_class_and_end() {
+        let code = vec![
+            Instruction::Class(CharClass::new(&[('A', 'C'), ('x', 'x')], false)),
+            Instruction::Class(CharClass::new(&[('/', '/')], true)),
+            Instruction::End,
+            Instruction::Match,
+        ];
+        let prog = Program::with_flags(code.clone(), FLAG_IGNORE_CASE);
+        assert!(prog.do_matching("bz", true).unwrap());
+        assert!(prog.do_matching("Xz", false).unwrap());
+        assert!(!prog.do_matching("b/", true).unwrap());
+        assert!(!prog.do_matching("bzz", false).unwrap());
+
+        let loaded = Program::from_bytes(&prog.to_bytes()).unwrap();
+        assert_eq!(prog, loaded);
+
+        // バージョン 1 の形式は class と end を持たない
+        let mut v1 = Program::new(code).to_bytes();
+        v1[4] = 1;
+        assert!(matches!(
+            Program::from_bytes(&v1),
+            Err(LoadError::InvalidTag(0, TAG_CLASS))
+        ));
+        let mut v1 = compile("ab|c").unwrap().to_bytes();
+        v1[4] = 1;
+        assert!(Program::from_bytes(&v1).is_ok());
+
+        // class [c-a]
+        let mut bytes = Program::new(vec![
+            Instruction::Class(CharClass::new(&[('a', 'c')], false)),
+            Instruction::Match,
+        ])
+        .to_bytes();
+        bytes[28..32].copy_from_slice(&u32::from('c').to_le_bytes());
+        bytes[32..36].copy_from_slice(&u32::from('a').to_le_bytes());
+        assert!(matches!(
+            Program::from_bytes(&bytes),
+            Err(LoadError::InvalidClass(0))
+        ));
+        bytes[23] = 2;
+        assert!(matches!(
+            Program::from_bytes(&bytes),
+            Err(LoadError::InvalidClass(0))
+        ));
+    }
+
+    #[test]
+    fn test_line_anchors() {
+        let code = vec![
+            Instruction::LineStart,
+            Instruction::Char('a'),
+            Instruction::Split(1, 3),
+            Instruction::LineEnd,
+            Instruction::Match,
+        ];
+        let prog = Program::with_flags(code.clone(), FLAG_LONGEST);
+        assert!(prog.do_matching("aa\nb", true).unwrap());
+        assert!(!prog.do_matching("aab", false).unwrap());
+
+        let loaded = Program::from_bytes(&prog.to_bytes()).unwrap();
+        assert_eq!(prog, loaded);
+        assert_eq!(loaded.flags(), FLAG_LONGEST);
+
+        // バージョン 2 の形式は line_start と line_end を持たない
+        let mut v2 = prog.to_bytes();
+        v2[4] = 2;
+        assert!(matches!(
+            Program::from_bytes(&v2),
+            Err(LoadError::InvalidTag(0, TAG_LINE_START))
+        ));
+    }
+
+    #[test]
+    fn test_backtracking() {
+        let prog = compile("(a|b)\\1(?=c)(?<!aa)").unwrap();
+        assert!(prog.requires_backtracking());
+        assert_eq!(prog.captures(), 1);
+        assert!(prog.do_matching("bbcd", true).unwrap());
+        assert!(!prog.do_matching("aac", true).unwrap());
+        assert!(prog.do_matching("bbcd", false).is_err());
+        assert!(!compile("ab|c").unwrap().requires_backtracking());
+
+        let loaded = Program::from_bytes(&prog.to_bytes()).unwrap();
+        assert_eq!(prog, loaded);
+        assert!(loaded.requires_backtracking());
+
+        // ヘッダのキャプチャ数が命令列と一致しない
+        let mut captures = prog.to_bytes();
+        captures[10..14].copy_from_slice(&5u32.to_le_bytes());
+        assert!(matches!(
+            Program::from_bytes(&captures),
+            Err(LoadError::InvalidCaptures(5, 1))
+        ));
+        captures[10..14].copy_from_slice(&0u32.to_le_bytes());
+        assert!(matches!(
+            Program::from_bytes(&captures),
+            Err(LoadError::InvalidCaptures(0, 1))
+        ));
+
+        // 最左最長一致では評価できない
+        let longest = Program::with_flags(prog.code().to_vec(), FLAG_LONGEST);
+        assert!(longest.do_matching("bbcd", true).is_err());
+
+        // バージョン 3 の形式は save, backref, look を持たない
+        let mut v3 = prog.to_bytes();
+        v3[4] = 3;
+        assert!(matches!(
+            Program::from_bytes(&v3),
+            Err(LoadError::InvalidTag(0, TAG_SAVE))
+        ));
+
+        // バージョン 4 の形式は atomic_start と atomic_end を持たない
+        let mut v4 = compile("a++").unwrap().to_bytes();
+        assert!(Program::from_bytes(&v4).unwrap().requires_backtracking());
+        v4[4] = 4;
+        assert!(matches!(
+            Program::from_bytes(&v4),
+            Err(LoadError::InvalidTag(0, TAG_ATOMIC_START))
+        ));
+
+        // バージョン 5 の形式は mark と progress を持たない
+        let prog = compile("(a?)*").unwrap();
+        let loaded = Program::from_bytes(&prog.to_bytes()).unwrap();
+        assert_eq!(prog, loaded);
+        assert!(loaded.do_matching("aa", true).unwrap());
+        let mut v5 = prog.to_bytes();
+        v5[4] = 5;
+        assert!(matches!(
+            Program::from_bytes(&v5),
+            Err(LoadError::InvalidTag(1, TAG_MARK))
+        ));
+
+        // look の種類が不正
+        let mut bytes = compile("(?=a)").unwrap().to_bytes();
+        bytes[23] = 4;
+        assert!(matches!(
+            Program::from_bytes(&bytes),
+            Err(LoadError::InvalidLook(0, 4))
+        ));
+    }
+
+    #[test]
+    fn test_from_static() {
+        static CODE: [Instruction; 2] = [Instruction::Char('a'), Instruction::Match];
+        let prog = Program::from_static(&CODE);
+        assert_eq!(prog, Program::new(CODE.to_vec()));
+        // 命令列を複製しない
+        assert!(std::ptr::eq(prog.code(), &CODE[..]));
+        assert!(prog.do_matching("ab", true).unwrap());
+
+        static INVALID: [Instruction; 1] = [Instruction::Jump(5)];
+        assert!(std::panic::catch_unwind(|| Program::from_static(&INVALID)).is_err());
+    }
+
+    #[test]
+    fn test_invalid_input() {
+        let bytes = compile("ab|c").unwrap().to_bytes();
+
+        assert!(matches!(
+            Program::from_bytes(b"XXXX"),
+            Err(LoadError::InvalidMagic)
+        ));
+        assert!(matches!(
+            Program::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(LoadError::UnexpectedEof)
+        ));
+
+        let mut extra = bytes.clone();
+        extra.push(0);
+        assert!(matches!(
+            Program::from_bytes(&extra),
+            Err(LoadError::TrailingBytes(1))
+        ));
+
+        let mut version = bytes.clone();
+        version[4] = 99;
+        assert!(matches!(
+            Program::from_bytes(&version),
+            Err(LoadError::UnsupportedVersion(99))
+        ));
+
+        // split 0001, 0100
+        let mut target = bytes.clone();
+        target[31..39].copy_from_slice(&100u64.to_le_bytes());
+        assert!(matches!(
+            Program::from_bytes(&target),
+            Err(LoadError::Verify(VerifyError::InvalidTarget(0, 100)))
+        ));
+
+        // 空ループを含む命令列: split 0001, 0002; jump 0000; match
+        let mut prog = Program::new(vec![
+            Instruction::Split(1, 2),
+            Instruction::Jump(0),
+            Instruction::Match,
+        ])
+        .to_bytes();
+        assert!(matches!(
+            Program::from_bytes(&prog),
+            Err(LoadError::Verify(VerifyError::EmptyLoop(0)))
+        ));
+
+        // 命令数 0
+        prog.truncate(22);
+        prog[14..22].copy_from_slice(&0u64.to_le_bytes());
+        assert!(matches!(
+            Program::from_bytes(&prog),
+            Err(LoadError::Verify(VerifyError::Empty))
+        ));
+    }
+}
@@ -0,0 +1,776 @@
+//! 一度だけコンパイルし、繰り返し検索に用いる正規表現型
+//!
+//! [`crate::do_matching`] は文字列の先頭からのマッチングのみを行うが、
+//! [`Regex`] は文字列中の任意の位置から始まるマッチを検索する。
+use super::{
+    codegen,
+    parser::{self, Syntax},
+    program::{Program, FLAG_IGNORE_CASE, FLAG_LONGEST},
+    threaded::{Backend, Threaded},
+    trace::{NopTracer, Tracer},
+    verifier, Error, Instruction,
+};
+use std::{ops::Range, sync::Arc};
+
+/// コンパイル済みの正規表現
+#[derive(Debug, Clone)]
+pub struct Regex {
+    prog: Program,
+    is_depth: bool,
+    // Backend::Threaded の場合に変換したクロージャの列
+    threaded: Option<Arc<Threaded>>,
+}
+
+// 1つの Regex を複数のスレッドで共有できることをコンパイル時に確認する
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Regex>();
+    assert_send_sync::<Program>();
+    assert_send_sync::<super::Instruction>();
+};
+
+/// 正規表現をオプション付きでコンパイルするための型
+#[derive(Debug, Clone)]
+pub struct RegexBuilder {
+    expr: String,
+    syntax: Syntax,
+    ignore_case: bool,
+    longest: bool,
+    is_depth: bool,
+    backend: Backend,
+}
+
+/// マッチした範囲
+///
+/// start と end は検索対象の文字列のバイト単位のインデックス
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Match {
+    start: usize,
+    end: usize,
+}
+
+impl Match {
+    /// マッチの開始位置
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// マッチの終了位置
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    /// マッチした範囲
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.end
+    }
+
+    /// マッチが空文字列かどうか
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+}
+
+impl RegexBuilder {
+    pub fn new(expr: &str) -> Self {
+        RegexBuilder {
+            expr: expr.to_string(),
+            syntax: Syntax::Native,
+            ignore_case: false,
+            longest: false,
+            is_depth: true,
+            backend: Backend::Interpreter,
+        }
+    }
+
+    /// 正規表現の構文を指定する。既定は Syntax::Native
+    pub fn syntax(&mut self, syntax: Syntax) -> &mut Self {
+        self.syntax = syntax;
+        self
+    }
+
+    /// true の場合は大文字と小文字を区別しない
+    pub fn ignore_case(&mut self, yes: bool) -> &mut Self {
+        self.ignore_case = yes;
+        self
+    }
+
+    /// true の場合は最左最長一致、false の場合は最左優先一致で検索する
+    ///
+    /// 最左優先一致では、同じ位置から始まるマッチのうち選択や繰り返しの優先順位が
+    /// 最も高いものを返す。POSIX と同じ結果を得るには、最左最長一致を指定する
+    ///
+    /// # 利用例
+    ///
+    /// ```
+    /// use regex::{RegexBuilder, Syntax};
+    /// let re = RegexBuilder::new("a|ab").syntax(Syntax::Ere).longest(true).build().unwrap();
+    /// assert_eq!(re.find("xabc").unwrap().unwrap().range(), 1..3);
+    /// ```
+    pub fn longest(&mut self, yes: bool) -> &mut Self {
+        self.longest = yes;
+        self
+    }
+
+    /// true の場合は深さ優先探索、false の場合は幅優先探索で評価する
+    pub fn depth_first(&mut self, yes: bool) -> &mut Self {
+        self.is_depth = yes;
+        self
+    }
+
+    /// 評価器の実装を指定する。既定は Backend::Interpreter
+    ///
+    /// Backend::Threaded の場合は depth_first の指定によらず深さ優先探索で評価する。
+    /// ただし、最左最長一致と、tracer を渡す検索、後方参照や先読みと後読み、
+    /// 空文字列にマッチし得る式の繰り返しを含む正規表現では命令列を解釈して評価する
+    ///
+    /// # 利用例
+    ///
+    /// ```
+    /// use regex::{Backend, RegexBuilder};
+    /// let re = RegexBuilder::new("ab(c|d)+").backend(Backend::Threaded).build().unwrap();
+    /// assert_eq!(re.find("xabdcx").unwrap().unwrap().range(), 1..5);
+    /// ```
+    pub fn backend(&mut self, backend: Backend) -> &mut Self {
+        self.backend = backend;
+        self
+    }
+
+    /// 正規表現をパースしてコード生成し、命令列を検証する
+    ///
+    /// 後方参照や先読みと後読みは深さ優先探索でのみ評価できるため、
+    /// 幅優先探索か最左最長一致を指定した場合は Err を返す
+    ///
+    /// # 利用例
+    ///
+    /// ```
+    /// use regex::RegexBuilder;
+    /// let re = RegexBuilder::new("(ab|c)-\\1(?!x)").build().unwrap();
+    /// assert_eq!(re.find("ab-c c-c").unwrap().unwrap().range(), 5..8);
+    /// assert!(RegexBuilder::new("(ab|c)-\\1").depth_first(false).build().is_err());
+    /// ```
+    pub fn build(&self) -> Result<Regex, Error> {
+        let ast = parser::parse_with(&self.expr, self.syntax)?;
+        let code = codegen::get_code(&ast)?;
+        verifier::verify(&code)?;
+
+        let backtracking = code.iter().any(Instruction::requires_backtracking);
+        if backtracking && (self.longest || !self.is_depth) {
+            return Err(Error::RequiresBacktracking);
+        }
+
+        let mut flags = 0;
+        if self.ignore_case {
+            flags |= FLAG_IGNORE_CASE;
+        }
+        if self.longest {
+            flags |= FLAG_LONGEST;
+        }
+        // Progress で空ループを防ぐ繰り返しは、レジスタを持たないクロージャに変換できない
+        let progress = code
+            .iter()
+            .any(|inst| matches!(inst, Instruction::Progress(_)));
+        let threaded = match self.backend {
+            Backend::Threaded if !self.longest && !backtracking && !progress => {
+                Some(Arc::new(Threaded::new(&code)?))
+            }
+            _ => None,
+        };
+        Ok(Regex {
+            prog: Program::with_flags(code, flags),
+            is_depth: self.is_depth,
+            threaded,
+        })
+    }
+}
+
+/// 検索対象の文字列を、評価器に渡す文字の列に変換したもの
+///
+/// [`Regex::load`] で内容を置き換えられるため、バッファを再利用して繰り返し検索できる
+#[derive(Debug, Clone, Default)]
+pub struct Input {
+    chars: Vec<char>,
+    // i 番目の文字の開始バイト位置。末尾にはバイト長を置く
+    offsets: Vec<usize>,
+}
+
+impl Input {
+    pub fn new() -> Self {
+        Input::default()
+    }
+
+    /// バイト位置を文字単位のインデックスに変換
+    ///
+    /// 文字の境界でない場合は、その次の文字のインデックスを返す
+    fn char_index(&self, byte: usize) -> usize {
+        self.offsets.partition_point(|&i| i < byte)
+    }
+
+    /// byte より後ろにある最初の文字の境界のバイト位置
+    ///
+    /// 末尾より後ろには境界がないため、byte + 1 を返す
+    fn next_boundary(&self, byte: usize) -> usize {
+        let i = self.offsets.partition_point(|&i| i <= byte);
+        self.offsets.get(i).copied().unwrap_or(byte + 1)
+    }
+}
+
+/// 文字列中のマッチを左から順に返すイテレータ
+///
+/// [`Regex::find_iter`] と [`Regex::find_overlapping_iter`] が返す。
+/// 評価に失敗した場合はエラーを返し、以降は None を返す
+#[derive(Debug)]
+pub struct Matches<'r> {
+    re: &'r Regex,
+    input: Input,
+    // 次に検索を始めるバイト位置
+    pos: usize,
+    // 直前のマッチの終了位置
+    last_end: Option<usize>,
+    overlapping: bool,
+    // 検索し終えたかどうか
+    done: bool,
+}
+
+impl<'r> Matches<'r> {
+    fn new(re: &'r Regex, text: &str, overlapping: bool) -> Self {
+        let mut input = Input::new();
+        re.load(&mut input, text.as_bytes());
+        Matches {
+            re,
+            input,
+            pos: 0,
+            last_end: None,
+            overlapping,
+            done: false,
+        }
+    }
+}
+
+impl Iterator for Matches<'_> {
+    type Item = Result<Match, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            let m = match self.re.find_in(&self.input, self.pos) {
+                Ok(Some(m)) => m,
+                Ok(None) => break,
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            };
+
+            if self.overlapping {
+                // マッチの開始位置の次の文字から検索する
+                self.pos = self.input.next_boundary(m.start);
+                return Some(Ok(m));
+            }
+
+            // 空文字列へのマッチの後は、1文字進めてから検索する
+            self.pos = if m.is_empty() {
+                self.input.next_boundary(m.end)
+            } else {
+                m.end
+            };
+            // 直前のマッチの末尾に接する空文字列へのマッチは除く
+            if m.is_empty() && self.last_end == Some(m.end) {
+                continue;
+            }
+            self.last_end = Some(m.end);
+            return Some(Ok(m));
+        }
+        self.done = true;
+        None
+    }
+}
+
+impl Regex {
+    /// 正規表現をコンパイル
+    ///
+    /// # 利用例
+    ///
+    /// ```
+    /// use regex::Regex;
+    /// let re = Regex::new("b(cd)+").unwrap();
+    /// let m = re.find("abcdcde").unwrap().unwrap();
+    /// assert_eq!(m.range(), 1..6);
+    /// ```
+    pub fn new(expr: &str) -> Result<Regex, Error> {
+        RegexBuilder::new(expr).build()
+    }
+
+    /// コンパイル済みのプログラムから生成
+    pub fn from_program(prog: Program, is_depth: bool) -> Regex {
+        Regex {
+            prog,
+            is_depth,
+            threaded: None,
+        }
+    }
+
+    /// コンパイル済みのプログラムを返す
+    pub fn program(&self) -> &Program {
+        &self.prog
+    }
+
+    /// 文字列中にマッチする箇所があるかを判定
+    pub fn is_match(&self, text: &str) -> Result<bool, Error> {
+        Ok(self.find(text)?.is_some())
+    }
+
+    /// 文字列中で最も左にあるマッチを返す
+    pub fn find(&self, text: &str) -> Result<Option<Match>, Error> {
+        self.find_at(text, 0)
+    }
+
+    /// 文字列の start バイト目以降で最も左にあるマッチを返す
+    pub fn find_at(&self, text: &str, start: usize) -> Result<Option<Match>, Error> {
+        let mut input = Input::new();
+        self.load(&mut input, text.as_bytes());
+        self.find_in(&input, start)
+    }
+
+    /// 文字列中の重ならないマッチを左から順に返す
+    ///
+    /// 空文字列へのマッチの後は次の文字から検索し、
+    /// 直前のマッチの末尾に接する空文字列へのマッチは返さない
+    ///
+    /// # 利用例
+    ///
+    /// ```
+    /// use regex::Regex;
+    /// let re = Regex::new("a*").unwrap();
+    /// let ranges = re.find_iter("baaa").map(|m| m.unwrap().range()).collect::<Vec<_>>();
+    /// assert_eq!(ranges, [0..0, 1..4]);
+    /// ```
+    pub fn find_iter(&self, text: &str) -> Matches<'_> {
+        Matches::new(self, text, false)
+    }
+
+    /// 文字列中の各位置から始まる最も左のマッチを、重なりを許して左から順に返す
+    ///
+    /// マッチの後は、その開始位置の次の文字から検索する
+    ///
+    /// # 利用例
+    ///
+    /// ```
+    /// use regex::Regex;
+    /// let re = Regex::new("aa").unwrap();
+    /// assert_eq!(re.find_iter("aaaa").count(), 2);
+    /// assert_eq!(re.find_overlapping_iter("aaaa").count(), 3);
+    /// ```
+    pub fn find_overlapping_iter(&self, text: &str) -> Matches<'_> {
+        Matches::new(self, text, true)
+    }
+
+    /// 命令を1つ実行するたびに tracer へ通知しながら、最も左にあるマッチを返す
+    pub fn find_traced<T: Tracer>(
+        &self,
+        text: &str,
+        tracer: &mut T,
+    ) -> Result<Option<Match>, Error> {
+        self.find_traced_at(text, 0, tracer)
+    }
+
+    fn find_traced_at<T: Tracer>(
+        &self,
+        text: &str,
+        start: usize,
+        tracer: &mut T,
+    ) -> Result<Option<Match>, Error> {
+        let mut input = Input::new();
+        self.load(&mut input, text.as_bytes());
+        self.find_in_traced(&input, start, tracer)
+    }
+
+    /// バイト列を評価器に渡す文字の列に変換し、input に格納する
+    ///
+    /// UTF-8 として不正なバイトは、1バイトずつ U+FFFD とみなす
+    ///
+    /// # 利用例
+    ///
+    /// ```
+    /// use regex::{Input, Regex};
+    /// let re = Regex::new("b+").unwrap();
+    /// let mut input = Input::new();
+    /// re.load(&mut input, b"a\xffbb");
+    /// assert_eq!(re.find_in(&input, 0).unwrap().unwrap().range(), 2..4);
+    /// ```
+    pub fn load(&self, input: &mut Input, text: &[u8]) {
+        input.chars.clear();
+        input.offsets.clear();
+
+        let mut pos = 0;
+        for chunk in text.utf8_chunks() {
+            for (i, c) in chunk.valid().char_indices() {
+                input.chars.push(self.prog.fold(c));
+                input.offsets.push(pos + i);
+            }
+            pos += chunk.valid().len();
+            for _ in chunk.invalid() {
+                input.chars.push(char::REPLACEMENT_CHARACTER);
+                input.offsets.push(pos);
+                pos += 1;
+            }
+        }
+        input.offsets.push(text.len());
+    }
+
+    /// load で格納した文字列の start バイト目以降で、最も左にあるマッチを返す
+    pub fn find_in(&self, input: &Input, start: usize) -> Result<Option<Match>, Error> {
+        let Some(threaded) = &self.threaded else {
+            return self.find_in_traced(input, start, &mut NopTracer);
+        };
+
+        let mut stack = Vec::new();
+        for i in input.char_index(start)..=input.chars.len() {
+            if let Some(end) = threaded.eval_from(&input.chars, i, &mut stack) {
+                return Ok(Some(Match {
+                    start: input.offsets[i],
+                    end: input.offsets[end],
+                }));
+            }
+        }
+        Ok(None)
+    }
+
+    /// 命令を1つ実行するたびに tracer へ通知しながら、find_in と同様に検索する
+    pub fn find_in_traced<T: Tracer>(
+        &self,
+        input: &Input,
+        start: usize,
+        tracer: &mut T,
+    ) -> Result<Option<Match>, Error> {
+        // 1文字ずつずらしながら評価する。末尾では空文字列へのマッチを試みる
+        for i in input.char_index(start)..=input.chars.len() {
+            if let Some(end) = self
+                .prog
+                .eval_from(&input.chars, i, self.is_depth, tracer)?
+            {
+                return Ok(Some(Match {
+                    start: input.offsets[i],
+                    end: input.offsets[end],
+                }));
+            }
+        }
+        Ok(None)
+    }
+
+    /// マッチが改行文字を含みうるかどうか
+    ///
+    /// false の場合、複数行をまとめて検索してもマッチは行をまたがない
+    pub fn matches_newline(&self) -> bool {
+        self.prog.matches_newline()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::trace::PrintTracer;
+
+    #[test]
+    fn test_find() {
+        let re = Regex::new("b(cd)+").unwrap();
+        assert_eq!(re.find("abcdcdx").unwrap().unwrap().range(), 1..6);
+        assert_eq!(re.find_at("abcdcdbcd", 2).unwrap().unwrap().range(), 6..9);
+        assert!(re.find("acd").unwrap().is_none());
+
+        // バイト位置は UTF-8 のバイト単位
+        let re = Regex::new("い+").unwrap();
+        assert_eq!(re.find("あいいう").unwrap().unwrap().range(), 3..9);
+        // 文字の境界でない位置は次の文字から検索
+        assert_eq!(re.find_at("いあい", 1).unwrap().unwrap().range(), 6..9);
+
+        // 空文字列へのマッチ
+        let re = Regex::new("a*").unwrap();
+        assert!(re.find("bbb").unwrap().unwrap().is_empty());
+        assert_eq!(re.find_at("bbb", 3).unwrap().unwrap().range(), 3..3);
+    }
+
+    #[test]
+    fn test_find_iter() {
+        let ranges = |iter: Matches| iter.map(|m| m.unwrap().range()).collect::<Vec<_>>();
+
+        // 空文字列へのマッチ
+        let re = Regex::new("a*").unwrap();
+        assert_eq!(ranges(re.find_iter("baaa")), [0..0, 1..4]);
+        assert_eq!(
+            ranges(re.find_overlapping_iter("baaa")),
+            [0..0, 1..4, 2..4, 3..4, 4..4]
+        );
+        assert_eq!(re.find_iter("").next().unwrap().unwrap().range(), 0..0);
+        assert_eq!(re.find_iter("").count(), 1);
+        assert_eq!(ranges(re.find_iter("bb")), [0..0, 1..1, 2..2]);
+        assert_eq!(ranges(re.find_iter("aba")), [0..1, 2..3]);
+
+        // 文字の境界ごとに進める
+        assert_eq!(ranges(re.find_iter("あaい")), [0..0, 3..4, 7..7]);
+        assert_eq!(ranges(re.find_overlapping_iter("あい")), [0..0, 3..3, 6..6]);
+
+        let re = Regex::new("ab|b").unwrap();
+        assert_eq!(ranges(re.find_iter("abab")), [0..2, 2..4]);
+        assert_eq!(
+            ranges(re.find_overlapping_iter("abab")),
+            [0..2, 1..2, 2..4, 3..4]
+        );
+        assert_eq!(re.find_iter("xxx").count(), 0);
+
+        let re = RegexBuilder::new("い+")
+            .backend(Backend::Threaded)
+            .build()
+            .unwrap();
+        assert_eq!(ranges(re.find_iter("いあいい")), [0..3, 6..12]);
+        assert_eq!(
+            ranges(re.find_overlapping_iter("いあいい")),
+            [0..3, 6..12, 9..12]
+        );
+    }
+
+    #[test]
+    fn test_load() {
+        let re = RegexBuilder::new("b+").ignore_case(true).build().unwrap();
+        let mut input = Input::new();
+
+        // 不正なバイトは U+FFFD とみなし、位置は元のバイト列のもの
+        re.load(&mut input, b"\xff\xfeBb\xe3\x81");
+        assert_eq!(re.find_in(&input, 0).unwrap().unwrap().range(), 2..4);
+        assert_eq!(re.find_in(&input, 4).unwrap(), None);
+
+        // 同じ Input を再利用
+        re.load(&mut input, "あb".as_bytes());
+        assert_eq!(re.find_in(&input, 0).unwrap().unwrap().range(), 3..4);
+
+        assert!(!re.matches_newline());
+        assert!(Regex::new("a\nb").unwrap().matches_newline());
+    }
+
+    #[test]
+    fn test_builder() {
+        let re = RegexBuilder::new("ab|cd")
+            .ignore_case(true)
+            .build()
+            .unwrap();
+        assert!(re.is_match("xxCD").unwrap());
+        assert!(re.is_match("aB").unwrap());
+
+        let re = RegexBuilder::new("ab|cd")
+            .depth_first(false)
+            .build()
+            .unwrap();
+        assert!(re.is_match("xxcd").unwrap());
+        assert!(!re.is_match("xxCD").unwrap());
+
+        let re = RegexBuilder::new("ab|cd")
+            .backend(Backend::Threaded)
+            .ignore_case(true)
+            .build()
+            .unwrap();
+        assert_eq!(re.find("xxCd").unwrap().unwrap().range(), 2..4);
+        let mut input = Input::new();
+        re.load(&mut input, "abあcD".as_bytes());
+        assert_eq!(re.find_in(&input, 1).unwrap().unwrap().range(), 5..7);
+
+        // 空文字列にマッチし得る式の繰り返しは、命令列の解釈で評価する
+        let re = RegexBuilder::new("(a?)*b")
+            .backend(Backend::Threaded)
+            .build()
+            .unwrap();
+        assert_eq!(re.find("xaab").unwrap().unwrap().range(), 1..4);
+    }
+
+    #[test]
+    fn test_backtracking() {
+        let range = |re: &Regex, text| re.find(text).unwrap().map(|m| m.range());
+        let new = |expr: &str| Regex::new(expr).unwrap();
+
+        let re = new("(ab|c)\\1");
+        assert_eq!(range(&re, "abcc"), Some(2..4));
+        assert_eq!(range(&re, "xababc"), Some(1..5));
+        assert_eq!(range(&re, "abc"), None);
+
+        // 後読みは検索の開始位置より前の文字も参照する
+        let re = new("(?<=a)b+(?!c)");
+        assert_eq!(range(&re, "bbabbc"), Some(3..4));
+        assert_eq!(re.find_at("ab", 1).unwrap().unwrap().range(), 1..2);
+        let re = new("(?<!a)b");
+        assert_eq!(range(&re, "abcb"), Some(3..4));
+
+        // 肯定の先読みでキャプチャした位置は後方参照できる
+        let re = new("(?=(a|b))\\1+");
+        assert_eq!(range(&re, "xbba"), Some(1..3));
+
+        // スレッデッドコードは命令列の解釈にフォールバックする
+        let threaded = |expr: &str| {
+            RegexBuilder::new(expr)
+                .backend(Backend::Threaded)
+                .build()
+                .unwrap()
+        };
+        let re = threaded("(a|b)\\1");
+        assert_eq!(range(&re, "abb"), Some(1..3));
+
+        // 線形時間の評価方法は指定できない
+        let build = |expr: &str, longest, depth| {
+            RegexBuilder::new(expr)
+                .longest(longest)
+                .depth_first(depth)
+                .build()
+        };
+        for expr in ["(a)\\1", "(?=a)", "a(?<!b)"] {
+            assert!(build(expr, false, true).is_ok(), "{expr}");
+            let err = build(expr, true, true).unwrap_err();
+            assert!(matches!(err, Error::RequiresBacktracking), "{expr}");
+            assert!(build(expr, false, false).is_err(), "{expr}");
+        }
+    }
+
+    #[test]
+    fn test_lookbehind() {
+        let range = |re: &Regex, text| re.find(text).unwrap().map(|m| m.range());
+        let new = |expr: &str| Regex::new(expr).unwrap();
+
+        // 本体が消費する文字数が一定でない後読み
+        assert_eq!(range(&new("(?<=ab|c)d"), "adcdabd"), Some(3..4));
+        assert_eq!(range(&new("(?<=xa+)b"), "abxaab"), Some(5..6));
+        assert_eq!(range(&new("(?<=(a|bc)\\1)d"), "bcbcd"), Some(4..5));
+        assert_eq!(range(&new("(?<=a(?=b)b)c"), "abc"), Some(2..3));
+        assert_eq!(range(&new("(?<!ab)c"), "abcbc"), Some(4..5));
+
+        // 後読みは本体の長さの範囲の開始位置のみ試すため、文字列の長さに比例するステップ数で評価する
+        let steps = |expr: &str, n: usize| {
+            let mut tracer = PrintTracer::new(std::io::sink());
+            let text = "b".repeat(n);
+            assert!(new(expr).find_traced(&text, &mut tracer).unwrap().is_none());
+            tracer.steps()
+        };
+        for expr in ["(?<=a)c", "(?<=ab|a)c", "(?<!b)c", "(?<=a(?=b)b)c"] {
+            let (small, large) = (steps(expr, 1000), steps(expr, 4000));
+            assert!(large <= small * 5, "{expr}: {small} -> {large}");
+        }
+    }
+
+    #[test]
+    fn test_empty_loop() {
+        let range = |re: &Regex, text| re.find(text).unwrap().map(|m| m.range());
+        let build = |expr: &str, longest, depth| {
+            RegexBuilder::new(expr)
+                .longest(longest)
+                .depth_first(depth)
+                .build()
+                .unwrap()
+        };
+
+        // 空文字列にマッチし得る式の繰り返しは、どの評価方法でも停止する
+        for (longest, depth) in [(false, true), (false, false), (true, true)] {
+            assert_eq!(range(&build("(a?)*b", longest, depth), "xaab"), Some(1..4));
+            assert_eq!(
+                range(&build("(a*b*)+c", longest, depth), "abbac"),
+                Some(0..5)
+            );
+            assert_eq!(range(&build("(a|b?)+", longest, depth), "cab"), Some(0..0));
+            assert_eq!(range(&build("((a?)+b?)*c", longest, depth), "aabd"), None);
+        }
+
+        // 後方参照や先読みを含む繰り返し
+        let new = |expr: &str| Regex::new(expr).unwrap();
+        assert_eq!(range(&new("(a?)*\\1b"), "aab"), Some(0..3));
+        assert_eq!(range(&new("(a*)(\\1)+b"), "aaaab"), Some(0..5));
+        assert_eq!(range(&new("((?=a))*ab"), "xab"), Some(1..3));
+        assert_eq!(range(&new("(?>a*)*b"), "aab"), Some(0..3));
+    }
+
+    #[test]
+    fn test_posix() {
+        let build = |expr: &str, syntax, longest| {
+            RegexBuilder::new(expr)
+                .syntax(syntax)
+                .longest(longest)
+                .build()
+                .unwrap()
+        };
+        let range = |re: &Regex, text| re.find(text).unwrap().map(|m| m.range());
+
+        // 最左優先一致と最左最長一致
+        assert_eq!(
+            range(&build("a|ab", Syntax::Ere, false), "xabc"),
+            Some(1..2)
+        );
+        assert_eq!(range(&build("a|ab", Syntax::Ere, true), "xabc"), Some(1..3));
+        assert_eq!(
+            range(&build("(a|ab)(c|bcd)", Syntax::Ere, true), "abcd"),
+            Some(0..4)
+        );
+        assert_eq!(range(&build("a*?", Syntax::Ere, true), "baa"), Some(0..0));
+        assert_eq!(range(&build("x*", Syntax::Native, true), "xxy"), Some(0..2));
+
+        // 行頭と行末
+        let re = build("^b+$", Syntax::Ere, true);
+        assert_eq!(range(&re, "ab\nbb\nb"), Some(3..5));
+        assert_eq!(range(&re, "abb"), None);
+        let re = build("\\(^a\\|b$\\)", Syntax::Bre, false);
+        assert_eq!(range(&re, "cab"), Some(2..3));
+        assert_eq!(range(&re, "bc\nab"), Some(3..4));
+
+        let re = RegexBuilder::new("[[:upper:]]\\{2\\}")
+            .syntax(Syntax::Bre)
+            .ignore_case(true)
+            .build()
+            .unwrap();
+        assert_eq!(range(&re, "1aB2"), Some(1..3));
+
+        // POSIX の構文では空文字列にマッチする式も繰り返せる
+        assert_eq!(
+            range(&build("(a|b?)+c", Syntax::Ere, true), "xabc"),
+            Some(1..4)
+        );
+        let build_bre = |expr| RegexBuilder::new(expr).syntax(Syntax::Bre).build();
+        assert!(build_bre("a{2").is_ok());
+        assert!(build_bre("a\\{2").is_err());
+    }
+
+    #[test]
+    fn test_atomic() {
+        let range = |re: &Regex, text| re.find(text).unwrap().map(|m| m.range());
+        let new = |expr: &str| Regex::new(expr).unwrap();
+
+        // アトミックグループを抜けた後は、グループの中の分岐に戻らない
+        assert_eq!(range(&new("(a|ab)c"), "abc"), Some(0..3));
+        assert_eq!(range(&new("(?>a|ab)c"), "abc"), None);
+        assert_eq!(range(&new("(?>ab|a)c"), "xac"), Some(1..3));
+        assert_eq!(range(&new("a*a"), "aaa"), Some(0..3));
+        assert_eq!(range(&new("a*+a"), "aaa"), None);
+        assert_eq!(range(&new("a++b|a?+a"), "aab"), Some(0..3));
+        assert_eq!(range(&new("x?+x"), "xx"), Some(0..2));
+
+        // グループの前に戻る場合は、グループの中でキャプチャした位置も元に戻す
+        assert_eq!(range(&new("(?>(a|b))x|(c)\\2"), "cc"), Some(0..2));
+        assert_eq!(range(&new("(?>(a|ab))\\1"), "abab"), None);
+        assert_eq!(range(&new("(?>(ab|a))\\1"), "abab"), Some(0..4));
+
+        // 破滅的なバックトラックを起こさない
+        let text = "a".repeat(1000);
+        assert_eq!(range(&new("(a|aa)++b"), &text), None);
+
+        let build = |expr: &str| RegexBuilder::new(expr).depth_first(false).build();
+        assert!(build("a*+").is_err());
+        assert!(build("(?>a)").is_err());
+    }
+
+    #[test]
+    fn test_share_between_threads() {
+        let re = Regex::new("a(b|c)+").unwrap();
+        std::thread::scope(|s| {
+            let handles = (0..4)
+                .map(|i| {
+                    let re = &re;
+                    s.spawn(move || re.find(&format!("{}abcb", "x".repeat(i))).unwrap())
+                })
+                .collect::<Vec<_>>();
+            for (i, h) in handles.into_iter().enumerate() {
+                assert_eq!(h.join().unwrap().unwrap().range(), i..i + 4);
+            }
+        });
+    }
+}
@@ -0,0 +1,324 @@
+//! スレッデッドコードによる評価器
+//!
+//! 命令列を一度だけ変換し、命令ごとに特化したクロージャの列を作る。
+//! 評価時は命令を `match` で解釈する代わりに、pc 番目のクロージャを呼び出す。
+//!
+//! 変換の際に次の最適化を行う。
+//!
+//! - 連続する Char 命令は、文字列としてまとめて比較する
+//! - Jump 命令の連鎖は、飛び先を直接指すように置き換える
+//!
+//! 評価は深さ優先探索 (バックトラック) で行い、結果は [`super::evaluator`] の深さ優先探索と一致する。
+use super::{evaluator::EvalError, Error, Instruction};
+use std::fmt;
+
+/// 評価器の実装
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Backend {
+    /// 命令列を1命令ずつ解釈する
+    #[default]
+    Interpreter,
+    /// 命令列をクロージャの列に変換してから評価する
+    Threaded,
+}
+
+/// クロージャを実行した後の制御
+enum Flow {
+    // pc の命令に進む
+    Continue,
+    // このスレッドは失敗した。バックトラックする
+    Fail,
+    // マッチした
+    Match,
+}
+
+/// 評価中の状態
+struct Machine<'a> {
+    line: &'a [char],
+    pc: usize,
+    sp: usize,
+    // バックトラック先 (pc, sp)
+    stack: &'a mut Vec<(usize, usize)>,
+}
+
+/// 1命令に特化したクロージャ
+type Op = Box<dyn Fn(&mut Machine) -> Flow + Send + Sync>;
+
+/// クロージャの列に変換した命令列
+pub struct Threaded {
+    ops: Vec<Op>,
+    start: usize,
+}
+
+impl fmt::Debug for Threaded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Threaded")
+            .field("ops", &self.ops.len())
+            .field("start", &self.start)
+            .finish()
+    }
+}
+
+impl Threaded {
+    /// 命令列を変換する
+    ///
+    /// 飛び先が命令列の範囲外の場合や、Jump 命令だけで循環する場合は Err を返す
+    pub fn new(code: &[Instruction]) -> Result<Threaded, Error> {
+        // Jump と Split の飛び先。連続する Char 命令はここで区切る
+        let mut targets = vec![false; code.len()];
+        for inst in code {
+            match inst {
+                Instruction::Jump(addr) => {
+                    *targets.get_mut(*addr).ok_or(EvalError::InvalidPC)? = true
+                }
+                Instruction::Split(addr1, addr2) => {
+                    for addr in [addr1, addr2] {
+                        *targets.get_mut(*addr).ok_or(EvalError::InvalidPC)? = true;
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        let resolve = |pc| resolve(code, pc);
+        let mut ops = Vec::with_capacity(code.len());
+        for (pc, inst) in code.iter().enumerate() {
+            let op: Op = match inst {
+                Instruction::Char(_) => {
+                    // 直前の Char 命令から続けて実行される場合は、そちらでまとめて比較する
+                    let follows =
+                        pc > 0 && matches!(code[pc - 1], Instruction::Char(_)) && !targets[pc];
+                    let len = if follows {
+                        1
+                    } else {
+                        code[pc..]
+                            .iter()
+                            .enumerate()
+                            .take_while(|(i, inst)| {
+                                matches!(inst, Instruction::Char(_))
+                                    && (*i == 0 || !targets[pc + i])
+                            })
+                            .count()
+                    };
+                    let next = resolve(pc + len)?;
+                    if len == 1 {
+                        let Instruction::Char(c) = *inst else {
+                            unreachable!()
+                        };
+                        Box::new(move |m| {
+                            if m.line.get(m.sp) == Some(&c) {
+                                m.sp += 1;
+                                m.pc = next;
+                                Flow::Continue
+                            } else {
+                                Flow::Fail
+                            }
+                        })
+                    } else {
+                        let lit = code[pc..pc + len]
+                            .iter()
+                            .map(|inst| match inst {
+                                Instruction::Char(c) => *c,
+                                _ => unreachable!(),
+                            })
+                            .collect::<Vec<_>>();
+                        Box::new(move |m| {
+                            if m.line[m.sp.min(m.line.len())..].starts_with(&lit) {
+                                m.sp += lit.len();
+                                m.pc = next;
+                                Flow::Continue
+                            } else {
+                                Flow::Fail
+                            }
+                        })
+                    }
+                }
+                Instruction::Class(class) => {
+                    let class = class.clone();
+                    let next = resolve(pc + 1)?;
+                    Box::new(move |m| {
+                        if m.line.get(m.sp).is_some_and(|c| class.matches(*c)) {
+                            m.sp += 1;
+                            m.pc = next;
+                            Flow::Continue
+                        } else {
+                            Flow::Fail
+                        }
+                    })
+                }
+                Instruction::Match => Box::new(|_| Flow::Match),
+                Instruction::Jump(addr) => {
+                    let next = resolve(*addr)?;
+                    Box::new(move |m| {
+                        m.pc = next;
+                        Flow::Continue
+                    })
+                }
+                Instruction::Split(addr1, addr2) => {
+                    let (next1, next2) = (resolve(*addr1)?, resolve(*addr2)?);
+                    Box::new(move |m| {
+                        m.stack.push((next2, m.sp));
+                        m.pc = next1;
+                        Flow::Continue
+                    })
+                }
+                Instruction::End => {
+                    let next = resolve(pc + 1)?;
+                    Box::new(move |m| {
+                        if m.sp == m.line.len() {
+                            m.pc = next;
+                            Flow::Continue
+                        } else {
+                            Flow::Fail
+                        }
+                    })
+                }
+                Instruction::LineStart => {
+                    let next = resolve(pc + 1)?;
+                    Box::new(move |m| {
+                        if m.sp == 0 || m.line.get(m.sp - 1) == Some(&'\n') {
+                            m.pc = next;
+                            Flow::Continue
+                        } else {
+                            Flow::Fail
+                        }
+                    })
+                }
+                Instruction::LineEnd => {
+                    let next = resolve(pc + 1)?;
+                    Box::new(move |m| {
+                        if m.sp == m.line.len() || m.line.get(m.sp) == Some(&'\n') {
+                            m.pc = next;
+                            Flow::Continue
+                        } else {
+                            Flow::Fail
+                        }
+                    })
+                }
+                // キャプチャの位置とバックトラック先の区切り、Mark のレジスタを保持しないため、
+                // 命令列の解釈で評価する
+                Instruction::Save(_)
+                | Instruction::Backref(_)
+                | Instruction::Look(..)
+                | Instruction::AtomicStart
+                | Instruction::AtomicEnd
+                | Instruction::Mark(_)
+                | Instruction::Progress(_) => {
+                    return Err(Error::RequiresBacktracking);
+                }
+            };
+            ops.push(op);
+        }
+
+        Ok(Threaded {
+            start: resolve(0)?,
+            ops,
+        })
+    }
+
+    /// 文字列の start 番目の文字から評価し、マッチが終了した位置を返す
+    ///
+    /// stack はバックトラック先の保存に用いる。呼び出しをまたいで再利用できる
+    pub fn eval_from(
+        &self,
+        line: &[char],
+        start: usize,
+        stack: &mut Vec<(usize, usize)>,
+    ) -> Option<usize> {
+        stack.clear();
+        let mut m = Machine {
+            line,
+            pc: self.start,
+            sp: start,
+            stack,
+        };
+        loop {
+            match (self.ops[m.pc])(&mut m) {
+                Flow::Continue => (),
+                Flow::Fail => {
+                    let (pc, sp) = m.stack.pop()?;
+                    m.pc = pc;
+                    m.sp = sp;
+                }
+                Flow::Match => return Some(m.sp),
+            }
+        }
+    }
+}
+
+/// Jump 命令の連鎖をたどり、最初に実行される Jump 以外の命令の位置を返す
+fn resolve(code: &[Instruction], mut pc: usize) -> Result<usize, EvalError> {
+    for _ in 0..=code.len() {
+        match code.get(pc) {
+            Some(Instruction::Jump(addr)) => pc = *addr,
+            Some(_) => return Ok(pc),
+            None => return Err(EvalError::InvalidPC),
+        }
+    }
+    Err(EvalError::InvalidPC)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{compile, evaluator, trace::NopTracer, CharClass};
+
+    /// 文字列のすべての位置について、深さ優先探索の評価器と結果を比較
+    fn check(expr: &str, lines: &[&str]) {
+        let code = compile(expr).unwrap().code().to_vec();
+        let threaded = Threaded::new(&code).unwrap();
+        let mut stack = Vec::new();
+        for line in lines {
+            let line = line.chars().collect::<Vec<_>>();
+            for start in 0..=line.len() {
+                let expected =
+                    evaluator::eval_from(&code, &line, start, true, &mut NopTracer).unwrap();
+                assert_eq!(
+                    threaded.eval_from(&line, start, &mut stack),
+                    expected,
+                    "{expr}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_eval() {
+        check("abc", &["abc", "xabcd", "ab", ""]);
+        check("a(bc|bd)+e", &["abcbde", "abce", "abd"]);
+        check("(ab)*ab", &["ababab", "ab", "a"]);
+        check("a?a?aa", &["aa", "aaa", "a"]);
+        check("(a|b)*c$", &["abc", "abcc", "ab"]);
+        check("xy*z|xyw", &["xyw", "xyyz", "xz"]);
+        check("((ab)+|a)c", &["ababc", "ac", "abac"]);
+    }
+
+    #[test]
+    fn test_anchors_and_classes() {
+        let class = CharClass::new(&[('0', '9')], false);
+        let code = vec![
+            Instruction::LineStart,
+            Instruction::Class(class),
+            Instruction::Jump(3),
+            Instruction::LineEnd,
+            Instruction::Match,
+        ];
+        let threaded = Threaded::new(&code).unwrap();
+        let line = "a\n1\n".chars().collect::<Vec<_>>();
+        let mut stack = Vec::new();
+        assert_eq!(threaded.eval_from(&line, 0, &mut stack), None);
+        assert_eq!(threaded.eval_from(&line, 2, &mut stack), Some(3));
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(Threaded::new(&[Instruction::Jump(5)]).is_err());
+        assert!(Threaded::new(&[Instruction::Jump(1), Instruction::Jump(0)]).is_err());
+        assert!(Threaded::new(&[Instruction::Char('a')]).is_err());
+        let code = compile("(a)\\1").unwrap().code().to_vec();
+        assert!(matches!(
+            Threaded::new(&code),
+            Err(Error::RequiresBacktracking)
+        ));
+    }
+}
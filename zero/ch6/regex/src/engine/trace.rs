@@ -0,0 +1,92 @@
+//! 評価器の実行トレース
+//!
+//! 評価器は命令を1つ実行するたびに [`Tracer::step`] を呼び出す。
+use super::Instruction;
+use std::{io::Write, ops::ControlFlow};
+
+/// 評価器の実行を観測するためのトレイト
+pub trait Tracer {
+    /// 命令を実行する直前に呼ばれる
+    ///
+    /// pc はプログラムカウンタ、sp は文字列中の位置、thread はスレッド ID。
+    /// Break を返すと評価を打ち切り、評価器は EvalError::Aborted を返す
+    fn step(&mut self, pc: usize, sp: usize, thread: usize, inst: &Instruction) -> ControlFlow<()>;
+}
+
+/// 何もしないトレーサ
+pub struct NopTracer;
+
+impl Tracer for NopTracer {
+    #[inline(always)]
+    fn step(
+        &mut self,
+        _pc: usize,
+        _sp: usize,
+        _thread: usize,
+        _inst: &Instruction,
+    ) -> ControlFlow<()> {
+        ControlFlow::Continue(())
+    }
+}
+
+/// 実行ログを出力するトレーサ
+///
+/// 以下のように、1ステップにつき1行を出力する。
+///
+/// ```text
+/// thread 0000 | pc 0000 | sp 0000 | split 0001, 0003
+/// ```
+pub struct PrintTracer<W: Write> {
+    out: W,
+    steps: usize,
+}
+
+impl<W: Write> PrintTracer<W> {
+    pub fn new(out: W) -> Self {
+        PrintTracer { out, steps: 0 }
+    }
+
+    /// これまでに実行したステップ数を返す
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+}
+
+impl<W: Write> Tracer for PrintTracer<W> {
+    fn step(&mut self, pc: usize, sp: usize, thread: usize, inst: &Instruction) -> ControlFlow<()> {
+        self.steps += 1;
+        // トレースの出力に失敗してもマッチングは継続する
+        let _ = writeln!(
+            self.out,
+            "thread {thread:04} | pc {pc:04} | sp {sp:04} | {inst}"
+        );
+        ControlFlow::Continue(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{codegen::get_code, evaluator::eval_traced, parser::parse};
+
+    #[test]
+    fn test_print_tracer() {
+        let code = get_code(&parse("a|b").unwrap()).unwrap();
+        let line = ['b'];
+
+        let mut depth = PrintTracer::new(Vec::new());
+        assert!(eval_traced(&code, &line, true, &mut depth).unwrap());
+        let log = String::from_utf8(depth.out).unwrap();
+        assert_eq!(
+            log,
+            "thread 0000 | pc 0000 | sp 0000 | split 0001, 0003\n\
+             thread 0000 | pc 0001 | sp 0000 | char a\n\
+             thread 0001 | pc 0003 | sp 0000 | char b\n\
+             thread 0001 | pc 0004 | sp 0001 | match\n"
+        );
+
+        let mut width = PrintTracer::new(Vec::new());
+        assert!(eval_traced(&code, &line, false, &mut width).unwrap());
+        assert_eq!(width.steps(), 4);
+    }
+}
@@ -0,0 +1,379 @@
+//! 命令列の検証器
+//!
+//! 評価器が不正な命令列を受け取らないよう、評価前に以下を検査する。
+//!
+//! - Jump と Split の飛び先が命令列の範囲内にあること
+//! - Char, Class, End などの次の命令が命令列の範囲内にあること
+//! - 先頭から Match に到達可能であること
+//! - 文字を消費しない命令だけで構成されるループ (空ループ) が存在しないこと
+//!
+//! Progress(n) を通るループは、必ず Mark(n) も通る場合は空ループとみなさない。
+//! Mark(n) からの間に文字を消費していなければ Progress(n) で失敗するためである。
+use super::Instruction;
+use std::{
+    error::Error,
+    fmt::{self, Display},
+};
+
+/// 検証エラーを表す型
+#[derive(Debug)]
+pub enum VerifyError {
+    // 命令列が空
+    Empty,
+    // 飛び先が範囲外
+    InvalidTarget(usize, usize),
+    // 最後の命令が Char などで、次の命令が存在しない
+    FallThrough(usize),
+    // Match に到達不能
+    NoMatch,
+    // 空ループ
+    EmptyLoop(usize),
+}
+
+impl Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerifyError::Empty => write!(f, "VerifyError: empty code"),
+            VerifyError::InvalidTarget(pc, addr) => {
+                write!(
+                    f,
+                    "VerifyError: invalid jump target: pc = {pc}, addr = {addr}"
+                )
+            }
+            VerifyError::FallThrough(pc) => {
+                write!(f, "VerifyError: falls through the end of code: pc = {pc}")
+            }
+            VerifyError::NoMatch => write!(f, "VerifyError: match is unreachable"),
+            VerifyError::EmptyLoop(pc) => {
+                write!(f, "VerifyError: loop without consuming a char: pc = {pc}")
+            }
+        }
+    }
+}
+
+impl Error for VerifyError {}
+
+/// 命令列を検証する関数
+pub fn verify(code: &[Instruction]) -> Result<(), VerifyError> {
+    if code.is_empty() {
+        return Err(VerifyError::Empty);
+    }
+    check_targets(code)?;
+    check_match(code)?;
+    check_empty_loop(code)?;
+    Ok(())
+}
+
+/// 文字を消費せずに遷移できる飛び先を返す
+///
+/// 後方参照は空文字列にマッチし得るものとみなす
+fn empty_edges(pc: usize, inst: &Instruction) -> Vec<usize> {
+    match inst {
+        Instruction::Jump(addr) => vec![*addr],
+        Instruction::Split(addr1, addr2) => vec![*addr1, *addr2],
+        Instruction::End
+        | Instruction::LineStart
+        | Instruction::LineEnd
+        | Instruction::Save(_)
+        | Instruction::Backref(_)
+        | Instruction::AtomicStart
+        | Instruction::AtomicEnd
+        | Instruction::Mark(_)
+        | Instruction::Progress(_) => vec![pc + 1],
+        // 本体と、先読みと後読みの後に続く命令
+        Instruction::Look(_, addr) => vec![pc + 1, *addr],
+        Instruction::Char(_) | Instruction::Class(_) | Instruction::Match => Vec::new(),
+    }
+}
+
+/// すべての遷移先を返す
+fn edges(pc: usize, inst: &Instruction) -> Vec<usize> {
+    match inst {
+        Instruction::Char(_) | Instruction::Class(_) => vec![pc + 1],
+        _ => empty_edges(pc, inst),
+    }
+}
+
+/// 飛び先の範囲検査
+fn check_targets(code: &[Instruction]) -> Result<(), VerifyError> {
+    for (pc, inst) in code.iter().enumerate() {
+        if let Instruction::Char(_)
+        | Instruction::Class(_)
+        | Instruction::End
+        | Instruction::LineStart
+        | Instruction::LineEnd
+        | Instruction::Save(_)
+        | Instruction::Backref(_)
+        | Instruction::AtomicStart
+        | Instruction::AtomicEnd
+        | Instruction::Mark(_)
+        | Instruction::Progress(_) = inst
+        {
+            if pc + 1 >= code.len() {
+                return Err(VerifyError::FallThrough(pc));
+            }
+        }
+        for addr in empty_edges(pc, inst) {
+            if addr >= code.len() {
+                return Err(VerifyError::InvalidTarget(pc, addr));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Match への到達可能性の検査
+fn check_match(code: &[Instruction]) -> Result<(), VerifyError> {
+    let mut visited = vec![false; code.len()];
+    let mut stack = vec![0];
+    visited[0] = true;
+
+    while let Some(pc) = stack.pop() {
+        if let Instruction::Match = code[pc] {
+            return Ok(());
+        }
+        for next in edges(pc, &code[pc]) {
+            if !visited[next] {
+                visited[next] = true;
+                stack.push(next);
+            }
+        }
+    }
+    Err(VerifyError::NoMatch)
+}
+
+/// 空文字列にマッチし得るグループの検査
+///
+/// Save(2n) の次の命令から、文字を消費せずに Save(2n + 1) に到達できる場合、
+/// グループ n は空文字列にマッチし得る。返り値はグループの番号で引く
+fn nullable_groups(code: &[Instruction]) -> Vec<bool> {
+    let mut nullable = Vec::new();
+    for (pc, inst) in code.iter().enumerate() {
+        let Instruction::Save(slot) = inst else {
+            continue;
+        };
+        if slot % 2 != 0 {
+            continue;
+        }
+
+        let mut visited = vec![false; code.len()];
+        let mut stack = vec![pc + 1];
+        let mut found = false;
+        while let Some(pc) = stack.pop() {
+            if pc >= code.len() || visited[pc] {
+                continue;
+            }
+            visited[pc] = true;
+            if code[pc] == Instruction::Save(slot + 1) {
+                found = true;
+                break;
+            }
+            stack.extend(empty_edges(pc, &code[pc]));
+        }
+
+        let n = slot / 2;
+        if nullable.len() <= n {
+            nullable.resize(n + 1, false);
+        }
+        nullable[n] |= found;
+    }
+    nullable
+}
+
+/// 空ループの検査
+///
+/// 文字を消費しない辺のみからなるグラフを深さ優先探索し、
+/// 後退辺が見つかった場合は空ループとみなす。
+/// 後方参照は、参照するグループが空文字列にマッチし得る場合のみ文字を消費しないとみなす。
+/// 繰り返しを打ち切れる Progress からは辺を辿らない。
+/// 長い命令列でもスタックオーバーフローしないよう、再帰は使わない。
+fn check_empty_loop(code: &[Instruction]) -> Result<(), VerifyError> {
+    #[derive(Clone, Copy, PartialEq)]
+    enum Mark {
+        // 未訪問
+        New,
+        // 探索中
+        Active,
+        // 探索済み
+        Done,
+    }
+
+    let nullable = nullable_groups(code);
+    let empty_edges = |pc: usize| match code[pc] {
+        Instruction::Backref(n) if !nullable.get(n).copied().unwrap_or(false) => Vec::new(),
+        ref inst => empty_edges(pc, inst),
+    };
+    let bounded = code
+        .iter()
+        .enumerate()
+        .map(|(pc, inst)| matches!(inst, Instruction::Progress(n) if guarded(pc, *n, empty_edges, code)))
+        .collect::<Vec<_>>();
+    let empty_edges = |pc: usize| {
+        if bounded[pc] {
+            Vec::new()
+        } else {
+            empty_edges(pc)
+        }
+    };
+
+    let mut mark = vec![Mark::New; code.len()];
+
+    for root in 0..code.len() {
+        if mark[root] != Mark::New {
+            continue;
+        }
+
+        // (命令のアドレス, 次に調べる辺のインデックス) のスタック
+        let mut stack = vec![(root, 0)];
+        mark[root] = Mark::Active;
+
+        while let Some((pc, i)) = stack.pop() {
+            if let Some(&next) = empty_edges(pc).get(i) {
+                stack.push((pc, i + 1));
+                match mark[next] {
+                    Mark::New => {
+                        mark[next] = Mark::Active;
+                        stack.push((next, 0));
+                    }
+                    Mark::Active => return Err(VerifyError::EmptyLoop(next)),
+                    Mark::Done => (),
+                }
+            } else {
+                mark[pc] = Mark::Done;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// pc にある Progress(n) から文字を消費せずに戻る経路が、すべて Mark(n) を通るかを検査
+fn guarded<F>(pc: usize, n: usize, empty_edges: F, code: &[Instruction]) -> bool
+where
+    F: Fn(usize) -> Vec<usize>,
+{
+    let mut visited = vec![false; code.len()];
+    let mut stack = empty_edges(pc);
+    while let Some(next) = stack.pop() {
+        if next == pc {
+            return false;
+        }
+        if visited[next] || code[next] == Instruction::Mark(n) {
+            continue;
+        }
+        visited[next] = true;
+        stack.extend(empty_edges(next));
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{codegen::get_code, parser::parse};
+
+    #[test]
+    fn test_verify() {
+        use Instruction::*;
+
+        assert!(verify(&[Char('a'), Match]).is_ok());
+        assert!(matches!(verify(&[]), Err(VerifyError::Empty)));
+        assert!(matches!(
+            verify(&[Split(1, 3), Match]),
+            Err(VerifyError::InvalidTarget(0, 3))
+        ));
+        assert!(matches!(
+            verify(&[Match, Char('a')]),
+            Err(VerifyError::FallThrough(1))
+        ));
+        assert!(matches!(
+            verify(&[Char('a'), Match, End]),
+            Err(VerifyError::FallThrough(2))
+        ));
+        assert!(verify(&[Char('a'), End, Match]).is_ok());
+        assert!(matches!(
+            verify(&[Char('a'), Jump(0), Match]),
+            Err(VerifyError::NoMatch)
+        ));
+        assert!(matches!(
+            verify(&[Split(1, 2), Jump(0), Match]),
+            Err(VerifyError::EmptyLoop(0))
+        ));
+        // End は文字を消費しない
+        assert!(matches!(
+            verify(&[Split(1, 3), End, Jump(0), Match]),
+            Err(VerifyError::EmptyLoop(0))
+        ));
+        assert!(matches!(
+            verify(&[Match, LineStart]),
+            Err(VerifyError::FallThrough(1))
+        ));
+        assert!(matches!(
+            verify(&[Split(1, 4), LineStart, LineEnd, Jump(0), Match]),
+            Err(VerifyError::EmptyLoop(0))
+        ));
+
+        // Mark(n) を通らずに Progress(n) に戻るループは空ループになる
+        assert!(verify(&[
+            Mark(0),
+            Split(2, 3),
+            Char('a'),
+            Split(4, 6),
+            Progress(0),
+            Jump(0),
+            Match
+        ])
+        .is_ok());
+        assert!(matches!(
+            verify(&[Mark(0), Split(2, 4), Progress(0), Jump(1), Match]),
+            Err(VerifyError::EmptyLoop(1))
+        ));
+        assert!(matches!(
+            verify(&[Mark(1), Progress(0), Split(0, 3), Match]),
+            Err(VerifyError::EmptyLoop(0))
+        ));
+        assert!(matches!(
+            verify(&[Match, Progress(0)]),
+            Err(VerifyError::FallThrough(1))
+        ));
+    }
+
+    #[test]
+    fn test_verify_codegen() {
+        for expr in ["abc|(de|cd)+", "((((a*)*)*)*)", "a**b", "(ab|cd)*"] {
+            let code = get_code(&parse(expr).unwrap()).unwrap();
+            assert!(verify(&code).is_ok(), "{expr}");
+        }
+
+        // 空文字列にマッチする式の繰り返しも、Progress で打ち切るため空ループにならない
+        for expr in ["(a?)*", "(a*b*)*", "(a|b?)+", "((a?)*)*", "((a?)+b?)*"] {
+            let code = get_code(&parse(expr).unwrap()).unwrap();
+            assert!(verify(&code).is_ok(), "{expr}");
+            assert!(
+                code.iter()
+                    .any(|inst| matches!(inst, Instruction::Progress(_))),
+                "{expr}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_verify_backtracking() {
+        for expr in [
+            "(a|b)\\1*",
+            "(?=a)b",
+            "((?<!a)b)+",
+            "(a)(?=\\1)",
+            "(?>a|b)*",
+            "a*+b++",
+        ] {
+            let code = get_code(&parse(expr).unwrap()).unwrap();
+            assert!(verify(&code).is_ok(), "{expr}");
+        }
+
+        // 空文字列にマッチし得るグループへの後方参照と、先読みの繰り返し
+        for expr in ["(a?)\\1*", "((?=a))*", "(a*)(\\1)+", "(?>a*)*", "(a?+)*"] {
+            let code = get_code(&parse(expr).unwrap()).unwrap();
+            assert!(verify(&code).is_ok(), "{expr}");
+        }
+    }
+}
@@ -0,0 +1,71 @@
+//! パターンの説明を表示するコマンド
+//!
+//! パターンを字句の種類ごとに色付けして表示し、続けて各部分の説明を木として表示する。
+//! 説明の各行は対応する字句と同じ色で表示する。
+use crate::grep::{paint, ColorChoice, DynError};
+use clap::ValueEnum;
+use regex::{Explanation, Language, TokenKind};
+use std::io::{self, Write};
+
+/// 説明文の言語
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Lang {
+    En,
+    Ja,
+}
+
+/// `--explain` で指定された設定
+#[derive(Debug)]
+pub struct Config {
+    pub pattern: String,
+    pub lang: Lang,
+    /// 字句と説明の色付け。検索と同じく --color で指定する
+    pub color: ColorChoice,
+}
+
+/// 字句の種類ごとの SGR パラメータ
+fn sgr(kind: TokenKind) -> &'static str {
+    match kind {
+        TokenKind::Literal => "32",
+        TokenKind::Escape => "36",
+        TokenKind::Quantifier => "33",
+        TokenKind::Group => "35",
+        TokenKind::Alternation => "34",
+        TokenKind::Backref => "31",
+    }
+}
+
+pub fn run(config: Config) -> Result<(), DynError> {
+    let lang = match config.lang {
+        Lang::En => Language::English,
+        Lang::Ja => Language::Japanese,
+    };
+    let tokens = regex::tokenize(&config.pattern)?;
+    let explanation = regex::explain(&config.pattern, lang)?;
+    let color = config.color.enabled();
+
+    let mut out = io::BufWriter::new(io::stdout().lock());
+    let chars = config.pattern.chars().collect::<Vec<_>>();
+    for token in tokens {
+        let text = chars[token.span()].iter().collect::<String>();
+        paint(&mut out, color, sgr(token.kind()), text)?;
+    }
+    writeln!(out)?;
+    write_tree(&mut out, color, &explanation, 0)?;
+    out.flush()?;
+    Ok(())
+}
+
+/// 説明を字下げした木として出力
+fn write_tree<W: Write>(out: &mut W, color: bool, e: &Explanation, depth: usize) -> io::Result<()> {
+    write!(out, "{:indent$}", "", indent = depth * 2)?;
+    match e.kind() {
+        Some(kind) => paint(out, color, sgr(kind), e.label())?,
+        None => write!(out, "{}", e.label())?,
+    }
+    writeln!(out)?;
+    for child in e.children() {
+        write_tree(out, color, child, depth + 1)?;
+    }
+    Ok(())
+}
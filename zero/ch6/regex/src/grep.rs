@@ -0,0 +1,1086 @@
+//! grep 互換の検索コマンド
+//!
+//! 終了ステータスは grep と同じく、行が選択された場合は 0、
+//! 選択されなかった場合は 1、エラーが発生した場合は 2 とする。
+use crate::explain;
+use clap::{Parser, ValueEnum};
+use memmap2::Mmap;
+use regex::{Glob, Input, Match, PrintTracer, Regex, RegexBuilder, Syntax};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    env,
+    fs::{self, File},
+    io::{self, IsTerminal, Read, Write},
+    num::NonZeroUsize,
+    path::Path,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+/// コマンドが返すエラー
+pub type DynError = Box<dyn std::error::Error + Send + Sync + 'static>;
+
+/// 標準入力を表すファイル名
+const STDIN: &str = "-";
+
+/// 標準入力から読み込んだ行に付与するファイル名
+const STDIN_LABEL: &str = "(standard input)";
+
+/// ファイルをブロック単位で読み込む場合のブロックの大きさ
+const BLOCK_SIZE: usize = 256 * 1024;
+
+/// 一度に Input へ読み込むバイト数の目安
+const WINDOW: usize = 64 * 1024;
+
+/// 前後の行のグループの区切り
+const GROUP_SEPARATOR: &str = "--";
+
+/// 出力の色付けに用いる SGR パラメータ (GNU grep の既定値と同じ)
+const SGR_MATCH: &str = "01;31";
+const SGR_FILENAME: &str = "35";
+const SGR_LINE_NUMBER: &str = "32";
+const SGR_SEPARATOR: &str = "36";
+
+/// 選択された行とそれ以外の行で、ファイル名や行番号の後に付与する区切り文字
+const SEP_SELECTED: char = ':';
+const SEP_CONTEXT: char = '-';
+
+/// 出力を色付けするかどうか
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ColorChoice {
+    /// 標準出力が端末の場合のみ色付けする
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// 標準出力を色付けするかどうか
+    pub fn enabled(self) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                io::stdout().is_terminal() && env::var("TERM").is_ok_and(|t| t != "dumb")
+            }
+        }
+    }
+}
+
+/// 実行するコマンド
+#[derive(Debug)]
+pub enum Command {
+    /// パターンにマッチする行を検索
+    Search(Config),
+    /// パターンの各部分を説明
+    Explain(explain::Config),
+}
+
+#[derive(Debug)]
+pub struct Config {
+    patterns: Vec<String>,
+    files: Vec<String>,
+    /// パターンの構文。POSIX の構文の場合は最左最長一致で検索する
+    syntax: Syntax,
+    ignore_case: bool,
+    invert_match: bool,
+    count: bool,
+    line_number: bool,
+    files_with_matches: bool,
+    only_matching: bool,
+    recursive: bool,
+    debug: bool,
+    color: ColorChoice,
+    /// マッチした行の後に表示する行数
+    after_context: usize,
+    /// マッチした行の前に表示する行数
+    before_context: usize,
+    /// -A, -B, -C のいずれかが指定されたかどうか
+    context: bool,
+    /// バイナリファイルをテキストとして扱うかどうか
+    text: bool,
+    /// 検索するファイル名のグロブ
+    include: Vec<String>,
+    /// 検索しないファイル名のグロブ
+    exclude: Vec<String>,
+    /// 検索に用いるスレッド数。0 の場合は CPU 数
+    threads: usize,
+    stats: bool,
+}
+
+/// 検索結果
+#[derive(Debug, Default)]
+pub struct Status {
+    /// 1行以上選択されたかどうか
+    pub matched: bool,
+    /// ファイルの読み込みなどでエラーが発生したかどうか
+    pub error: bool,
+    /// 検索したファイル数
+    pub files: usize,
+    /// 1行以上選択されたファイル数
+    pub matched_files: usize,
+    /// 選択された行数
+    pub matched_lines: usize,
+}
+
+impl Status {
+    /// 1ファイル分の検索結果を加える
+    fn merge(&mut self, other: &Status) {
+        self.matched |= other.matched;
+        self.error |= other.error;
+        self.files += other.files;
+        self.matched_files += other.matched_files;
+        self.matched_lines += other.matched_lines;
+    }
+}
+
+#[derive(Parser, Debug)]
+#[command(
+    version,
+    about = "Print lines that match patterns",
+    long_about = None
+)]
+struct Cli {
+    /// Pattern to search for
+    #[arg(value_name = "PATTERN", required_unless_present_any = ["regexp", "explain"])]
+    pattern: Option<String>,
+
+    /// Input file(s)
+    #[arg(num_args = 0.., value_name = "FILE")]
+    files: Vec<String>,
+
+    /// Use PATTERN for matching (may be given multiple times)
+    #[arg(short = 'e', long, value_name = "PATTERN")]
+    regexp: Vec<String>,
+
+    /// PATTERN is a POSIX extended regular expression
+    #[arg(short = 'E', long, overrides_with = "basic_regexp")]
+    extended_regexp: bool,
+
+    /// PATTERN is a POSIX basic regular expression
+    #[arg(short = 'G', long, overrides_with = "extended_regexp")]
+    basic_regexp: bool,
+
+    /// Ignore case distinctions
+    #[arg(short = 'i', long)]
+    ignore_case: bool,
+
+    /// Select non-matching lines
+    #[arg(short = 'v', long)]
+    invert_match: bool,
+
+    /// Print only a count of selected lines per file
+    #[arg(short = 'c', long)]
+    count: bool,
+
+    /// Print line number with output lines
+    #[arg(short = 'n', long)]
+    line_number: bool,
+
+    /// Print only names of files with selected lines
+    #[arg(short = 'l', long)]
+    files_with_matches: bool,
+
+    /// Show only the matched parts of a line
+    #[arg(short = 'o', long)]
+    only_matching: bool,
+
+    /// Search directories recursively
+    #[arg(short = 'r', long)]
+    recursive: bool,
+
+    /// Print the AST, code and an execution trace of the evaluator
+    #[arg(short = 'd', long)]
+    debug: bool,
+
+    /// Highlight matches, file names and line numbers
+    #[arg(
+        long,
+        alias = "colour",
+        value_name = "WHEN",
+        value_enum,
+        num_args = 0..=1,
+        require_equals = true,
+        default_value_t = ColorChoice::Auto,
+        default_missing_value = "auto"
+    )]
+    color: ColorChoice,
+
+    /// Print NUM lines of trailing context
+    #[arg(short = 'A', long, value_name = "NUM")]
+    after_context: Option<usize>,
+
+    /// Print NUM lines of leading context
+    #[arg(short = 'B', long, value_name = "NUM")]
+    before_context: Option<usize>,
+
+    /// Print NUM lines of output context
+    #[arg(short = 'C', long, value_name = "NUM")]
+    context: Option<usize>,
+
+    /// Process a binary file as if it were text
+    #[arg(short = 'a', long)]
+    text: bool,
+
+    /// Search only files whose name matches GLOB
+    #[arg(long, value_name = "GLOB")]
+    include: Vec<String>,
+
+    /// Skip files whose name matches GLOB
+    #[arg(long, value_name = "GLOB")]
+    exclude: Vec<String>,
+
+    /// Number of worker threads (0 = number of CPUs)
+    #[arg(short = 'j', long, value_name = "NUM", default_value_t = 0)]
+    threads: usize,
+
+    /// Print a summary of searched files and matches to stderr
+    #[arg(long)]
+    stats: bool,
+
+    /// Explain each part of PATTERN in plain words instead of searching
+    #[arg(long, value_name = "PATTERN", conflicts_with_all = ["pattern", "regexp"])]
+    explain: Option<String>,
+
+    /// Language of the explanation [default: en]
+    #[arg(long, value_enum, requires = "explain", conflicts_with_all = ["pattern", "regexp"])]
+    lang: Option<explain::Lang>,
+}
+
+pub fn get_args() -> Result<Command, DynError> {
+    let cli = Cli::parse();
+    if let Some(pattern) = cli.explain {
+        return Ok(Command::Explain(explain::Config {
+            pattern,
+            lang: cli.lang.unwrap_or(explain::Lang::En),
+            color: cli.color,
+        }));
+    }
+
+    // -e が指定された場合、最初の位置引数はファイル名
+    let mut files = cli.files;
+    let patterns = if cli.regexp.is_empty() {
+        cli.pattern.into_iter().collect()
+    } else {
+        if let Some(file) = cli.pattern {
+            files.insert(0, file);
+        }
+        cli.regexp
+    };
+
+    // grep と同じく、改行で区切られたパターンはそれぞれ別のパターンとみなす
+    let patterns = patterns
+        .iter()
+        .flat_map(|p| p.split('\n'))
+        .map(|p| p.to_string())
+        .collect();
+
+    // -E と -G は後に指定したものが優先される
+    let syntax = if cli.extended_regexp {
+        Syntax::Ere
+    } else if cli.basic_regexp {
+        Syntax::Bre
+    } else {
+        Syntax::Native
+    };
+
+    Ok(Command::Search(Config {
+        patterns,
+        files,
+        syntax,
+        ignore_case: cli.ignore_case,
+        invert_match: cli.invert_match,
+        count: cli.count,
+        line_number: cli.line_number,
+        files_with_matches: cli.files_with_matches,
+        only_matching: cli.only_matching,
+        recursive: cli.recursive,
+        debug: cli.debug,
+        color: cli.color,
+        // -A と -B は -C より優先する
+        after_context: cli.after_context.or(cli.context).unwrap_or(0),
+        before_context: cli.before_context.or(cli.context).unwrap_or(0),
+        context: cli.after_context.is_some()
+            || cli.before_context.is_some()
+            || cli.context.is_some(),
+        text: cli.text,
+        include: cli.include,
+        exclude: cli.exclude,
+        threads: cli.threads,
+        stats: cli.stats,
+    }))
+}
+
+pub fn run(config: Config) -> Result<Status, DynError> {
+    let mut regexes = Vec::new();
+    for pattern in &config.patterns {
+        if config.debug {
+            regex::print_with(pattern, config.syntax)?;
+            println!();
+        }
+        let re = RegexBuilder::new(pattern)
+            .syntax(config.syntax)
+            .longest(config.syntax != Syntax::Native)
+            .ignore_case(config.ignore_case)
+            .build()
+            .map_err(|e| format!("{pattern}: {e:#}"))?;
+        regexes.push(re);
+    }
+
+    // ファイルが指定されなかった場合は、-r ならカレントディレクトリ、そうでなければ標準入力
+    let (files, implicit) = if config.files.is_empty() {
+        let file = if config.recursive { "." } else { STDIN };
+        (vec![file.to_string()], true)
+    } else {
+        (config.files.clone(), false)
+    };
+
+    let with_filename =
+        files.len() > 1 || (config.recursive && files.iter().any(|f| Path::new(f).is_dir()));
+
+    let color = config.color.enabled();
+
+    let shared = Shared {
+        config: &config,
+        regexes: &regexes,
+        with_filename,
+        color,
+    };
+
+    // 検索するファイルの一覧。ディレクトリを走査できなかった場合はエラーメッセージを置く
+    let mut found = Vec::new();
+    for file in &files {
+        if config.recursive && Path::new(file).is_dir() {
+            walk(Path::new(file), implicit, &mut found);
+        } else {
+            found.push(Ok(file.clone()));
+        }
+    }
+
+    let filter = Filter::new(&config)?;
+    let mut jobs = Vec::new();
+    for job in found {
+        match &job {
+            Ok(name) if name != STDIN && !filter.accepts(name)? => (),
+            _ => jobs.push(job),
+        }
+    }
+
+    // -d の実行ログが混ざらないよう、-d の場合は1スレッドで検索する
+    let threads = if config.debug {
+        1
+    } else if config.threads > 0 {
+        config.threads
+    } else {
+        thread::available_parallelism().map_or(1, NonZeroUsize::get)
+    };
+
+    let timer = Instant::now();
+    let mut writer = Writer {
+        out: io::BufWriter::new(io::stdout().lock()),
+        config: &config,
+        color,
+        status: Status::default(),
+        printed: false,
+    };
+    search_parallel(&shared, &jobs, threads, &mut writer)?;
+    writer.out.flush()?;
+
+    if config.stats {
+        print_stats(&writer.status, timer.elapsed());
+    }
+    Ok(writer.status)
+}
+
+/// --include と --exclude によるファイル名の絞り込み
+struct Filter {
+    include: Vec<Glob>,
+    exclude: Vec<Glob>,
+}
+
+impl Filter {
+    fn new(config: &Config) -> Result<Filter, DynError> {
+        let compile = |globs: &[String]| -> Result<Vec<Glob>, DynError> {
+            globs
+                .iter()
+                .map(|g| Glob::new(g).map_err(|e| format!("{g}: {e:#}").into()))
+                .collect()
+        };
+        Ok(Filter {
+            include: compile(&config.include)?,
+            exclude: compile(&config.exclude)?,
+        })
+    }
+
+    /// ファイルを検索するかどうかを判定
+    ///
+    /// grep と同じく、名前全体か、/ の直後から始まる名前の末尾部分のいずれかが
+    /// グロブにマッチするかを調べる。--exclude は --include より優先する
+    fn accepts(&self, name: &str) -> Result<bool, DynError> {
+        let suffixes = std::iter::once(name).chain(
+            name.match_indices('/')
+                .map(|(i, _)| &name[i + 1..])
+                .filter(|s| !s.is_empty() && !s.starts_with('/')),
+        );
+
+        let mut included = self.include.is_empty();
+        for suffix in suffixes {
+            for glob in &self.exclude {
+                if glob.is_match(suffix)? {
+                    return Ok(false);
+                }
+            }
+            for glob in &self.include {
+                included = included || glob.is_match(suffix)?;
+            }
+        }
+        Ok(included)
+    }
+}
+
+/// ディレクトリを再帰的に走査し、ファイルを名前順に jobs へ追加する
+///
+/// GNU grep の -r と同じく、走査中に見つけたシンボリックリンクはたどらない。
+/// strip_dot が true の場合は、ファイル名の先頭の `./` を取り除く
+fn walk(dir: &Path, strip_dot: bool, jobs: &mut Vec<Result<String, String>>) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => return jobs.push(Err(format!("{}: {e}", dir.display()))),
+    };
+
+    let mut paths = Vec::new();
+    for entry in entries {
+        match entry.and_then(|e| Ok((e.path(), e.file_type()?))) {
+            Ok(entry) => paths.push(entry),
+            Err(e) => jobs.push(Err(format!("{}: {e}", dir.display()))),
+        }
+    }
+    paths.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    for (path, file_type) in paths {
+        if file_type.is_symlink() {
+            continue;
+        }
+        if file_type.is_dir() {
+            walk(&path, strip_dot, jobs);
+        } else {
+            let path = if strip_dot {
+                path.strip_prefix(".").unwrap_or(&path)
+            } else {
+                &path
+            };
+            jobs.push(Ok(path.display().to_string()));
+        }
+    }
+}
+
+/// ファイルを複数のスレッドで検索し、結果を jobs の順に出力する
+///
+/// 各スレッドはファイルごとの出力をバッファに書き込み、
+/// メインスレッドが順番の揃ったものから標準出力へ書き出す
+fn search_parallel<W: Write>(
+    shared: &Shared,
+    jobs: &[Result<String, String>],
+    threads: usize,
+    writer: &mut Writer<W>,
+) -> Result<(), DynError> {
+    let next = AtomicUsize::new(0);
+    let (tx, rx) = mpsc::channel();
+
+    thread::scope(|s| {
+        for _ in 0..threads.min(jobs.len()) {
+            let tx = tx.clone();
+            let next = &next;
+            s.spawn(move || loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some(job) = jobs.get(i) else {
+                    break;
+                };
+                let result = match job {
+                    Ok(name) => Ok(shared.search_file(name)),
+                    Err(msg) => Err(msg.clone()),
+                };
+                // 受信側が書き込みエラーで終了した場合は検索を打ち切る
+                if tx.send((i, result)).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(tx);
+
+        let mut pending = BTreeMap::new();
+        let mut done = 0;
+        for (i, result) in rx {
+            pending.insert(i, result);
+            while let Some(result) = pending.remove(&done) {
+                match result {
+                    Ok(searcher) => writer.write(searcher)?,
+                    Err(msg) => writer.report(&msg),
+                }
+                done += 1;
+            }
+        }
+        Ok(())
+    })
+}
+
+/// 検索したファイル数とマッチした数を標準エラー出力に表示
+fn print_stats(status: &Status, elapsed: Duration) {
+    eprintln!(
+        "{} files searched, {} files matched, {} lines matched in {:.3}s",
+        status.files,
+        status.matched_files,
+        status.matched_lines,
+        elapsed.as_secs_f64()
+    );
+}
+
+/// color が true の場合は、SGR パラメータ sgr で色付けして出力
+pub fn paint<W: Write>(
+    out: &mut W,
+    color: bool,
+    sgr: &str,
+    text: impl std::fmt::Display,
+) -> io::Result<()> {
+    if color {
+        write!(out, "\x1b[{sgr}m\x1b[K{text}\x1b[m\x1b[K")
+    } else {
+        write!(out, "{text}")
+    }
+}
+
+/// すべてのスレッドで共有する検索の設定
+struct Shared<'a> {
+    config: &'a Config,
+    regexes: &'a [Regex],
+    with_filename: bool,
+    color: bool,
+}
+
+impl<'a> Shared<'a> {
+    /// 1ファイルを検索し、出力をバッファに書き込んだ Searcher を返す
+    fn search_file(&self, name: &str) -> Searcher<'a, Vec<u8>> {
+        let mut searcher = Searcher {
+            config: self.config,
+            regexes: self.regexes,
+            with_filename: self.with_filename,
+            color: self.color,
+            out: Vec::new(),
+            status: Status {
+                files: 1,
+                ..Status::default()
+            },
+            printed: false,
+            errors: Vec::new(),
+            binary_match: None,
+            inputs: vec![Input::new(); self.regexes.len()],
+            line_inputs: vec![Input::new(); self.regexes.len()],
+        };
+        searcher.search_file(name);
+        searcher
+    }
+}
+
+/// ファイルごとの検索結果を標準出力に書き出す型
+struct Writer<'a, W: Write> {
+    out: W,
+    config: &'a Config,
+    color: bool,
+    status: Status,
+    /// いずれかの行を出力したかどうか
+    printed: bool,
+}
+
+impl<W: Write> Writer<'_, W> {
+    /// エラーを表示し、終了ステータスに反映する
+    fn report(&mut self, msg: &str) {
+        eprintln!("regex: {msg}");
+        self.status.error = true;
+    }
+
+    fn write(&mut self, searcher: Searcher<Vec<u8>>) -> io::Result<()> {
+        // 前のファイルの出力との間にもグループの区切りを出力する
+        if self.config.context && self.printed && searcher.printed {
+            paint(&mut self.out, self.color, SGR_SEPARATOR, GROUP_SEPARATOR)?;
+            writeln!(self.out)?;
+        }
+        self.printed |= searcher.printed;
+        self.out.write_all(&searcher.out)?;
+
+        for msg in &searcher.errors {
+            self.report(msg);
+        }
+        if let Some(name) = &searcher.binary_match {
+            eprintln!("regex: {name}: binary file matches");
+        }
+        self.status.merge(&searcher.status);
+        Ok(())
+    }
+}
+
+/// 1ファイルの検索の状態を保持する型
+struct Searcher<'a, W: Write> {
+    config: &'a Config,
+    regexes: &'a [Regex],
+    with_filename: bool,
+    color: bool,
+    out: W,
+    status: Status,
+    /// いずれかの行を出力したかどうか。グループの区切りの出力に用いる
+    printed: bool,
+    /// 発生したエラーのメッセージ
+    errors: Vec<String>,
+    /// バイナリファイル中でマッチした場合は、そのファイル名
+    binary_match: Option<String>,
+    /// ブロック全体を変換した文字列。パターンごとに持つ
+    inputs: Vec<Input>,
+    /// 1行分を変換した文字列。パターンごとに持つ
+    line_inputs: Vec<Input>,
+}
+
+/// ファイル中の前後の行の表示状態
+#[derive(Debug, Default)]
+struct Context {
+    /// 選択された行の前に表示する候補の行 (行番号, 行)
+    before: VecDeque<(usize, Vec<u8>)>,
+    /// 続けて表示する後ろの行の残り数
+    after: usize,
+    /// 最後に出力した行番号
+    last: Option<usize>,
+}
+
+/// 1ファイルを読み進める間の状態
+#[derive(Debug, Default)]
+struct Scan {
+    /// 次の行の行番号 - 1
+    line_number: usize,
+    /// 選択された行数
+    count: usize,
+    ctx: Context,
+    /// NUL 文字を含むブロックを読んだかどうか
+    binary: bool,
+    /// これ以上読む必要がないかどうか
+    done: bool,
+}
+
+/// 行末の改行を取り除く
+fn trim_newline(line: &[u8]) -> &[u8] {
+    line.strip_suffix(b"\n").unwrap_or(line)
+}
+
+impl<W: Write> Searcher<'_, W> {
+    /// エラーを記録し、終了ステータスに反映する
+    fn report(&mut self, name: &str, err: impl std::fmt::Display) {
+        self.errors.push(format!("{name}: {err:#}"));
+        self.status.error = true;
+    }
+
+    fn search_file(&mut self, name: &str) {
+        let result = if name == STDIN {
+            self.search_reader(io::stdin().lock(), STDIN_LABEL)
+        } else if Path::new(name).is_dir() {
+            Err("Is a directory".into())
+        } else {
+            match File::open(name) {
+                Ok(f) => self.search_mmap(f, name),
+                Err(e) => Err(e.into()),
+            }
+        };
+
+        if let Err(e) = result {
+            self.report(name, e);
+        }
+    }
+
+    /// ファイルをメモリにマップして検索する
+    ///
+    /// マップできないファイル (パイプなど) はブロック単位で読み込んで検索する
+    fn search_mmap(&mut self, file: File, name: &str) -> Result<(), DynError> {
+        // SAFETY: 検索中に他のプロセスがファイルを変更した場合、
+        // 読み込む内容が不定になるが、grep と同じくこれを許容する
+        match unsafe { Mmap::map(&file) } {
+            Ok(mmap) => {
+                let mut scan = Scan::default();
+                self.search_block(&mmap, name, &mut scan)?;
+                self.finish(name, &scan)
+            }
+            Err(_) => self.search_reader(file, name),
+        }
+    }
+
+    /// BLOCK_SIZE 単位で読み込み、完全な行を含む部分ごとに検索する
+    fn search_reader<R: Read>(&mut self, mut reader: R, name: &str) -> Result<(), DynError> {
+        let mut scan = Scan::default();
+        let mut buf = vec![0; BLOCK_SIZE];
+        // buf[..len] は読み込み済みで未検索のバイト列
+        let mut len = 0;
+
+        loop {
+            // 1行がバッファより長い場合はバッファを広げる
+            if len == buf.len() {
+                buf.resize(buf.len() * 2, 0);
+            }
+            let n = match reader.read(&mut buf[len..]) {
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            };
+            if n == 0 {
+                self.search_block(&buf[..len], name, &mut scan)?;
+                break;
+            }
+
+            let end = len + n;
+            if let Some(i) = buf[len..end].iter().rposition(|&b| b == b'\n') {
+                let complete = len + i + 1;
+                self.search_block(&buf[..complete], name, &mut scan)?;
+                buf.copy_within(complete..end, 0);
+                len = end - complete;
+            } else {
+                len = end;
+            }
+            if scan.done {
+                break;
+            }
+        }
+        self.finish(name, &scan)
+    }
+
+    /// 行の並びからなるバイト列を検索する
+    ///
+    /// Input は元のバイト列の十数倍の大きさとなるため、
+    /// ブロック全体ではなく WINDOW バイト程度の行の並びごとに読み込んで検索する
+    fn search_block(&mut self, block: &[u8], name: &str, scan: &mut Scan) -> Result<(), DynError> {
+        if !self.config.text && block.contains(&0) {
+            scan.binary = true;
+        }
+
+        let mut pos = 0;
+        while pos < block.len() && !scan.done {
+            // WINDOW バイト目以降で最初の改行までを1つの窓とする
+            let end = block
+                .get(pos + WINDOW..)
+                .and_then(|rest| rest.iter().position(|&b| b == b'\n'))
+                .map_or(block.len(), |i| pos + WINDOW + i + 1);
+            self.search_window(&block[pos..end], name, scan)?;
+            pos = end;
+        }
+        Ok(())
+    }
+
+    /// 行の並びからなる窓を検索する
+    ///
+    /// マッチが行をまたがない場合は窓全体をまとめて検索し、
+    /// マッチが見つかった箇所の前後でのみ行の境界を求める
+    fn search_window(&mut self, block: &[u8], name: &str, scan: &mut Scan) -> Result<(), DynError> {
+        let config = self.config;
+
+        // -d の場合は、実行ログを行ごとに表示するため1行ずつ検索する
+        let whole = !config.debug && self.regexes.iter().all(|re| !re.matches_newline());
+        // パターンごとの、次のマッチの開始位置
+        let mut next = Vec::new();
+        if whole {
+            for (re, input) in self.regexes.iter().zip(self.inputs.iter_mut()) {
+                re.load(input, block);
+                next.push(re.find_in(input, 0)?.map(|m| m.start()));
+            }
+        }
+
+        let mut pos = 0;
+        while pos < block.len() && !scan.done {
+            let hit = if whole {
+                self.next_hit(block, pos, &mut next)?
+            } else {
+                self.next_hit_by_line(block, pos)?
+            };
+
+            // pos から次にマッチする行までは、マッチしない行
+            let (start, end) = hit.unwrap_or((block.len(), block.len()));
+            let gap = &block[pos..start];
+            if config.invert_match || scan.ctx.after > 0 || config.before_context > 0 {
+                for line in gap.split_inclusive(|&b| b == b'\n') {
+                    if scan.done {
+                        return Ok(());
+                    }
+                    self.line(trim_newline(line), false, name, scan)?;
+                }
+            } else {
+                scan.line_number += gap.iter().filter(|&&b| b == b'\n').count();
+            }
+
+            if hit.is_some() && !scan.done {
+                self.line(&block[start..end], true, name, scan)?;
+            }
+            pos = end + 1;
+        }
+        Ok(())
+    }
+
+    /// pos 以降で、いずれかのパターンにマッチする最初の行の範囲を返す
+    ///
+    /// next にはパターンごとに、前回求めたマッチの開始位置を保持する
+    fn next_hit(
+        &self,
+        block: &[u8],
+        pos: usize,
+        next: &mut [Option<usize>],
+    ) -> Result<Option<(usize, usize)>, DynError> {
+        let mut first: Option<usize> = None;
+        for (i, re) in self.regexes.iter().enumerate() {
+            if let Some(start) = next[i] {
+                if start < pos {
+                    next[i] = re.find_in(&self.inputs[i], pos)?.map(|m| m.start());
+                }
+            }
+            if let Some(start) = next[i] {
+                first = Some(first.map_or(start, |f| f.min(start)));
+            }
+        }
+
+        // 最後の改行の後ろにある空文字列は行ではない
+        let Some(m) = first.filter(|&m| m < block.len() || !block.ends_with(b"\n")) else {
+            return Ok(None);
+        };
+        let start = block[pos..m]
+            .iter()
+            .rposition(|&b| b == b'\n')
+            .map_or(pos, |i| pos + i + 1);
+        let end = block[m..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .map_or(block.len(), |i| m + i);
+        Ok(Some((start, end)))
+    }
+
+    /// pos 以降の行を1行ずつ検索し、マッチする最初の行の範囲を返す
+    fn next_hit_by_line(
+        &mut self,
+        block: &[u8],
+        pos: usize,
+    ) -> Result<Option<(usize, usize)>, DynError> {
+        let mut start = pos;
+        for line in block[pos..].split_inclusive(|&b| b == b'\n') {
+            let line = trim_newline(line);
+            if self.is_match(line)? {
+                return Ok(Some((start, start + line.len())));
+            }
+            start += line.len() + 1;
+        }
+        Ok(None)
+    }
+
+    /// 1行を処理する。matched はパターンにマッチしたかどうか
+    fn line(
+        &mut self,
+        line: &[u8],
+        matched: bool,
+        name: &str,
+        scan: &mut Scan,
+    ) -> Result<(), DynError> {
+        let config = self.config;
+        scan.line_number += 1;
+        let line_number = scan.line_number;
+        let ctx = &mut scan.ctx;
+
+        if matched == config.invert_match {
+            if ctx.after > 0 {
+                ctx.after -= 1;
+                self.print_line(name, line_number, line, SEP_CONTEXT, ctx)?;
+            } else if config.before_context > 0 {
+                if ctx.before.len() == config.before_context {
+                    ctx.before.pop_front();
+                }
+                ctx.before.push_back((line_number, line.to_vec()));
+            }
+            return Ok(());
+        }
+
+        scan.count += 1;
+        self.status.matched = true;
+        self.status.matched_lines += 1;
+
+        if config.files_with_matches {
+            scan.done = true;
+        } else if !config.count {
+            // -c の場合は行を出力せず、数えるだけとする
+            if scan.binary {
+                // バイナリファイルの場合は行を出力せず、マッチしたことのみ報告する
+                self.binary_match = Some(name.to_string());
+                scan.done = true;
+            } else {
+                while let Some((n, l)) = ctx.before.pop_front() {
+                    self.print_line(name, n, &l, SEP_CONTEXT, ctx)?;
+                }
+                self.print_line(name, line_number, line, SEP_SELECTED, ctx)?;
+                ctx.after = config.after_context;
+            }
+        }
+        Ok(())
+    }
+
+    /// ファイルの検索を終え、-l や -c の結果を出力する
+    fn finish(&mut self, name: &str, scan: &Scan) -> Result<(), DynError> {
+        if scan.count > 0 {
+            self.status.matched_files = 1;
+        }
+
+        if self.config.files_with_matches {
+            if scan.count > 0 {
+                self.paint(SGR_FILENAME, name)?;
+                writeln!(self.out)?;
+            }
+        } else if self.config.count {
+            if self.with_filename {
+                self.paint(SGR_FILENAME, name)?;
+                self.paint(SGR_SEPARATOR, SEP_SELECTED)?;
+            }
+            writeln!(self.out, "{}", scan.count)?;
+        }
+        Ok(())
+    }
+
+    /// いずれかのパターンにマッチするかを判定
+    fn is_match(&mut self, line: &[u8]) -> Result<bool, DynError> {
+        for (re, input) in self.regexes.iter().zip(self.line_inputs.iter_mut()) {
+            re.load(input, line);
+            let m = if self.config.debug {
+                eprintln!("TRACE: {:?}", String::from_utf8_lossy(line));
+                re.find_in_traced(input, 0, &mut PrintTracer::new(io::stderr()))?
+            } else {
+                re.find_in(input, 0)?
+            };
+            if m.is_some() {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    fn paint(&mut self, sgr: &str, text: impl std::fmt::Display) -> io::Result<()> {
+        paint(&mut self.out, self.color, sgr, text)
+    }
+
+    /// 1行を出力
+    ///
+    /// sep は選択された行なら `:`、前後の行なら `-`。
+    /// 直前に出力した行と連続していない場合は、先にグループの区切りを出力する。
+    /// -o の場合、前後の行は出力しない
+    fn print_line(
+        &mut self,
+        name: &str,
+        line_number: usize,
+        line: &[u8],
+        sep: char,
+        ctx: &mut Context,
+    ) -> Result<(), DynError> {
+        let config = self.config;
+        if config.context && self.printed && ctx.last.is_none_or(|n| n + 1 != line_number) {
+            self.paint(SGR_SEPARATOR, GROUP_SEPARATOR)?;
+            writeln!(self.out)?;
+        }
+        self.printed = true;
+        ctx.last = Some(line_number);
+
+        // -v の場合は、前後の行がパターンにマッチした行
+        let matching = (sep == SEP_SELECTED) != config.invert_match;
+        if config.only_matching {
+            if matching {
+                for m in self.matches(line)? {
+                    self.print_prefix(name, line_number, sep)?;
+                    self.paint_bytes(SGR_MATCH, &line[m.range()])?;
+                    writeln!(self.out)?;
+                }
+            }
+            return Ok(());
+        }
+
+        self.print_prefix(name, line_number, sep)?;
+        if self.color && matching {
+            let mut pos = 0;
+            for m in self.matches(line)? {
+                self.out.write_all(&line[pos..m.start()])?;
+                self.paint_bytes(SGR_MATCH, &line[m.range()])?;
+                pos = m.end();
+            }
+            self.out.write_all(&line[pos..])?;
+        } else {
+            self.out.write_all(line)?;
+        }
+        writeln!(self.out)?;
+        Ok(())
+    }
+
+    /// 行の一部を、UTF-8 として不正なバイトも含めてそのまま出力
+    fn paint_bytes(&mut self, sgr: &str, bytes: &[u8]) -> io::Result<()> {
+        if self.color {
+            write!(self.out, "\x1b[{sgr}m\x1b[K")?;
+            self.out.write_all(bytes)?;
+            write!(self.out, "\x1b[m\x1b[K")
+        } else {
+            self.out.write_all(bytes)
+        }
+    }
+
+    /// ファイル名と行番号を出力
+    fn print_prefix(&mut self, name: &str, line_number: usize, sep: char) -> io::Result<()> {
+        if self.with_filename {
+            self.paint(SGR_FILENAME, name)?;
+            self.paint(SGR_SEPARATOR, sep)?;
+        }
+        if self.config.line_number {
+            self.paint(SGR_LINE_NUMBER, line_number)?;
+            self.paint(SGR_SEPARATOR, sep)?;
+        }
+        Ok(())
+    }
+
+    /// 行中の空でないマッチを左から順に返す
+    ///
+    /// 空文字列へのマッチは含めず、1文字進めて検索を続ける
+    fn matches(&mut self, line: &[u8]) -> Result<Vec<Match>, DynError> {
+        for (re, input) in self.regexes.iter().zip(self.line_inputs.iter_mut()) {
+            re.load(input, line);
+        }
+
+        let mut result = Vec::new();
+        let mut pos = 0;
+        while pos <= line.len() {
+            let Some(m) = self.find_at(pos)? else {
+                break;
+            };
+            if m.is_empty() {
+                // 文字の境界でない位置からは、次の文字から検索される
+                pos = m.end() + 1;
+            } else {
+                pos = m.end();
+                result.push(m);
+            }
+        }
+        Ok(result)
+    }
+
+    /// line_inputs に変換した行について、すべてのパターンのうち最も左にあるマッチを返す
+    ///
+    /// 開始位置が同じ場合は、より長いマッチを優先する
+    fn find_at(&self, pos: usize) -> Result<Option<Match>, DynError> {
+        let mut best: Option<Match> = None;
+        for (re, input) in self.regexes.iter().zip(&self.line_inputs) {
+            if let Some(m) = re.find_in(input, pos)? {
+                let better = match best {
+                    None => true,
+                    Some(b) => {
+                        m.start() < b.start() || (m.start() == b.start() && m.end() > b.end())
+                    }
+                };
+                if better {
+                    best = Some(m);
+                }
+            }
+        }
+        Ok(best)
+    }
+}
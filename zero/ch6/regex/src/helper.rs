@@ -9,9 +9,9 @@ impl SafeAdd for usize {
 }
 
 pub fn safe_add<T, F, E>(dst: &mut T, src: &T, f: F) -> Result<(), E>
-    where
-        T: SafeAdd,
-        F: Fn() -> E,
+where
+    T: SafeAdd,
+    F: Fn() -> E,
 {
     if let Some(n) = dst.safe_add(src) {
         *dst = n;
@@ -20,5 +20,3 @@ pub fn safe_add<T, F, E>(dst: &mut T, src: &T, f: F) -> Result<(), E>
         Err(f())
     }
 }
-
-pub type DynError = Box<dyn std::error::Error + Send + Sync + 'static>;
\ No newline at end of file
@@ -8,8 +8,124 @@
 //! let line = "cdefdefdef"; // マッチ対象文字列
 //! regex::do_matching(expr, line, true); // 幅優先探索でマッチング
 //! regex::print(expr); // 正規表現のASTと命令列を表示
+//!
+//! let prog = regex::compile(expr).unwrap(); // 一度だけコンパイル
+//! let bytes = prog.to_bytes(); // バイナリ形式で保存
+//! let prog = regex::Program::from_bytes(&bytes).unwrap(); // 読み込み
+//! prog.do_matching(line, true);
+//!
+//! let re = regex::Regex::new(expr).unwrap(); // 文字列中の任意の位置を検索
+//! re.find("xxcdefxx"); // 最も左にあるマッチの範囲
+//! re.find_iter("abcdefcd").count(); // 重ならないマッチの数
+//!
+//! // POSIX 拡張正規表現を最左最長一致で検索
+//! let re = regex::RegexBuilder::new("[[:digit:]]{2,}|x")
+//!     .syntax(regex::Syntax::Ere)
+//!     .longest(true)
+//!     .build()
+//!     .unwrap();
+//! re.find("ab1234");
+//!
+//! // DFA に変換し、2 つの正規表現が同じ文字列にマッチし得るか調べる
+//! let a = regex::Dfa::new("a(b|c)*").unwrap();
+//! let b = regex::Dfa::new("(a|b)*c").unwrap();
+//! let both = a.intersection(&b);
+//! assert_eq!(both.shortest_witness().as_deref(), Some("ac"));
+//! assert!(a.is_subset_of(&regex::Dfa::new("a(b|c|d)*").unwrap()));
+//!
+//! // マッチする文字列をシード付きの乱数で生成し、短い順に列挙
+//! let mut rng = regex::Xor64::new(1234);
+//! regex::generate("a(b|c)*", &mut rng, 8).unwrap();
+//! let all = regex::enumerate("a(b|c)*", 2).unwrap().collect::<Vec<_>>();
+//! assert_eq!(all, ["a", "ab", "ac"]);
 //! ```
 mod engine;
 mod helper;
 
-pub use engine::{do_matching, print};
\ No newline at end of file
+pub use engine::{
+    analyze, ast_to_json, canonicalize, compile, do_matching, enumerate, explain, generate, print,
+    print_with, program_to_json, to_dot, tokenize, trace_to_json, Backend, CharClass, CodeGenError,
+    Dfa, DfaError, Enumerate, Error, EvalError, Explanation, Finding, Generator, Glob, GlobError,
+    Input, Instruction, Issue, JsonTracer, Language, LoadError, Look, Match, Matches, NopTracer,
+    ParseError, PrintTracer, Program, Regex, RegexBuilder, Report, Severity, Syntax, Token,
+    TokenKind, Tracer, VerifyError, Xor64, FLAG_IGNORE_CASE, FLAG_LONGEST, MAX_TRACE_STEPS,
+};
+
+// 単体テスト
+#[cfg(test)]
+mod tests {
+    use crate::engine::do_matching;
+    use crate::helper::{safe_add, SafeAdd};
+
+    #[test]
+    fn test_safe_add() {
+        let n: usize = 10;
+        assert_eq!(Some(30), n.safe_add(&20));
+
+        let n: usize = !0;
+        assert_eq!(None, n.safe_add(&1));
+
+        let mut n: usize = 10;
+        assert!(safe_add(&mut n, &20, || ()).is_ok());
+
+        let mut n: usize = !0;
+        assert!(safe_add(&mut n, &1, || ()).is_err());
+    }
+
+    #[test]
+    fn test_matching() {
+        // パースエラー
+        assert!(do_matching("+b", "bbb", true).is_err());
+        assert!(do_matching("*b", "bbb", true).is_err());
+        assert!(do_matching("|b", "bbb", true).is_err());
+        assert!(do_matching("?b", "bbb", true).is_err());
+        assert!(do_matching("b\\", "bbb", true).is_err());
+        assert!(do_matching("a\\x", "ax", true).is_err());
+        assert!(do_matching(&"(".repeat(300), "", true).is_err());
+        assert!(do_matching(
+            &format!("{}a{}", "(".repeat(300), ")".repeat(300)),
+            "a",
+            true
+        )
+        .is_err());
+        assert!(do_matching(&format!("a{}", "*".repeat(300)), "a", true).is_err());
+
+        // クラスの略記と後方参照
+        assert!(do_matching("(\\w+) \\1", "ab ab", true).unwrap());
+        assert!(!do_matching("(\\w+) \\1", "ab ac", true).unwrap());
+        assert!(do_matching("\\d+\\s\\D", "12 x", true).unwrap());
+        assert!(!do_matching("\\S\\W", "a_", true).unwrap());
+
+        // 空文字列にマッチする式の繰り返し
+        assert!(do_matching("(a?)*", "aaa", true).unwrap());
+        assert!(do_matching("(a*b*)+c", "abc", false).unwrap());
+        assert!(!do_matching("(a*b*)+c", "abd", true).unwrap());
+
+        // パース成功、マッチ成功
+        assert!(do_matching("abc|def", "def", true).unwrap());
+        assert!(do_matching("(abc)*", "abcabc", true).unwrap());
+        assert!(do_matching("(ab|cd)+", "abcdcd", true).unwrap());
+        assert!(do_matching("abc?", "ab", true).unwrap());
+        assert!(do_matching("abc?d", "abcd", true).unwrap());
+        assert!(do_matching("abc?d", "abd", true).unwrap());
+        assert!(do_matching("ab*", "a", true).unwrap());
+        assert!(do_matching("ab*", "aabb", true).unwrap());
+        assert!(do_matching("((ab+)+)+", "ababababab", true).unwrap());
+        assert!(do_matching("((((a*)*)*)*)", "aaaaaaaaa", true).unwrap());
+        assert!(do_matching("(a*)*b", "aaaaaaaaab", true).unwrap());
+        assert!(do_matching("(a*)*b", "b", true).unwrap());
+        assert!(do_matching("a**b", "aaaaaaaaab", true).unwrap());
+        assert!(do_matching("a**b", "b", true).unwrap());
+        assert!(do_matching("a*b", &format!("{}b", "a".repeat(1_000_000)), true).unwrap());
+
+        assert!(do_matching("(?>ab|a)b", "abb", true).unwrap());
+        assert!(do_matching("a++b", &format!("{}b", "a".repeat(1_000_000)), true).unwrap());
+
+        // パース成功、マッチ失敗
+        assert!(!do_matching("abc|def", "efa", true).unwrap());
+        assert!(!do_matching("(ab|cd)+", "", true).unwrap());
+        assert!(!do_matching("abc?", "acb", true).unwrap());
+        assert!(!do_matching("(?>a|ab)c", "abc", true).unwrap());
+        assert!(!do_matching("a*+ab", "aaab", true).unwrap());
+    }
+}
@@ -1,107 +1,33 @@
 //! 正規表現
-mod engine;
-mod helper;
+mod explain;
+mod grep;
 
-use helper::DynError;
-use std::{env, fs::File, io::{BufRead, BufReader}};
+use grep::Command;
+use std::process;
 
-/// 指定ファイルから正規表現にマッチする行を表示する
+/// 正規表現にマッチする行を表示する
 ///
-/// # 引数
-///
-/// 第一引数には正規表現、第二引数にはファイル名を指定する
-pub fn main() -> Result<(), DynError> {
-    let args: Vec<String> = env::args().collect();
-
-    if args.len() <= 2 {
-        eprintln!("usage: {} regex file", args[0]);
-        return Err("invalid arguments".into());
-    } else {
-        match_file(&args[1], &args[2])?;
-    }
-    Ok(())
-}
-
-/// ファイルをオープンし、行ごとにマッチングを行う。
-///
-/// マッチングはそれぞれの行頭から1文字ずつずらして行い、
-/// いずれかにマッチした場合に、その行がマッチしたものとみなす。
-///
-/// たとえば、abcdという文字列があった場合、以下の順にマッチが行われ、
-/// このいずれかにマッチした場合、与えられた正規表現にマッチする行と判定する。
-///
-/// - abcd
-/// - bcd
-/// - cd
-/// - d
-fn match_file(expr: &str, file: &str) -> Result<(), DynError> {
-    let f = File::open(file)?;
-    let reader = BufReader::new(f);
-
-    engine::print(expr).expect("TODO: panic message");
-    println!();
-
-    for line in reader.lines() {
-        let line = line?;
-        // i は UTF-8 の文字単位でのインデックス
-        for (i, _) in line.char_indices() {
-            if engine::do_matching(expr, &line[i..], true)? {
-                println!("{line}");
-                break;
+/// 使い方は `regex --help` を参照。
+/// 行が選択された場合は 0、選択されなかった場合は 1、エラーの場合は 2 で終了する。
+/// `--explain` は成功した場合に 0 で終了する
+pub fn main() {
+    let result = grep::get_args().and_then(|command| match command {
+        Command::Search(config) => grep::run(config).map(|status| {
+            if status.error {
+                2
+            } else if status.matched {
+                0
+            } else {
+                1
             }
+        }),
+        Command::Explain(config) => explain::run(config).map(|()| 0),
+    });
+    match result {
+        Ok(code) => process::exit(code),
+        Err(e) => {
+            eprintln!("regex: {e:#}");
+            process::exit(2);
         }
     }
-    Ok(())
 }
-
-// 単体テスト
-#[cfg(test)]
-mod tests {
-    use crate::helper::{safe_add, SafeAdd};
-    use crate::engine::do_matching;
-
-    #[test]
-    fn test_safe_add() {
-        let n: usize = 10;
-        assert_eq!(Some(30), n.safe_add(&20));
-
-        let n: usize = !0;
-        assert_eq!(None, n.safe_add(&1));
-
-        let mut n: usize = 10;
-        assert!(safe_add(&mut n, &20, || ()).is_ok());
-
-        let mut n: usize = !0;
-        assert!(safe_add(&mut n, &1, || ()).is_err());
-    }
-
-    #[test]
-    fn test_matching() {
-        // パースエラー
-        assert!(do_matching("+b", "bbb", true).is_err());
-        assert!(do_matching("*b", "bbb", true).is_err());
-        assert!(do_matching("|b", "bbb", true).is_err());
-        assert!(do_matching("?b", "bbb", true).is_err());
-
-        // パース成功、マッチ成功
-        assert!(do_matching("abc|def", "def", true).unwrap());
-        assert!(do_matching("(abc)*", "abcabc", true).unwrap());
-        assert!(do_matching("(ab|cd)+", "abcdcd", true).unwrap());
-        assert!(do_matching("abc?", "ab", true).unwrap());
-        assert!(do_matching("abc?d", "abcd", true).unwrap());
-        assert!(do_matching("abc?d", "abd", true).unwrap());
-        assert!(do_matching("ab*", "a", true).unwrap());
-        assert!(do_matching("ab*", "aabb", true).unwrap());
-        assert!(do_matching("((ab+)+)+", "ababababab", true).unwrap());
-        assert!(do_matching("((((a*)*)*)*)", "aaaaaaaaa", true).unwrap());
-        assert!(do_matching("(a*)*b", "aaaaaaaaab", true).unwrap());
-        assert!(do_matching("(a*)*b", "b", true).unwrap());
-        assert!(do_matching("a**b", "aaaaaaaaab", true).unwrap());
-        assert!(do_matching("a**b", "b", true).unwrap());
-
-        // パース成功、マッチ失敗
-        assert!(!do_matching("abc|def", "efa", true).unwrap());
-        assert!(!do_matching("(ab|cd)+", "", true).unwrap());
-        assert!(!do_matching("abc?", "acb", true).unwrap());
-    }
-}
\ No newline at end of file
@@ -0,0 +1,563 @@
+//! regex コマンドのテスト
+//!
+//! 期待値は mk-outs.sh で GNU grep の出力から生成したもの
+use std::{
+    error::Error,
+    fs,
+    io::Write,
+    process::{Command, Output, Stdio},
+};
+
+type TestResult = Result<(), Box<dyn Error>>;
+
+const PRG: &str = env!("CARGO_BIN_EXE_regex");
+const EMPTY: &str = "tests/inputs/empty.txt";
+const FOX: &str = "tests/inputs/fox.txt";
+const BUSTLE: &str = "tests/inputs/the-bustle.txt";
+const DIR: &str = "tests/inputs/dir";
+
+fn exec(args: &[&str], stdin: Option<&str>) -> Result<Output, Box<dyn Error>> {
+    let mut child = Command::new(PRG)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.unwrap_or("").as_bytes())?;
+    Ok(child.wait_with_output()?)
+}
+
+// --------------------------------------------------
+fn run(args: &[&str], expected_file: &str) -> TestResult {
+    let expected = fs::read_to_string(expected_file)?;
+    let output = exec(args, None)?;
+    assert_eq!(String::from_utf8(output.stdout)?, expected);
+    assert_eq!(output.status.code(), Some(0));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn usage() -> TestResult {
+    for flag in &["-h", "--help"] {
+        let output = exec(&[flag], None)?;
+        assert!(String::from_utf8(output.stdout)?.contains("Usage"));
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn exit_status() -> TestResult {
+    // マッチしない場合は 1
+    let output = exec(&["xyz", BUSTLE], None)?;
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+
+    // 不正なパターンは 2
+    let output = exec(&["a(b", BUSTLE], None)?;
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8(output.stderr)?.contains("no right parenthesis"));
+
+    // 存在しないファイルがある場合は、マッチしても 2
+    let output = exec(&["the", "tests/inputs/no-such-file", BUSTLE], None)?;
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8(output.stderr)?.contains("no-such-file"));
+    assert_eq!(
+        String::from_utf8(output.stdout)?,
+        format!("{BUSTLE}:The sweeping up the heart,\n")
+    );
+
+    // -r なしのディレクトリは 2
+    let output = exec(&["the", DIR], None)?;
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8(output.stderr)?.contains("Is a directory"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn bustle() -> TestResult {
+    run(&["the", BUSTLE], "tests/expected/bustle.the.out")
+}
+
+#[test]
+fn bustle_ignore_case() -> TestResult {
+    run(&["-i", "the", BUSTLE], "tests/expected/bustle.the.i.out")
+}
+
+#[test]
+fn bustle_invert_match() -> TestResult {
+    run(&["-v", "the", BUSTLE], "tests/expected/bustle.the.v.out")
+}
+
+#[test]
+fn bustle_count() -> TestResult {
+    run(&["-c", "the", BUSTLE], "tests/expected/bustle.the.c.out")
+}
+
+#[test]
+fn bustle_line_number() -> TestResult {
+    run(&["-n", "the", BUSTLE], "tests/expected/bustle.the.n.out")?;
+    run(&["-vn", "the", BUSTLE], "tests/expected/bustle.the.vn.out")
+}
+
+#[test]
+fn bustle_only_matching() -> TestResult {
+    run(
+        &["-on", "(in|up)+", BUSTLE],
+        "tests/expected/bustle.inup.on.out",
+    )
+}
+
+#[test]
+fn bustle_multiple_patterns() -> TestResult {
+    run(
+        &["-e", "death", "-e", "love", BUSTLE],
+        "tests/expected/bustle.death.love.out",
+    )?;
+    run(
+        &["-e", "death\nlove", BUSTLE],
+        "tests/expected/bustle.death.love.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn all_count() -> TestResult {
+    run(
+        &["-c", "the", BUSTLE, EMPTY, FOX],
+        "tests/expected/all.the.c.out",
+    )
+}
+
+#[test]
+fn all_files_with_matches() -> TestResult {
+    run(
+        &["-l", "the", BUSTLE, EMPTY, FOX],
+        "tests/expected/all.the.l.out",
+    )
+}
+
+#[test]
+fn all_line_number() -> TestResult {
+    run(
+        &["-n", "the|fox", BUSTLE, EMPTY, FOX],
+        "tests/expected/all.thefox.n.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dir_recursive() -> TestResult {
+    let expected = fs::read_to_string("tests/expected/dir.the.ri.out")?;
+    let output = exec(&["-r", "-i", "the", DIR], None)?;
+    assert_eq!(output.status.code(), Some(0));
+
+    // grep はディレクトリを名前順に走査しないため、行をソートして比較
+    let mut lines = String::from_utf8(output.stdout)?
+        .lines()
+        .map(|l| l.to_string())
+        .collect::<Vec<_>>();
+    lines.sort();
+    assert_eq!(lines.join("\n") + "\n", expected);
+    Ok(())
+}
+
+#[test]
+fn only_matching_ignore_case() -> TestResult {
+    run(
+        &["-oi", "th(e|o)", "tests/inputs/dir/sub/b.txt"],
+        "tests/expected/b.theo.oi.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn fox_stdin() -> TestResult {
+    let expected = fs::read_to_string("tests/expected/fox.o.stdin.out")?;
+    let input = fs::read_to_string(FOX)?;
+    for args in [&["o"][..], &["o", "-"][..]] {
+        let output = exec(args, Some(&input))?;
+        assert_eq!(String::from_utf8(output.stdout)?, expected);
+    }
+
+    let output = exec(&["-c", "o", "-", EMPTY], Some(&input))?;
+    assert_eq!(
+        String::from_utf8(output.stdout)?,
+        format!("(standard input):1\n{EMPTY}:0\n")
+    );
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn color_always() -> TestResult {
+    run(
+        &["--color=always", "the|fox", BUSTLE, EMPTY, FOX],
+        "tests/expected/all.thefox.color.out",
+    )?;
+    run(
+        &["--color=always", "-c", "the", BUSTLE, EMPTY, FOX],
+        "tests/expected/all.the.c.color.out",
+    )?;
+    run(
+        &["--color=always", "-l", "the", BUSTLE, EMPTY, FOX],
+        "tests/expected/all.the.l.color.out",
+    )?;
+    run(
+        &["--color=always", "-o", "in|up", BUSTLE],
+        "tests/expected/bustle.inup.o.color.out",
+    )
+}
+
+#[test]
+fn color_never() -> TestResult {
+    run(
+        &["--color=never", "the", BUSTLE],
+        "tests/expected/bustle.the.out",
+    )?;
+    // パイプへの出力は色付けしない
+    run(&["--color", "the", BUSTLE], "tests/expected/bustle.the.out")?;
+    run(
+        &["--colour=auto", "the", BUSTLE],
+        "tests/expected/bustle.the.out",
+    )
+}
+
+#[test]
+fn color_context() -> TestResult {
+    run(
+        &["--color=always", "-n", "-C1", "the", BUSTLE, FOX],
+        "tests/expected/all.the.n.C1.color.out",
+    )?;
+    // -v の場合は前後の行のマッチを色付けする
+    run(
+        &["--color=always", "-v", "-A1", "the", BUSTLE],
+        "tests/expected/bustle.the.v.A1.color.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn context() -> TestResult {
+    run(
+        &["-n", "-A1", "-B2", "death|love", BUSTLE],
+        "tests/expected/bustle.deathlove.n.A1.B2.out",
+    )?;
+    run(&["-C1", "Th", BUSTLE, FOX], "tests/expected/all.Th.C1.out")?;
+    run(
+        &["-v", "-B1", "e", BUSTLE],
+        "tests/expected/bustle.e.v.B1.out",
+    )?;
+    // -A, -B は -C より優先する
+    run(
+        &["-C5", "-A1", "-B2", "-n", "death|love", BUSTLE],
+        "tests/expected/bustle.deathlove.n.A1.B2.out",
+    )
+}
+
+#[test]
+fn context_separator_only() -> TestResult {
+    // 行数が 0 でも、連続しないグループの間には区切りを出力する
+    run(&["-A0", "Th", BUSTLE], "tests/expected/bustle.Th.A0.out")?;
+    // -o の場合は前後の行を出力せず、区切りのみ出力する
+    run(
+        &["-o", "-A1", "Th|Un", BUSTLE],
+        "tests/expected/bustle.ThUn.o.A1.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn parallel_order() -> TestResult {
+    // 多数のファイルを作成し、スレッド数によらず同じ順序で出力されることを確認
+    let dir = std::env::temp_dir().join(format!("regex-parallel-{}", std::process::id()));
+    fs::create_dir_all(dir.join("sub"))?;
+    let mut expected = String::new();
+    for i in 0..50 {
+        let sub = if i % 3 == 0 { "sub/" } else { "" };
+        let name = format!("{}/{sub}{i:02}.txt", dir.display());
+        fs::write(&name, format!("line {i}\nmatch {i}\nother\n"))?;
+        expected.push_str(&format!("{name}:2:match {i}\n"));
+    }
+
+    let dir_name = dir.display().to_string();
+    let mut outputs = Vec::new();
+    for threads in ["1", "4", "16"] {
+        let output = exec(&["-rn", "-j", threads, "match", &dir_name], None)?;
+        assert_eq!(output.status.code(), Some(0));
+        outputs.push(String::from_utf8(output.stdout)?);
+    }
+    fs::remove_dir_all(&dir)?;
+
+    // 名前順に走査するため、sub/ 以下のファイルは最後に出力される
+    let mut sorted = expected.lines().collect::<Vec<_>>();
+    sorted.sort_by_key(|l| l.contains("/sub/"));
+    let expected = sorted.join("\n") + "\n";
+    for output in outputs {
+        assert_eq!(output, expected);
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn large_file() -> TestResult {
+    // 一度に読み込む範囲の境界をまたいでも、行番号と前後の行が正しく出力されることを確認
+    let path = std::env::temp_dir().join(format!("regex-large-{}.txt", std::process::id()));
+    let mut text = String::new();
+    let mut expected = String::new();
+    for i in 1..=40000 {
+        let line = if i % 7 == 0 {
+            format!("match {i}")
+        } else {
+            format!("line {i}")
+        };
+        text.push_str(&line);
+        text.push('\n');
+        if i % 7 == 0 {
+            if !expected.is_empty() {
+                expected.push_str("--\n");
+            }
+            expected.push_str(&format!("{}-line {}\n{i}:{line}\n", i - 1, i - 1));
+        }
+    }
+    fs::write(&path, text)?;
+    let output = exec(&["-n", "-B1", "match", &path.display().to_string()], None);
+    fs::remove_file(&path)?;
+
+    let output = output?;
+    assert_eq!(String::from_utf8(output.stdout)?, expected);
+    assert_eq!(output.status.code(), Some(0));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[cfg(unix)]
+#[test]
+fn recursive_symlinks() -> TestResult {
+    use std::os::unix::fs::symlink;
+
+    // 走査中のシンボリックリンクはたどらないため、ループしていても停止する
+    let dir = std::env::temp_dir().join(format!("regex-symlink-{}", std::process::id()));
+    fs::create_dir_all(dir.join("sub"))?;
+    fs::write(dir.join("sub/a.txt"), "match\n")?;
+    symlink("..", dir.join("sub/loop"))?;
+    symlink("a.txt", dir.join("sub/link.txt"))?;
+
+    let dir_name = dir.display().to_string();
+    let output = exec(&["-r", "match", &dir_name], None);
+    let linked = exec(&["match", &format!("{dir_name}/sub/link.txt")], None);
+    fs::remove_dir_all(&dir)?;
+
+    let output = output?;
+    assert_eq!(
+        String::from_utf8(output.stdout)?,
+        format!("{dir_name}/sub/a.txt:match\n")
+    );
+    assert!(output.stderr.is_empty());
+    assert_eq!(output.status.code(), Some(0));
+
+    // コマンドラインで指定したシンボリックリンクはたどる
+    assert_eq!(String::from_utf8(linked?.stdout)?, "match\n");
+    Ok(())
+}
+
+#[test]
+fn stats() -> TestResult {
+    let output = exec(&["--stats", "-c", "the", BUSTLE, EMPTY, FOX], None)?;
+    assert_eq!(
+        String::from_utf8(output.stdout)?,
+        fs::read_to_string("tests/expected/all.the.c.out")?
+    );
+    assert!(String::from_utf8(output.stderr)?
+        .starts_with("3 files searched, 2 files matched, 2 lines matched in "));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn binary_file() -> TestResult {
+    const BINARY: &str = "tests/inputs/binary.bin";
+
+    // NUL 文字を含むファイルは行を出力せず、マッチしたことのみ報告する
+    let output = exec(&["abc", BINARY], None)?;
+    assert_eq!(output.status.code(), Some(0));
+    assert!(output.stdout.is_empty());
+    assert_eq!(
+        String::from_utf8(output.stderr)?,
+        format!("regex: {BINARY}: binary file matches\n")
+    );
+
+    let output = exec(&["-c", "abc", BINARY], None)?;
+    assert_eq!(String::from_utf8(output.stdout)?, "3\n");
+
+    // -a の場合はテキストとして扱う
+    let output = exec(&["-an", "y abc", BINARY], None)?;
+    assert_eq!(output.stdout, b"2:x\0y abc\n");
+    Ok(())
+}
+
+#[test]
+fn invalid_utf8() -> TestResult {
+    const LATIN1: &str = "tests/inputs/latin1.txt";
+
+    // 不正なバイトを含む行も、そのまま出力する
+    let output = exec(&["-n", "the|latin", LATIN1], None)?;
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(output.stdout, b"1:caf\xe9 latin-1\n2:\xff\xfe the end\n");
+
+    let output = exec(&["--color=always", "-o", "end", LATIN1], None)?;
+    assert_eq!(output.stdout, b"\x1b[01;31m\x1b[Kend\x1b[m\x1b[K\n");
+    Ok(())
+}
+
+#[test]
+fn long_stdin() -> TestResult {
+    // 読み込みのブロックをまたぐ長い行
+    let input = format!("{}fox\nb\n{}", "a".repeat(600_000), "c".repeat(300_000));
+    let output = exec(&["-n", "b|fox", "-"], Some(&input))?;
+    assert_eq!(
+        String::from_utf8(output.stdout)?,
+        format!("1:{}fox\n2:b\n", "a".repeat(600_000))
+    );
+
+    let output = exec(&["-c", "c+"], Some(&input))?;
+    assert_eq!(String::from_utf8(output.stdout)?, "1\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn include_exclude() -> TestResult {
+    let output = exec(
+        &[
+            "-rl",
+            "-i",
+            "--include=*.txt",
+            "--exclude=sub/*",
+            "the",
+            DIR,
+        ],
+        None,
+    )?;
+    assert_eq!(String::from_utf8(output.stdout)?, format!("{DIR}/a.txt\n"));
+
+    let output = exec(
+        &["-rl", "-i", "--include=**/dir/**/?.txt", "the", "tests"],
+        None,
+    )?;
+    assert_eq!(
+        String::from_utf8(output.stdout)?,
+        format!("{DIR}/a.txt\n{DIR}/sub/b.txt\n")
+    );
+
+    // コマンドライン引数のファイルにも適用する
+    let output = exec(&["-c", "--exclude=[!f]*.txt", "the", BUSTLE, FOX], None)?;
+    assert_eq!(String::from_utf8(output.stdout)?, format!("{FOX}:1\n"));
+
+    let output = exec(&["--include=[a", "the", BUSTLE], None)?;
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8(output.stderr)?.contains("no right bracket"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn posix_syntax() -> TestResult {
+    run(
+        &["-E", "-o", "th(e|ere)?|[[:upper:]][a-z]{2,}", BUSTLE],
+        "tests/expected/bustle.posix.E.o.out",
+    )?;
+    run(
+        &["-G", "-n", "e\\(a\\|ar\\)[[:alpha:]]*$", BUSTLE],
+        "tests/expected/bustle.posix.G.n.out",
+    )?;
+    run(
+        &["-E", "--color=always", "in|ing|^[^ ]+", BUSTLE],
+        "tests/expected/bustle.posix.E.color.out",
+    )?;
+
+    // POSIX の構文では最左最長一致、独自の構文では最左優先一致
+    let output = exec(&["-o", "a|ab"], Some("abc\n"))?;
+    assert_eq!(String::from_utf8(output.stdout)?, "a\n");
+    let output = exec(&["-E", "-o", "a|ab"], Some("abc\n"))?;
+    assert_eq!(String::from_utf8(output.stdout)?, "ab\n");
+
+    // 後に指定した構文が優先される
+    let output = exec(&["-E", "-G", "-o", "a\\{2\\}"], Some("aaa\n"))?;
+    assert_eq!(String::from_utf8(output.stdout)?, "aa\n");
+
+    let output = exec(&["-E", "a{2,1}", BUSTLE], None)?;
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8(output.stderr)?.contains("invalid interval"));
+
+    // POSIX の構文は後方参照と \w などのクラスの略記を持たない
+    for args in [
+        &["-E", "(a)\\1"][..],
+        &["-G", "\\(a\\)\\1"],
+        &["-E", "\\w+"],
+    ] {
+        // 標準入力を読む前に終了するため、入力は与えない
+        let output = exec(args, None)?;
+        assert_eq!(output.status.code(), Some(2), "{args:?}");
+        assert!(
+            String::from_utf8(output.stderr)?.contains("invalid escape"),
+            "{args:?}"
+        );
+    }
+
+    // 独自の構文では使える
+    let output = exec(&["-o", "(\\w+) \\1"], Some("ab ab\nab ac\n"))?;
+    assert_eq!(String::from_utf8(output.stdout)?, "ab ab\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn explain() -> TestResult {
+    let output = exec(&["--explain", "a(bc)+|d"], None)?;
+    assert_eq!(
+        String::from_utf8(output.stdout)?,
+        "a(bc)+|d\n\
+         either:\n  in sequence:\n    'a'\n    one or more of:\n\
+         \x20     in sequence:\n        'b'\n        'c'\n  'd'\n"
+    );
+    assert_eq!(output.status.code(), Some(0));
+
+    let output = exec(
+        &["--explain", "a?|\\*", "--lang", "ja", "--color=always"],
+        None,
+    )?;
+    assert_eq!(
+        String::from_utf8(output.stdout)?,
+        "\x1b[32m\x1b[Ka\x1b[m\x1b[K\x1b[33m\x1b[K?\x1b[m\x1b[K\x1b[34m\x1b[K|\x1b[m\x1b[K\
+         \x1b[36m\x1b[K\\*\x1b[m\x1b[K\n\
+         \x1b[34m\x1b[K次のいずれか:\x1b[m\x1b[K\n\
+         \x20 \x1b[33m\x1b[K省略可能:\x1b[m\x1b[K\n\
+         \x20   \x1b[32m\x1b[K「a」\x1b[m\x1b[K\n\
+         \x20 \x1b[32m\x1b[K「*」\x1b[m\x1b[K\n"
+    );
+
+    // 最初の位置引数は常にパターンで、explain という文字列も検索できる
+    let output = exec(&["explain"], Some("explain\nx\n"))?;
+    assert_eq!(String::from_utf8(output.stdout)?, "explain\n");
+    let output = exec(&["explain", "-"], Some("explain\n"))?;
+    assert_eq!(String::from_utf8(output.stdout)?, "explain\n");
+
+    // --explain は検索のパターンと同時に指定できない
+    let output = exec(&["--explain", "a", "b"], None)?;
+    assert_eq!(output.status.code(), Some(2));
+    let output = exec(&["--lang", "ja", "a"], None)?;
+    assert_eq!(output.status.code(), Some(2));
+
+    let output = exec(&["--explain", "a("], None)?;
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8(output.stderr)?.contains("no right parenthesis"));
+    Ok(())
+}
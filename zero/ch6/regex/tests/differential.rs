@@ -0,0 +1,288 @@
+//! 評価器の差分テスト
+//!
+//! ランダムに生成した正規表現と文字列について、すべての評価モードの結果と、
+//! 文字列中の位置の集合をシミュレートする参照実装の結果を比較する。
+//! 結果が一致しない場合は、正規表現と文字列を縮小した最小の反例を報告する。
+//!
+//! 生成する正規表現は POSIX 拡張正規表現としても有効なため、
+//! POSIX の構文でパースした場合の結果と、最左最長一致の範囲も比較する。
+use regex::{Backend, RegexBuilder, Syntax, Xor64};
+
+/// 比較する評価モード (名前, 構文, 最左最長一致かどうか, is_depth, 評価器の実装)
+const MODES: &[(&str, Syntax, bool, bool, Backend)] = &[
+    ("depth", Syntax::Native, false, true, Backend::Interpreter),
+    ("width", Syntax::Native, false, false, Backend::Interpreter),
+    ("longest", Syntax::Native, true, true, Backend::Interpreter),
+    ("ere", Syntax::Ere, true, true, Backend::Interpreter),
+    ("threaded", Syntax::Native, false, true, Backend::Threaded),
+];
+
+/// テスト用の正規表現
+#[derive(Debug, Clone)]
+enum Expr {
+    Char(char),
+    Seq(Vec<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Star(Box<Expr>),
+    Plus(Box<Expr>),
+    Question(Box<Expr>),
+}
+
+impl Expr {
+    /// 正規表現の文字列に変換
+    fn to_pattern(&self) -> String {
+        match self {
+            Expr::Char(c) => c.to_string(),
+            Expr::Seq(v) => v.iter().map(|e| e.to_operand()).collect(),
+            Expr::Or(e1, e2) => format!("{}|{}", e1.to_pattern(), e2.to_pattern()),
+            Expr::Star(e) => format!("{}*", e.to_operand()),
+            Expr::Plus(e) => format!("{}+", e.to_operand()),
+            Expr::Question(e) => format!("{}?", e.to_operand()),
+        }
+    }
+
+    /// 連接や限量子の対象となる場合は、必要に応じてカッコで囲む
+    fn to_operand(&self) -> String {
+        match self {
+            Expr::Char(_) => self.to_pattern(),
+            _ => format!("({})", self.to_pattern()),
+        }
+    }
+
+    /// 参照実装
+    ///
+    /// starts に含まれる各位置から照合を始めた場合に、照合を終えられる位置の集合を返す
+    fn ends(&self, line: &[char], starts: &[bool]) -> Vec<bool> {
+        match self {
+            Expr::Char(c) => {
+                let mut result = vec![false; starts.len()];
+                for (i, _) in starts.iter().enumerate().filter(|(_, s)| **s) {
+                    if line.get(i) == Some(c) {
+                        result[i + 1] = true;
+                    }
+                }
+                result
+            }
+            Expr::Seq(v) => v.iter().fold(starts.to_vec(), |acc, e| e.ends(line, &acc)),
+            Expr::Or(e1, e2) => union(&e1.ends(line, starts), &e2.ends(line, starts)),
+            Expr::Question(e) => union(starts, &e.ends(line, starts)),
+            Expr::Star(e) => {
+                // 不動点に達するまで e を繰り返し適用
+                let mut result = starts.to_vec();
+                loop {
+                    let next = union(&result, &e.ends(line, &result));
+                    if next == result {
+                        return result;
+                    }
+                    result = next;
+                }
+            }
+            Expr::Plus(e) => Expr::Star(e.clone()).ends(line, &e.ends(line, starts)),
+        }
+    }
+
+    /// 文字列の先頭から照合し、いずれかの位置で照合を終えられればマッチ
+    fn is_match(&self, line: &[char]) -> bool {
+        let mut starts = vec![false; line.len() + 1];
+        starts[0] = true;
+        self.ends(line, &starts).contains(&true)
+    }
+
+    /// 最左最長一致の範囲
+    ///
+    /// 照合を始められる最も左の位置と、そこから照合を終えられる最も後ろの位置を返す
+    fn find_longest(&self, line: &[char]) -> Option<(usize, usize)> {
+        (0..=line.len()).find_map(|i| {
+            let mut starts = vec![false; line.len() + 1];
+            starts[i] = true;
+            let ends = self.ends(line, &starts);
+            ends.iter().rposition(|e| *e).map(|end| (i, end))
+        })
+    }
+
+    /// 縮小した候補を返す
+    fn shrink(&self) -> Vec<Expr> {
+        let mut result = Vec::new();
+        match self {
+            Expr::Char(_) => (),
+            Expr::Seq(v) => {
+                for i in 0..v.len() {
+                    result.push(v[i].clone());
+                    if v.len() > 1 {
+                        let mut w = v.clone();
+                        w.remove(i);
+                        result.push(Expr::Seq(w));
+                    }
+                    for e in v[i].shrink() {
+                        let mut w = v.clone();
+                        w[i] = e;
+                        result.push(Expr::Seq(w));
+                    }
+                }
+            }
+            Expr::Or(e1, e2) => {
+                result.push((**e1).clone());
+                result.push((**e2).clone());
+                for e in e1.shrink() {
+                    result.push(Expr::Or(Box::new(e), e2.clone()));
+                }
+                for e in e2.shrink() {
+                    result.push(Expr::Or(e1.clone(), Box::new(e)));
+                }
+            }
+            Expr::Star(e) | Expr::Plus(e) | Expr::Question(e) => {
+                result.push((**e).clone());
+                for s in e.shrink() {
+                    result.push(match self {
+                        Expr::Star(_) => Expr::Star(Box::new(s)),
+                        Expr::Plus(_) => Expr::Plus(Box::new(s)),
+                        _ => Expr::Question(Box::new(s)),
+                    });
+                }
+            }
+        }
+        result
+    }
+}
+
+fn union(a: &[bool], b: &[bool]) -> Vec<bool> {
+    a.iter().zip(b).map(|(x, y)| *x || *y).collect()
+}
+
+const CHARS: &[char] = &['a', 'b', 'c'];
+
+/// size を入れ子の深さの上限として正規表現を生成
+fn gen_expr(rng: &mut Xor64, size: usize) -> Expr {
+    match if size == 0 { 0 } else { rng.below(7) } {
+        0 | 1 => Expr::Char(CHARS[rng.below(CHARS.len())]),
+        2 => Expr::Seq(
+            (0..=rng.below(3))
+                .map(|_| gen_expr(rng, size - 1))
+                .collect(),
+        ),
+        3 => Expr::Or(
+            Box::new(gen_expr(rng, size - 1)),
+            Box::new(gen_expr(rng, size - 1)),
+        ),
+        4 => Expr::Star(Box::new(gen_expr(rng, size - 1))),
+        5 => Expr::Plus(Box::new(gen_expr(rng, size - 1))),
+        _ => Expr::Question(Box::new(gen_expr(rng, size - 1))),
+    }
+}
+
+fn gen_line(rng: &mut Xor64) -> Vec<char> {
+    (0..rng.below(8))
+        .map(|_| CHARS[rng.below(CHARS.len())])
+        .collect()
+}
+
+/// 評価モードの名前、先頭からマッチするか、最左最長一致の範囲
+type Outcome = (&'static str, bool, Option<(usize, usize)>);
+
+/// 各評価モードと参照実装の結果を比較し、一致しない場合は結果の一覧を返す
+///
+/// 範囲は最左最長一致のモードのみ比較する。
+fn diff(expr: &Expr, line: &[char]) -> Option<Vec<Outcome>> {
+    let text: String = line.iter().collect();
+    let longest = expr.find_longest(line);
+
+    let mut results = vec![("reference", expr.is_match(line), longest)];
+    for (name, syntax, is_longest, is_depth, backend) in MODES {
+        let re = RegexBuilder::new(&expr.to_pattern())
+            .syntax(*syntax)
+            .longest(*is_longest)
+            .depth_first(*is_depth)
+            .backend(*backend)
+            .build();
+        let re = match re {
+            Ok(re) => re,
+            Err(e) => panic!("{}: {e}", expr.to_pattern()),
+        };
+
+        // Program は命令列を解釈するため、スレッデッドコードは先頭から始まるマッチの有無で比較する
+        let result = match backend {
+            Backend::Interpreter => re.program().do_matching(&text, *is_depth),
+            Backend::Threaded => re.find(&text).map(|m| m.is_some_and(|m| m.start() == 0)),
+        }
+        .expect("evaluation error");
+        let found = if *is_longest {
+            re.find(&text)
+                .expect("evaluation error")
+                .map(|m| (m.start(), m.end()))
+        } else {
+            longest
+        };
+        results.push((name, result, found));
+    }
+
+    if results
+        .iter()
+        .all(|(_, r, f)| *r == results[0].1 && *f == longest)
+    {
+        None
+    } else {
+        Some(results)
+    }
+}
+
+/// 反例を縮小する
+fn minimize(mut expr: Expr, mut line: Vec<char>) -> (Expr, Vec<char>) {
+    loop {
+        if let Some(e) = expr.shrink().into_iter().find(|e| diff(e, &line).is_some()) {
+            expr = e;
+            continue;
+        }
+
+        let smaller = (0..line.len()).map(|i| {
+            let mut l = line.clone();
+            l.remove(i);
+            l
+        });
+        if let Some(l) = smaller.into_iter().find(|l| diff(&expr, l).is_some()) {
+            line = l;
+            continue;
+        }
+
+        return (expr, line);
+    }
+}
+
+#[test]
+fn test_reference() {
+    // 参照実装自体の確認
+    let e = Expr::Seq(vec![Expr::Plus(Box::new(Expr::Char('a'))), Expr::Char('b')]);
+    assert!(e.is_match(&['a', 'a', 'b']));
+    assert!(!e.is_match(&['b']));
+    assert_eq!(e.find_longest(&['b', 'a', 'a', 'b', 'b']), Some((1, 4)));
+    assert_eq!(e.find_longest(&['a']), None);
+    assert_eq!(e.to_pattern(), "(a+)b");
+}
+
+#[test]
+fn test_differential() {
+    let mut rng = Xor64::new(42);
+    let mut compared = 0;
+
+    for i in 0..3000 {
+        let expr = gen_expr(&mut rng, 1 + i % 4);
+        for _ in 0..5 {
+            let line = gen_line(&mut rng);
+            if diff(&expr, &line).is_some() {
+                let (expr, line) = minimize(expr, line);
+                let results = diff(&expr, &line).unwrap();
+                panic!(
+                    "counterexample: pattern = {:?}, line = {:?}, results = {:?}",
+                    expr.to_pattern(),
+                    line.iter().collect::<String>(),
+                    results
+                );
+            }
+            if regex::compile(&expr.to_pattern()).is_ok() {
+                compared += 1;
+            }
+        }
+    }
+
+    // 検証で弾かれる正規表現ばかりになっていないことを確認
+    assert!(compared > 5000, "compared = {compared}");
+}